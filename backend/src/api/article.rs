@@ -1,11 +1,16 @@
 use std::ops::Try;
 
-use actix_web::{get, post, put, web};
+use actix_web::{get, patch, post, put, web};
 use einkaufsliste::model::article::Article;
+use einkaufsliste::model::patch::ArticlePatchV1;
+use einkaufsliste::model::requests::TransferOwnershipV1;
 use einkaufsliste::model::user::User;
+use einkaufsliste::model::{Permission, Role};
 
+use super::user::find_user_id;
 use crate::db::RawRkyvStore;
 use crate::response::Response;
+use crate::util::idempotency::{IdempotencyKey, IdempotencyStore};
 use crate::util::identity_ext::AuthenticatedUser;
 use crate::DbState;
 
@@ -16,7 +21,7 @@ pub(crate) async fn get_article_by_id(
   identity: AuthenticatedUser,
 ) -> Response<Article> {
   // check if the user has access:
-  state.verify_access::<Article, User>(*article_id, identity.id)?;
+  state.verify_access::<Article, User>(*article_id, identity.id, Permission::Read)?;
 
   let article = unsafe {
     <sled::Tree as RawRkyvStore<Article, 4096>>::get_unchecked(&state.article_db, *article_id)?
@@ -30,28 +35,64 @@ async fn update_article(
   data: web::Data<DbState>,
   identity: AuthenticatedUser,
 ) -> Response<()> {
-  data.verify_access::<Article, User>(article.id, identity.id)?;
+  data.verify_access::<Article, User>(article.id, identity.id, Permission::Write)?;
 
   data.store_unlisted(&article, article.id)?;
   Response::empty()
 }
 
+#[patch("/article/{id}")]
+pub(crate) async fn patch_article(
+  article_id: web::Path<u64>,
+  param: ArticlePatchV1,
+  data: web::Data<DbState>,
+  identity: AuthenticatedUser,
+) -> Response<Article> {
+  data.verify_access::<Article, User>(*article_id, identity.id, Permission::Write)?;
+
+  let (_, article) = data.modify(*article_id, |article: &mut Article| param.apply(article))?;
+
+  Response::from(article)
+}
+
 #[post("/article")]
 pub(crate) async fn store_article(
   mut article: Article,
   data: web::Data<DbState>,
   identity: AuthenticatedUser,
+  idempotency: web::Data<IdempotencyStore>,
+  key: IdempotencyKey,
 ) -> Response<u64> {
   let user_id = identity.id;
 
-  // variable not inlineable because...??????? fuck you
-  let new_id = data.db.generate_id()?;
-  article.id = new_id;
+  let new_id = idempotency.run(&key, user_id, "POST /article", || {
+    // variable not inlineable because...??????? fuck you
+    let new_id = data.db.generate_id()?;
+    article.id = new_id;
+
+    data.store_unlisted(&article, new_id)?;
 
-  data.store_unlisted(&article, new_id)?;
+    // since this is a new object we need to create an acl for this
+    data.create_acl::<Article, User>(new_id, user_id)?;
 
-  // since this is a new object we need to create an acl for this
-  data.create_acl::<Article, User>(new_id, user_id)?;
+    Ok(new_id)
+  })?;
 
   Response::from(new_id)
 }
+
+/// The previous owner stays on as editor of the article.
+#[put("/article/{id}/owner")]
+pub(crate) async fn transfer_article(
+  article_id: web::Path<u64>,
+  param: TransferOwnershipV1,
+  data: web::Data<DbState>,
+  identity: AuthenticatedUser,
+) -> Response<()> {
+  data.verify_access::<Article, User>(*article_id, identity.id, Permission::Manage)?;
+
+  let new_owner = find_user_id(&data, &param.new_owner)?;
+  data.transfer_ownership::<Article>(*article_id, new_owner, Role::Editor)?;
+
+  Response::empty()
+}
@@ -0,0 +1,162 @@
+use actix_web::{post, web};
+use einkaufsliste::model::activity::ActivityKind;
+use einkaufsliste::model::article::Article;
+use einkaufsliste::model::batch::{BatchCommand, BatchResult, BatchV1};
+use einkaufsliste::model::item::Item;
+use einkaufsliste::model::list::List;
+use einkaufsliste::model::requests::{DeleteItem, StoreItemAttached};
+use einkaufsliste::model::user::User;
+use einkaufsliste::model::Permission;
+
+use super::item::record_item_update;
+use crate::db::{BatchChange, BatchOperation, DbError, DbState};
+use crate::response::{Response, ResponseError};
+use crate::util::errors::bad_request;
+use crate::util::identity_ext::AuthenticatedUser;
+
+/// The whole batch runs in one transaction, so its size is limited
+const MAX_BATCH_SIZE: usize = 100;
+
+/**
+Executes the commands in order and returns one result per command.
+The permissions for all commands are checked before any of them is applied, and if one of them fails, none are.
+*/
+#[post("/batch")]
+pub(crate) async fn execute_batch(
+  param: BatchV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<BatchResult>> {
+  if param.commands.len() > MAX_BATCH_SIZE {
+    return bad_request(format!("Batches are limited to {MAX_BATCH_SIZE} commands")).into();
+  }
+
+  let mut operations = Vec::with_capacity(param.commands.len());
+  let mut results = Vec::with_capacity(param.commands.len());
+  for command in param.commands {
+    let (operation, result) = prepare_command(&state, command, user.id)?;
+    operations.push(operation);
+    results.push(result);
+  }
+
+  for change in state.execute_batch(&operations)? {
+    record_change(&state, change, user.id)?;
+  }
+
+  Response::from(results)
+}
+
+/// Verifies the permissions for the command and assigns ids to new objects
+fn prepare_command(
+  state: &DbState,
+  command: BatchCommand,
+  user_id: u64,
+) -> Result<(BatchOperation, BatchResult), ResponseError> {
+  let prepared = match command {
+    BatchCommand::StoreItem(StoreItemAttached {
+      mut item, list_id, ..
+    }) => {
+      state.verify_access::<List, User>(list_id, user_id, Permission::Write)?;
+      item.id = state.db.generate_id()?;
+
+      let result = BatchResult::Created(item.id);
+      (BatchOperation::AddItem { list_id, item }, result)
+    }
+    BatchCommand::UpdateItem(item) => {
+      // shoppers may only check items, so any other modification is discarded for them
+      let only_checked = match state.verify_item_access(item.id, user_id, Permission::Write) {
+        Ok(()) => false,
+        Err(DbError::Mismatch) => {
+          state.verify_item_access(item.id, user_id, Permission::Check)?;
+          true
+        }
+        Err(e) => return Err(e.into()),
+      };
+
+      (
+        BatchOperation::UpdateItem { item, only_checked },
+        BatchResult::Done,
+      )
+    }
+    BatchCommand::DeleteItem(DeleteItem { list_id, item_id }) => {
+      state.verify_access::<List, User>(list_id, user_id, Permission::Write)?;
+      state.verify_access::<Item, User>(item_id, user_id, Permission::Write)?;
+
+      let operation = BatchOperation::TrashItem {
+        list_id,
+        item_id,
+        deleted_by: user_id,
+      };
+      (operation, BatchResult::Done)
+    }
+    BatchCommand::UpdateList(list) => {
+      state.verify_access::<List, User>(list.id, user_id, Permission::Write)?;
+      // prevents leaking information about items the user does not have access to
+      for item_id in &list.items {
+        state.verify_access::<Item, User>(*item_id, user_id, Permission::Read)?;
+      }
+
+      (BatchOperation::UpdateList(list), BatchResult::Done)
+    }
+    BatchCommand::StoreArticle(mut article) => {
+      article.id = state.db.generate_id()?;
+
+      let result = BatchResult::Created(article.id);
+      (
+        BatchOperation::AddArticle {
+          article,
+          owner: user_id,
+        },
+        result,
+      )
+    }
+    BatchCommand::UpdateArticle(article) => {
+      state.verify_access::<Article, User>(article.id, user_id, Permission::Write)?;
+
+      (BatchOperation::UpdateArticle(article), BatchResult::Done)
+    }
+  };
+
+  Ok(prepared)
+}
+
+/// Records the same activity as the endpoints the commands stand for
+fn record_change(state: &DbState, change: BatchChange, user_id: u64) -> Result<(), ResponseError> {
+  match change {
+    BatchChange::ItemAdded { list_id, item } => state.record_activity(
+      list_id,
+      Some(user_id),
+      ActivityKind::ItemAdded {
+        item_id: item.id,
+        name: item.name,
+      },
+    )?,
+    BatchChange::ItemUpdated { previous, item } => {
+      let list_id = state.list_of_item(item.id)?;
+      record_item_update(state, list_id, &previous, item, user_id)?;
+    }
+    BatchChange::ItemTrashed { list_id, item } => state.record_activity(
+      list_id,
+      Some(user_id),
+      ActivityKind::ItemDeleted {
+        item_id: item.id,
+        name: item.name,
+      },
+    )?,
+    BatchChange::ListUpdated { previous, list } => {
+      if list.name != previous.name {
+        state.record_activity(
+          list.id,
+          Some(user_id),
+          ActivityKind::ListRenamed {
+            from: previous.name,
+            to: list.name,
+          },
+        )?;
+      }
+    }
+    BatchChange::ArticleStored => {}
+  }
+
+  Ok(())
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use actix_web::{delete, get, post, web};
+use einkaufsliste::model::article::Article;
+use einkaufsliste::model::category::{Category, CategoryGroup};
+use einkaufsliste::model::household::Household;
+use einkaufsliste::model::item::Item;
+use einkaufsliste::model::requests::CreateCategoryV1;
+
+use crate::db::{self, DbError, DbState};
+use crate::response::{Response, ResponseError};
+use crate::util::errors::bad_request;
+use crate::util::identity_ext::AuthenticatedUser;
+
+/// The built-in categories followed by the custom ones of the user
+#[get("/category")]
+pub(crate) async fn get_categories(
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<Category>> {
+  let custom_ids =
+    <db::DbState as db::ObjectStore<Category, sled::Tree, 512>>::object_list(&state, user.id)?;
+
+  let mut categories = Category::builtin();
+  for id in custom_ids.list {
+    categories.push(state.get_unchecked(id)?);
+  }
+
+  Response::from(categories)
+}
+
+#[post("/category")]
+pub(crate) async fn create_category(
+  param: CreateCategoryV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Category> {
+  let name = param.name.trim();
+  if name.is_empty() {
+    return bad_request("Category name must not be empty").into();
+  }
+
+  let category = Category {
+    id: state.db.generate_id()?,
+    name: name.to_owned(),
+    owner: Some(user.id),
+  };
+  state.store_listed(&category, user.id, category.id)?;
+
+  Response::from(category)
+}
+
+/// Items of a deleted category show up as uncategorized
+#[delete("/category/{id}")]
+pub(crate) async fn delete_category(
+  category_id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  if Category::is_builtin(*category_id) {
+    return bad_request("Built-in categories cannot be deleted").into();
+  }
+
+  let category: Category = state.get_unchecked(*category_id)?;
+  if category.owner != Some(user.id) {
+    return ResponseError::ErrorNotFound.into();
+  }
+
+  state.unlist::<Category>(user.id, *category_id)?;
+  state.delete::<Category>(*category_id)?;
+
+  Response::empty()
+}
+
+/**
+Groups the items by their category, falling back to the category of their article.
+The built-in categories come first in their fixed order, followed by custom categories by name and uncategorized items.
+
+Custom categories are only resolved if they belong to someone with access to the list, anything else would reveal the
+categories of other users. Items with such a category show up as uncategorized.
+*/
+pub(crate) fn group_by_category(
+  state: &DbState,
+  list_id: u64,
+  items: &[Item],
+) -> Result<Vec<CategoryGroup>, ResponseError> {
+  let builtin = Category::builtin();
+  let category_owners = users_with_access(state, list_id)?;
+  // lists contain many items of the same articles and categories
+  let mut article_categories: HashMap<u64, Option<u64>> = HashMap::new();
+  let mut categories: HashMap<u64, Option<Category>> = HashMap::new();
+  let mut groups: Vec<CategoryGroup> = Vec::new();
+
+  for item in items {
+    let category_id = item_category_id(state, item, &mut article_categories)?;
+
+    let category = match category_id {
+      Some(id) if Category::is_builtin(id) => builtin.iter().find(|c| c.id == id).cloned(),
+      Some(id) => match categories.get(&id) {
+        Some(category) => category.clone(),
+        None => {
+          let category = match state.get_unchecked::<Category>(id) {
+            Ok(category)
+              if category
+                .owner
+                .is_some_and(|owner| category_owners.contains(&owner)) =>
+            {
+              Some(category)
+            }
+            Ok(_) => None,
+            Err(DbError::NotFound) => None,
+            Err(e) => return Err(e.into()),
+          };
+          categories.insert(id, category.clone());
+          category
+        }
+      },
+      None => None,
+    };
+
+    let category_id = category.as_ref().map(|category| category.id);
+    match groups
+      .iter_mut()
+      .find(|group| group.category.as_ref().map(|category| category.id) == category_id)
+    {
+      Some(group) => group.item_ids.push(item.id),
+      None => groups.push(CategoryGroup {
+        category,
+        item_ids: vec![item.id],
+      }),
+    }
+  }
+
+  groups.sort_by_cached_key(|group| match &group.category {
+    Some(category) if category.owner.is_none() => (0, category.id, String::new()),
+    Some(category) => (1, 0, category.name.to_lowercase()),
+    None => (2, 0, String::new()),
+  });
+
+  Ok(groups)
+}
+
+/// The owner and members of the list, including the members of its household
+fn users_with_access(state: &DbState, list_id: u64) -> Result<Vec<u64>, ResponseError> {
+  let acl = state.list_acl(list_id)?;
+
+  let mut users = vec![acl.owner];
+  users.extend(acl.members.iter().map(|member| member.user_id));
+  if let Some(household_id) = acl.household {
+    let household = state.get_unchecked::<Household>(household_id)?;
+    users.extend(household.members.iter().map(|member| member.user_id));
+  }
+
+  Ok(users)
+}
+
+/// The category of the item, falling back to the category of its article
+pub(crate) fn item_category_id(
+  state: &DbState,
+  item: &Item,
+  article_categories: &mut HashMap<u64, Option<u64>>,
+) -> Result<Option<u64>, ResponseError> {
+  let article_id = match (item.category_id, item.article_id) {
+    (Some(category_id), _) => return Ok(Some(category_id)),
+    (None, Some(article_id)) => article_id,
+    (None, None) => return Ok(None),
+  };
+
+  if let Some(category_id) = article_categories.get(&article_id) {
+    return Ok(*category_id);
+  }
+
+  let category_id = match state.get_unchecked::<Article>(article_id) {
+    Ok(article) => article.category_id,
+    Err(DbError::NotFound) => None,
+    Err(e) => return Err(e.into()),
+  };
+  article_categories.insert(article_id, category_id);
+
+  Ok(category_id)
+}
@@ -0,0 +1,138 @@
+use actix_web::{delete, get, post, put, web};
+use einkaufsliste::model::household::Household;
+use einkaufsliste::model::list::List;
+use einkaufsliste::model::requests::CreateHouseholdV1;
+use einkaufsliste::model::user::User;
+use einkaufsliste::model::{Member, Permission, Role};
+
+use crate::db::{self, DbError, DbState};
+use crate::response::{Response, ResponseError};
+use crate::util::identity_ext::AuthenticatedUser;
+
+#[post("/household")]
+pub(crate) async fn create_household(
+  param: CreateHouseholdV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Household> {
+  let id = state.db.generate_id()?;
+
+  let household = Household {
+    id,
+    name: param.name,
+    members: vec![Member {
+      user_id: user.id,
+      role: Role::Owner,
+    }],
+  };
+  state.store_listed(&household, user.id, id)?;
+
+  Response::from(household)
+}
+
+#[get("/household")]
+pub(crate) async fn get_users_households(
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<Household>> {
+  let household_ids =
+    <db::DbState as db::ObjectStore<Household, sled::Tree, 512>>::object_list(&state, user.id)?;
+
+  let households = household_ids
+    .list
+    .into_iter()
+    .map(|id| state.get_unchecked(id))
+    .collect::<Result<Vec<Household>, _>>()?;
+
+  Response::from(households)
+}
+
+/// The last owner cannot leave, the household has to be dissolved instead.
+#[delete("/household/{id}/member")]
+pub(crate) async fn leave_household(
+  id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  state.update_household(*id, |household| {
+    let role = household.role_of(user.id).ok_or(DbError::NotFound)?;
+    let owners = household
+      .members
+      .iter()
+      .filter(|member| member.role == Role::Owner)
+      .count();
+    if role == Role::Owner && owners == 1 {
+      return Err(DbError::Mismatch);
+    }
+
+    household.members.retain(|member| member.user_id != user.id);
+
+    Ok(())
+  })?;
+  state.unlist::<Household>(user.id, *id)?;
+
+  Response::empty()
+}
+
+/// Moved lists are handed back to their owners, lists created in the household to one of its owners.
+#[delete("/household/{id}")]
+pub(crate) async fn dissolve_household(
+  id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  verify_household_access(&state, *id, user.id, Permission::Manage)?;
+
+  state.dissolve_household(*id)?;
+
+  Response::empty()
+}
+
+/// Creates a list owned by the household, so all members get access according to their role in the household.
+#[post("/household/{id}/itemList")]
+pub(crate) async fn store_household_list(
+  household_id: web::Path<u64>,
+  mut param: List,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<u64> {
+  verify_household_access(&state, *household_id, user.id, Permission::Write)?;
+
+  let id = state.db.generate_id()?;
+  param.id = id;
+
+  state.create_household_list(&param, *household_id)?;
+
+  id.into()
+}
+
+/// Moves an existing list into the household. Direct members of the list keep their access.
+#[put("/household/{id}/itemList/{list_id}")]
+pub(crate) async fn move_list_to_household(
+  path: web::Path<(u64, u64)>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  let (household_id, list_id) = *path;
+  verify_household_access(&state, household_id, user.id, Permission::Write)?;
+  state.verify_access::<List, User>(list_id, user.id, Permission::Manage)?;
+
+  state.move_list_to_household(list_id, household_id)?;
+
+  Response::empty()
+}
+
+/// Fails with [`DbError::Mismatch`] unless the user has a role in the household that grants the permission
+pub(crate) fn verify_household_access(
+  state: &DbState,
+  household_id: u64,
+  user_id: u64,
+  permission: Permission,
+) -> Result<Household, ResponseError> {
+  let household: Household = state.get_unchecked(household_id)?;
+
+  match household.role_of(user_id) {
+    Some(role) if role.permits(permission) => Ok(household),
+    _ => Err(DbError::Mismatch.into()),
+  }
+}
@@ -0,0 +1,121 @@
+use actix_web::{post, web};
+use einkaufsliste::model::activity::ActivityKind;
+use einkaufsliste::model::invite::{Invite, InviteCode};
+use einkaufsliste::model::list::List;
+use einkaufsliste::model::requests::{CreateInviteV1, RedeemInviteV1};
+use einkaufsliste::model::session::Session;
+use einkaufsliste::model::user::User;
+use einkaufsliste::model::Permission;
+
+use super::household::verify_household_access;
+use crate::db::{DbError, DbState};
+use crate::response::{Response, ResponseError};
+use crate::util::config::BackendConfig;
+use crate::util::errors::bad_request;
+use crate::util::identity_ext::AuthenticatedUser;
+use crate::util::secret::{generate_secret, hash_secret};
+
+/// Creates a single-use invite code. If a list or household is given, the invited user gets access to it on signup.
+#[post("/invite")]
+pub(crate) async fn create_invite(
+  param: CreateInviteV1,
+  state: web::Data<DbState>,
+  config: web::Data<BackendConfig>,
+  user: AuthenticatedUser,
+) -> Response<InviteCode> {
+  if config.invites_admin_only {
+    let profile: User = state.get_unchecked(user.id)?;
+    if !config.admins.contains(&profile.name) {
+      return ResponseError::ErrorUnauthorized.into();
+    }
+  }
+
+  if let Some(list_id) = param.list_id {
+    state.verify_access::<List, User>(list_id, user.id, Permission::Manage)?;
+  }
+  if let Some(household_id) = param.household_id {
+    verify_household_access(&state, household_id, user.id, Permission::Manage)?;
+  }
+
+  let valid_for = param
+    .valid_for
+    .unwrap_or(config.invite_validity)
+    .min(config.invite_validity);
+  let invite = Invite {
+    created_by: user.id,
+    expires: Session::get_current_time() + valid_for as i64,
+    list_id: param.list_id,
+    household_id: param.household_id,
+    role: param.role,
+  };
+
+  let code = generate_secret();
+  state.invite_db.insert(
+    hash_secret(&code),
+    &*rkyv::to_bytes::<_, 64>(&invite).map_err(DbError::from)?,
+  )?;
+
+  Response::from(InviteCode {
+    code,
+    expires: invite.expires,
+  })
+}
+
+/// Lets existing users accept an invite to a list or household.
+#[post("/invite/redeem")]
+pub(crate) async fn accept_invite(
+  param: RedeemInviteV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  let invite = redeem_invite(&state, &param.code)?;
+  apply_invite(&state, &invite, user.id)?;
+
+  Response::empty()
+}
+
+/// Consumes the invite, so every code can only be used once - even if it turns out to be expired.
+pub(crate) fn redeem_invite(state: &DbState, code: &str) -> Result<Invite, ResponseError> {
+  let bytes = match state.invite_db.remove(hash_secret(code.trim()))? {
+    Some(bytes) => bytes,
+    None => return Err(bad_request("Invalid invite code")),
+  };
+  let invite = unsafe { rkyv::from_bytes_unchecked::<Invite>(&bytes) }?;
+
+  if invite.expires < Session::get_current_time() {
+    return Err(bad_request("Invite code has expired"));
+  }
+
+  Ok(invite)
+}
+
+/// Grants the access the invite was created for. Lists or households deleted in the meantime are skipped.
+pub(crate) fn apply_invite(
+  state: &DbState,
+  invite: &Invite,
+  user_id: u64,
+) -> Result<(), ResponseError> {
+  if let Some(list_id) = invite.list_id {
+    match state.grant_list_access(list_id, user_id, invite.role) {
+      Ok(()) => state.record_activity(
+        list_id,
+        Some(user_id),
+        ActivityKind::MemberAdded {
+          user_id,
+          role: invite.role,
+        },
+      )?,
+      Err(DbError::NotFound) => {}
+      Err(e) => return Err(e.into()),
+    }
+  }
+
+  if let Some(household_id) = invite.household_id {
+    match state.join_household(household_id, user_id, invite.role) {
+      Ok(()) | Err(DbError::NotFound) => {}
+      Err(e) => return Err(e.into()),
+    }
+  }
+
+  Ok(())
+}
@@ -1,25 +1,37 @@
-use actix_web::{delete, get, post, put, web};
+use actix_web::{delete, get, patch, post, put, web};
+use einkaufsliste::model::activity::{ActivityEvent, ActivityKind, ActivityQuery};
 use einkaufsliste::model::item::Item;
 use einkaufsliste::model::list::{FlatItemsList, List};
-use einkaufsliste::model::requests::{DeleteItem, MassStoreItems, StoreItemAttached};
+use einkaufsliste::model::patch::{ItemPatchV1, ListPatchV1};
+use einkaufsliste::model::quick_add::parse_items;
+use einkaufsliste::model::requests::{
+  DeleteItem, MassStoreItems, MoveItemV1, QuickAddV1, ReorderItemV1, StoreItemAttached,
+  TransferOwnershipV1,
+};
+use einkaufsliste::model::shop::{ShopOrder, SortedListQuery};
 use einkaufsliste::model::user::User;
+use einkaufsliste::model::{Permission, Role};
 use sled::transaction::{abort, TransactionalTree};
 use zerocopy::AsBytes;
 
-use super::user;
-use crate::db::DbError;
+use super::{category, shop, user};
+use crate::db::{AddedItem, DbError};
 use crate::response::{Response, ResponseError};
-use crate::util::errors::{abort_error, error, not_found};
+use crate::util::errors::{error, not_found};
+use crate::util::idempotency::{IdempotencyKey, IdempotencyStore};
 use crate::util::identity_ext::AuthenticatedUser;
 use crate::{db, DbState};
 
+const DEFAULT_ACTIVITY_PAGE_SIZE: usize = 50;
+const MAX_ACTIVITY_PAGE_SIZE: usize = 200;
+
 #[get("/item/{id}")]
 pub async fn get_item_by_id(
   id: web::Path<u64>,
   state: web::Data<DbState>,
   user: AuthenticatedUser,
 ) -> Response<Item> {
-  state.verify_access::<Item, User>(*id, user.id)?;
+  state.verify_item_access(*id, user.id, Permission::Read)?;
 
   let item: Item = state.get_unchecked(*id)?;
 
@@ -28,17 +40,100 @@ pub async fn get_item_by_id(
 
 #[post("/item/attached")]
 pub async fn store_item_attached(
-  mut param: StoreItemAttached,
+  param: StoreItemAttached,
   state: web::Data<DbState>,
   user: AuthenticatedUser,
+  idempotency: web::Data<IdempotencyStore>,
+  key: IdempotencyKey,
 ) -> Response<u64> {
-  let item_id = state.db.generate_id()?;
+  state.verify_access::<List, User>(param.list_id, user.id, Permission::Write)?;
+
+  let item_id = idempotency.run(&key, user.id, "POST /item/attached", || {
+    add_item(&state, param.list_id, param.item, param.merge, user.id)
+  })?;
+
+  Response::from(item_id)
+}
+
+/// Returns the ids of the new items in the order of the request
+#[post("/item/mass")]
+pub async fn store_items_mass(
+  param: MassStoreItems,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<u64>> {
+  state.verify_access::<List, User>(param.list_id, user.id, Permission::Write)?;
+
+  let added = state.add_items(param.list_id, &param.items, false)?;
+  let item_ids: Vec<u64> = added.iter().map(AddedItem::id).collect();
+
+  record_added_items(&state, param.list_id, param.items, added, user.id)?;
+
+  Response::from(item_ids)
+}
+
+/// Parses each line of the text into an item, e.g. `2kg Mehl`, and adds them in order
+#[post("/itemList/{id}/quickAdd")]
+pub async fn quick_add_items(
+  list_id: web::Path<u64>,
+  param: QuickAddV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<u64>> {
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Write)?;
+
+  let items = parse_items(&param.text);
+  let added = state.add_items(*list_id, &items, param.merge)?;
+  let item_ids: Vec<u64> = added.iter().map(AddedItem::id).collect();
+
+  record_added_items(&state, *list_id, items, added, user.id)?;
+
+  Response::from(item_ids)
+}
+
+fn record_added_items(
+  state: &DbState,
+  list_id: u64,
+  items: Vec<Item>,
+  added: Vec<AddedItem>,
+  user_id: u64,
+) -> Result<(), ResponseError> {
+  for (item, added) in items.into_iter().zip(added) {
+    let kind = match added {
+      AddedItem::Created(item_id) => ActivityKind::ItemAdded {
+        item_id,
+        name: item.name,
+      },
+      AddedItem::Merged(duplicate) => ActivityKind::ItemEdited {
+        item_id: duplicate.id,
+        name: duplicate.name,
+      },
+    };
+    state.record_activity(list_id, Some(user_id), kind)?;
+  }
+
+  Ok(())
+}
+
+/// Callers have to verify the access to the list themselves
+fn add_item(
+  state: &DbState,
+  list_id: u64,
+  mut item: Item,
+  merge: bool,
+  user_id: u64,
+) -> Result<u64, ResponseError> {
+  if merge {
+    if let Some(duplicate_id) = merge_into_duplicate(state, list_id, &item, user_id)? {
+      return Ok(duplicate_id);
+    }
+  }
 
-  state.verify_access::<List, User>(param.list_id, user.id)?;
-  param.item.id = item_id;
+  let item_id = state.db.generate_id()?;
+  item.id = item_id;
 
   // insert item
-  state.store_unlisted(&param.item, item_id)?;
+  state.store_unlisted(&item, item_id)?;
 
   // direct usage of trees is unsafe as it can lead to storing the wrong type of object in a tree
   unsafe {
@@ -46,17 +141,15 @@ pub async fn store_item_attached(
       .list_db
       .transaction(|tx_db| {
         let mut current_list =
-          match <&TransactionalTree as db::RawRkyvStore<List, 512>>::get_unchecked(
-            &tx_db,
-            param.list_id,
-          ) {
+          match <&TransactionalTree as db::RawRkyvStore<List, 512>>::get_unchecked(&tx_db, list_id)
+          {
             Ok(val) => val,
             Err(e) => return abort(not_found(e)),
           };
-        current_list.items.push(param.item.id);
+        current_list.items.push(item_id);
         match <&TransactionalTree as db::RawRkyvStore<List, 512>>::store_unlisted(
           &tx_db,
-          param.list_id,
+          list_id,
           &current_list,
         ) {
           Ok(_) => Ok(()),
@@ -71,9 +164,65 @@ pub async fn store_item_attached(
       })?;
   }
   // ensure that we can get items independent of their corresponding list
-  state.copy_acl::<List, Item>(param.list_id, param.item.id)?;
+  state.copy_acl::<List, Item>(list_id, item_id)?;
 
-  Response::from(item_id)
+  state.record_activity(
+    list_id,
+    Some(user_id),
+    ActivityKind::ItemAdded {
+      item_id,
+      name: item.name,
+    },
+  )?;
+
+  Ok(item_id)
+}
+
+/// Adds the amount of the item to the first unchecked duplicate in the list and returns the id of the duplicate
+fn merge_into_duplicate(
+  state: &DbState,
+  list_id: u64,
+  item: &Item,
+  user_id: u64,
+) -> Result<Option<u64>, ResponseError> {
+  let Some(duplicate) = state.merge_into_duplicate(list_id, item)? else {
+    return Ok(None);
+  };
+
+  state.record_activity(
+    list_id,
+    Some(user_id),
+    ActivityKind::ItemEdited {
+      item_id: duplicate.id,
+      name: duplicate.name,
+    },
+  )?;
+
+  Ok(Some(duplicate.id))
+}
+
+/// Merges unchecked duplicates into the first of them, the others are moved into the trash
+#[post("/itemList/{id}/merge")]
+pub async fn merge_duplicates(
+  list_id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<FlatItemsList> {
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Write)?;
+
+  for (target, merged_item_ids) in state.merge_duplicates(*list_id, user.id)? {
+    state.record_activity(
+      *list_id,
+      Some(user.id),
+      ActivityKind::ItemsMerged {
+        item_id: target.id,
+        name: target.name,
+        merged_item_ids,
+      },
+    )?;
+  }
+
+  Response::from(load_flat_list(&state, *list_id)?)
 }
 
 #[put("/item")]
@@ -82,66 +231,206 @@ pub async fn update_item_attached(
   state: web::Data<DbState>,
   user: AuthenticatedUser,
 ) -> Response<()> {
-  state.verify_access::<Item, User>(param.id, user.id)?;
+  let may_edit = match state.verify_item_access(param.id, user.id, Permission::Write) {
+    Ok(()) => true,
+    Err(DbError::Mismatch) => {
+      state.verify_item_access(param.id, user.id, Permission::Check)?;
+      false
+    }
+    Err(e) => return ResponseError::from(e).into(),
+  };
+
+  let previous: Item = state.get_unchecked(param.id)?;
+  // shoppers may only check items, so any other modification is discarded for them
+  let item = match may_edit {
+    true => param,
+    false => Item {
+      checked: param.checked,
+      ..previous.clone()
+    },
+  };
+
+  state.store_unlisted(&item, item.id)?;
 
-  state.store_unlisted(&param, param.id)?;
+  let list_id = state.list_of_item(item.id)?;
+  record_item_update(&state, list_id, &previous, item, user.id)?;
 
   Response::from(())
 }
 
+/// Records the activity for a changed item, along with the check-off if it was checked
+pub(crate) fn record_item_update(
+  state: &DbState,
+  list_id: u64,
+  previous: &Item,
+  item: Item,
+  user_id: u64,
+) -> Result<(), ResponseError> {
+  if item.checked != previous.checked {
+    state.record_activity(
+      list_id,
+      Some(user_id),
+      ActivityKind::ItemChecked {
+        item_id: item.id,
+        name: item.name.clone(),
+        checked: item.checked,
+      },
+    )?;
+
+    if item.checked {
+      state.record_check_off(list_id, &item)?;
+    }
+  }
+  // the equality of items does not cover the name
+  let unchecked_item = Item {
+    checked: previous.checked,
+    ..item.clone()
+  };
+  if unchecked_item != *previous || item.name != previous.name {
+    state.record_activity(
+      list_id,
+      Some(user_id),
+      ActivityKind::ItemEdited {
+        item_id: item.id,
+        name: item.name,
+      },
+    )?;
+  }
+
+  Ok(())
+}
+
+/// Shoppers may only patch `checked`.
+#[patch("/item/{id}")]
+pub async fn patch_item(
+  id: web::Path<u64>,
+  param: ItemPatchV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Item> {
+  // shoppers may only check items
+  let permission = match param.only_checks() {
+    true => Permission::Check,
+    false => Permission::Write,
+  };
+  state.verify_item_access(*id, user.id, permission)?;
+
+  let (previous, item) = state.modify(*id, |item: &mut Item| param.apply(item))?;
+
+  let list_id = state.list_of_item(item.id)?;
+  record_item_update(&state, list_id, &previous, item.clone(), user.id)?;
+
+  Response::from(item)
+}
+
 #[delete("/item")]
 pub async fn delete_item(
   param: DeleteItem,
   state: web::Data<DbState>,
   user: AuthenticatedUser,
 ) -> Response<()> {
-  state.verify_access::<Item, User>(param.item_id, user.id)?;
+  state.verify_access::<List, User>(param.list_id, user.id, Permission::Write)?;
+  state.verify_access::<Item, User>(param.item_id, user.id, Permission::Write)?;
 
-  // delete actual Item data
-  state.delete::<Item>(param.item_id)?;
+  // the item can be restored from the trash until it is purged
+  let item = state.trash_item(param.list_id, param.item_id, user.id)?;
 
-  // update affected list meta
-  //TODO: optimize? - this could operate on archived data
-  state
-    .list_db
-    .transaction(|tx_db| unsafe {
-      let mut current_list: List = <&sled::transaction::TransactionalTree as db::RawRkyvStore<
-        einkaufsliste::model::list::List,
-        4096,
-      >>::get_unchecked(&tx_db, param.list_id)
-      .map_err(abort_error)?;
-      let item_id_idx = current_list
-        .items
-        .iter()
-        .position(|&x| x == param.item_id)
-        .ok_or(abort_error(DbError::NotFound))?;
-      current_list.items.remove(item_id_idx);
-      <&sled::transaction::TransactionalTree as db::RawRkyvStore<
-        einkaufsliste::model::list::List,
-        4096,
-      >>::store_unlisted(&tx_db, param.list_id, &current_list)
-      .map_err(abort_error)?;
-
-      Ok(())
-    })
-    .map_err(|e| match e {
-      // return inner error if its a sled-user-error:
-      sled::transaction::TransactionError::Abort(e) => e.into(),
-      // if not, something else went wrong, so generic server error:
-      _ => error(e),
-    })?;
+  state.record_activity(
+    param.list_id,
+    Some(user.id),
+    ActivityKind::ItemDeleted {
+      item_id: param.item_id,
+      name: item.name,
+    },
+  )?;
 
   Response::from(())
 }
 
+/// Moves the item to another position within its list
+#[put("/itemList/{id}/item/{item_id}/position")]
+pub async fn reorder_item(
+  path: web::Path<(u64, u64)>,
+  param: ReorderItemV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  let (list_id, item_id) = *path;
+  state.verify_access::<List, User>(list_id, user.id, Permission::Write)?;
+
+  state.move_item(list_id, list_id, item_id, Some(param.position))?;
+
+  Response::empty()
+}
+
+/// Moves the item into another list, which requires write access to both lists
+#[post("/itemList/{id}/item/{item_id}/move")]
+pub async fn move_item(
+  path: web::Path<(u64, u64)>,
+  param: MoveItemV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  let (list_id, item_id) = *path;
+  state.verify_access::<List, User>(list_id, user.id, Permission::Write)?;
+  state.verify_access::<List, User>(param.target_list_id, user.id, Permission::Write)?;
+
+  let item: Item = state.get_unchecked(item_id)?;
+  state.move_item(list_id, param.target_list_id, item_id, param.position)?;
+
+  if list_id != param.target_list_id {
+    for recorded_in in [list_id, param.target_list_id] {
+      state.record_activity(
+        recorded_in,
+        Some(user.id),
+        ActivityKind::ItemMoved {
+          item_id,
+          name: item.name.clone(),
+          from_list_id: list_id,
+          to_list_id: param.target_list_id,
+        },
+      )?;
+    }
+  }
+
+  Response::empty()
+}
+
 #[get("/itemList/{id}/flat")]
 pub async fn get_item_list_flat(
   list_id: web::Path<u64>,
   state: web::Data<DbState>,
   user: AuthenticatedUser,
 ) -> Response<FlatItemsList> {
-  state.verify_access::<List, User>(*list_id, user.id)?;
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Read)?;
+
+  Response::from(load_flat_list(&state, *list_id)?)
+}
+
+/// The items in the order the shop of the list is walked through, either by its layout or as learned from check-offs
+#[get("/itemList/{id}/sorted")]
+pub async fn get_item_list_sorted(
+  list_id: web::Path<u64>,
+  query: web::Query<SortedListQuery>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<FlatItemsList> {
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Read)?;
+
+  let mut list = load_flat_list(&state, *list_id)?;
+  match query.order {
+    ShopOrder::Layout => shop::sort_by_layout(&state, &mut list)?,
+    ShopOrder::Learned => shop::sort_by_learned_order(&state, &mut list)?,
+  }
+
+  Response::from(list)
+}
 
+/// Callers have to verify the access to the list themselves
+pub(crate) fn load_flat_list(
+  state: &DbState,
+  list_id: u64,
+) -> Result<FlatItemsList, ResponseError> {
   let list_bytes = state
     .list_db
     .get(list_id.as_bytes())?
@@ -168,9 +457,11 @@ pub async fn get_item_list_flat(
     .map(|ivec| unsafe { rkyv::from_bytes_unchecked::<Item>(ivec.as_bytes()).unwrap() })
     .collect::<Vec<_>>();
 
-  let flat_items_list = FlatItemsList::from_list_and_items(list, vec);
+  let groups = category::group_by_category(state, list_id, &vec)?;
+  let mut flat_items_list = FlatItemsList::from_list_and_items(list, vec);
+  flat_items_list.groups = groups;
 
-  Response::from(flat_items_list)
+  Ok(flat_items_list)
 }
 
 #[post("/itemList")]
@@ -178,33 +469,135 @@ pub(crate) async fn store_item_list(
   mut param: List,
   state: web::Data<DbState>,
   user: AuthenticatedUser,
+  idempotency: web::Data<IdempotencyStore>,
+  key: IdempotencyKey,
 ) -> Response<u64> {
-  // while an id is provided with the archived data, we do not use this id, given that the client does not know the new id as this is DB-managed information
-  let id = state.db.generate_id()?;
-  param.id = id;
+  let id = idempotency.run(&key, user.id, "POST /itemList", || {
+    // while an id is provided with the archived data, we do not use this id, given that the client does not know the new id as this is DB-managed information
+    let id = state.db.generate_id()?;
+    param.id = id;
 
-  state.store_listed(&param, user.id, id)?;
-  state.create_acl::<List, User>(id, user.id)?;
+    state.store_listed(&param, user.id, id)?;
+    state.create_acl::<List, User>(id, user.id)?;
+
+    Ok(id)
+  })?;
 
   // we need to return the newly generated id to the client
   id.into()
 }
 
-// update only the metadata of a list, not the items
+// the items can only be reordered or left out like with `patch_item_list`, they are added through their own endpoints
 #[put("/itemList")]
 pub async fn update_item_list(
   param: List,
   state: web::Data<DbState>,
   user: AuthenticatedUser,
 ) -> Response<()> {
-  state.verify_access::<List, User>(param.id, user.id)?;
+  state.verify_access::<List, User>(param.id, user.id, Permission::Write)?;
+
+  // only items already in the list are accepted, so no information about other items can be leaked
+  let patch = ListPatchV1 {
+    name: Some(param.name),
+    shop: Some(param.shop),
+    image_id: Some(param.image_id),
+    items: Some(param.items),
+  };
+  let (previous, list, trashed) = state.patch_list(param.id, &patch, user.id)?;
+
+  for item in trashed {
+    state.record_activity(
+      list.id,
+      Some(user.id),
+      ActivityKind::ItemDeleted {
+        item_id: item.id,
+        name: item.name,
+      },
+    )?;
+  }
 
-  // We check the items acls here to prevent leaking information about items that the user does not have access to
-  // doing this here alleviates the need to check the acls on GET requests
-  for item_id in &param.items {
-    state.verify_access::<Item, User>(*item_id, user.id)?;
+  if list.name != previous.name {
+    state.record_activity(
+      list.id,
+      Some(user.id),
+      ActivityKind::ListRenamed {
+        from: previous.name,
+        to: list.name,
+      },
+    )?;
   }
-  state.store_unlisted(&param, param.id)?;
 
   Response::from(())
 }
+
+/// The items can only be reordered or left out, which moves them into the trash.
+#[patch("/itemList/{id}")]
+pub async fn patch_item_list(
+  id: web::Path<u64>,
+  param: ListPatchV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<List> {
+  state.verify_access::<List, User>(*id, user.id, Permission::Write)?;
+
+  let (previous, list, trashed) = state.patch_list(*id, &param, user.id)?;
+
+  for item in trashed {
+    state.record_activity(
+      list.id,
+      Some(user.id),
+      ActivityKind::ItemDeleted {
+        item_id: item.id,
+        name: item.name,
+      },
+    )?;
+  }
+
+  if list.name != previous.name {
+    state.record_activity(
+      list.id,
+      Some(user.id),
+      ActivityKind::ListRenamed {
+        from: previous.name,
+        to: list.name.clone(),
+      },
+    )?;
+  }
+
+  Response::from(list)
+}
+
+/// The previous owner stays on as editor of the list.
+#[put("/itemList/{id}/owner")]
+pub async fn transfer_item_list(
+  list_id: web::Path<u64>,
+  param: TransferOwnershipV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Manage)?;
+
+  let new_owner = user::find_user_id(&state, &param.new_owner)?;
+  state.transfer_list_ownership(*list_id, new_owner, Role::Editor)?;
+
+  Response::empty()
+}
+
+/// Newest events first; pass the id of the last event as `before` to get the next page.
+#[get("/itemList/{id}/activity")]
+pub async fn get_item_list_activity(
+  list_id: web::Path<u64>,
+  query: web::Query<ActivityQuery>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<ActivityEvent>> {
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Read)?;
+
+  let limit = query
+    .limit
+    .unwrap_or(DEFAULT_ACTIVITY_PAGE_SIZE)
+    .min(MAX_ACTIVITY_PAGE_SIZE);
+  let events = state.list_activity(*list_id, query.before, limit)?;
+
+  Response::from(events)
+}
@@ -1,4 +1,14 @@
 pub(crate) mod article;
+pub(crate) mod batch;
+pub(crate) mod category;
+pub(crate) mod household;
+pub(crate) mod invite;
 pub(crate) mod item;
+pub(crate) mod oidc;
+pub(crate) mod share;
 pub(crate) mod shop;
+pub(crate) mod token;
+pub(crate) mod totp;
+pub(crate) mod trash;
 pub(crate) mod user;
+pub(crate) mod webhook;
@@ -0,0 +1,359 @@
+use actix_session::Session;
+use actix_web::{post, web, HttpMessage, HttpRequest};
+use einkaufsliste::model::requests::OidcCallbackV1;
+use einkaufsliste::model::user::{LoginResponseV1, OidcAuthorization, User, UserWithPassword};
+
+use super::totp::require_second_factor;
+use super::user::login_user;
+use crate::db::DbState;
+use crate::response::{Response, ResponseError};
+use crate::util::errors::{bad_request, error};
+use crate::util::identity_ext::AuthenticatedUser;
+use crate::util::oidc::{ExternalIdentity, OidcProvider, PendingAuthorization};
+use crate::util::secret::generate_secret;
+
+/// Session key remembering the secrets of the authorization request until the user returns from the provider
+const PENDING_OIDC_KEY: &str = "pending_oidc";
+/// Seconds the user has to authenticate at the provider
+const PENDING_OIDC_TIMEOUT: i64 = 10 * 60;
+
+/// Starts the authorization code flow. The client has to open the returned url and pass the redirect on to [`login_oidc_callback_v1`] in the same session.
+#[post("/login/oidc/v1")]
+pub(crate) async fn login_oidc_v1(
+  provider: Option<web::Data<OidcProvider>>,
+  session: Session,
+) -> Response<OidcAuthorization> {
+  let provider = match provider {
+    Some(provider) => provider,
+    None => return ResponseError::ErrorNotFound.into(),
+  };
+
+  let (authorization_url, pending) = provider.authorize();
+  session
+    .insert(
+      PENDING_OIDC_KEY,
+      (
+        pending.csrf_state,
+        pending.pkce_verifier,
+        pending.nonce,
+        now() + PENDING_OIDC_TIMEOUT,
+      ),
+    )
+    .map_err(error)?;
+
+  Response::from(OidcAuthorization { authorization_url })
+}
+
+/**
+Completes the authorization code flow.
+
+If the caller is already logged in, the external identity is linked to the current user instead, which is how existing accounts are connected.
+Unknown identities are rejected unless account creation is enabled in the configuration and registration is open.
+*/
+#[post("/login/oidc/callback/v1")]
+pub(crate) async fn login_oidc_callback_v1(
+  param: OidcCallbackV1,
+  provider: Option<web::Data<OidcProvider>>,
+  state: web::Data<DbState>,
+  session: Session,
+  current_user: Option<AuthenticatedUser>,
+  request: HttpRequest,
+) -> Response<LoginResponseV1> {
+  let provider = match provider {
+    Some(provider) => provider,
+    None => return ResponseError::ErrorNotFound.into(),
+  };
+
+  // removing the pending request first makes every authorization request single-use
+  let (csrf_state, pkce_verifier, nonce, deadline) =
+    match session.remove_as::<(String, String, String, i64)>(PENDING_OIDC_KEY) {
+      Some(Ok(pending)) => pending,
+      _ => return ResponseError::ErrorUnauthenticated.into(),
+    };
+  if csrf_state != param.state || deadline < now() {
+    return ResponseError::ErrorUnauthenticated.into();
+  }
+
+  let pending = PendingAuthorization {
+    csrf_state,
+    pkce_verifier,
+    nonce,
+  };
+  let identity = provider.exchange(param.code, pending).await?;
+
+  // api tokens must not be able to link identities
+  let current_user = current_user.filter(|user| user.api_token_id.is_none());
+
+  let user_id = match (provider.linked_user(&identity.subject)?, &current_user) {
+    (Some(linked), Some(current)) if linked != current.id => {
+      return bad_request("Identity is already linked to another account").into()
+    }
+    (Some(linked), _) => linked,
+    (None, Some(current)) => {
+      provider.link(&identity.subject, current.id)?;
+      current.id
+    }
+    (None, None) if provider.create_accounts => {
+      let id = create_account(&state, &identity).await?;
+      provider.link(&identity.subject, id)?;
+      id
+    }
+    (None, None) => return ResponseError::ErrorUnauthorized.into(),
+  };
+
+  let profile: User = state.get_unchecked(user_id)?;
+
+  if current_user.is_none() {
+    // the provider only replaces the password, an enrolled second factor is still required
+    if require_second_factor(&state, &session, user_id)? {
+      return Response::from(LoginResponseV1::TotpRequired);
+    }
+
+    login_user(&request.extensions(), user_id)?;
+  }
+
+  Response::from(LoginResponseV1::Authenticated(profile))
+}
+
+async fn create_account(
+  state: &DbState,
+  identity: &ExternalIdentity,
+) -> Result<u64, ResponseError> {
+  let name = identity
+    .preferred_username
+    .clone()
+    .unwrap_or_else(|| identity.subject.clone());
+
+  // linking to an existing account by name would allow taking over local accounts
+  if state.login_db.get(&name)?.is_some() {
+    return Err(bad_request(
+      "An account with this name already exists. Log in to link the identity to it.",
+    ));
+  }
+
+  let id = state.db.generate_id()?;
+
+  // nobody knows this password, but it keeps the account compatible with the password login
+  let value = UserWithPassword {
+    user: User {
+      id,
+      name,
+      profile_picture_id: None,
+    },
+    password: state.hash_password(generate_secret()).await?,
+  };
+
+  state.new_user(&value)?;
+  state.store_unlisted(&value.user, id)?;
+
+  Ok(id)
+}
+
+fn now() -> i64 {
+  einkaufsliste::model::session::Session::get_current_time()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::net::TcpListener;
+  use std::sync::Mutex;
+
+  use actix_identity::IdentityMiddleware;
+  use actix_session::storage::CookieSessionStore;
+  use actix_session::SessionMiddleware;
+  use actix_web::cookie::Key;
+  use actix_web::http::StatusCode;
+  use actix_web::{test, web, App, HttpResponse, HttpServer};
+  use einkaufsliste::model::requests::OidcCallbackV1;
+  use einkaufsliste::model::user::{LoginResponseV1, OidcAuthorization};
+  use openidconnect::core::{
+    CoreIdToken, CoreIdTokenClaims, CoreJsonWebKeySet, CoreJwsSigningAlgorithm,
+    CoreRsaPrivateSigningKey,
+  };
+  use openidconnect::{JsonWebKeyId, PrivateSigningKey};
+  use serde_json::json;
+
+  use super::{login_oidc_callback_v1, login_oidc_v1};
+  use crate::db::DbState;
+  use crate::util::oidc::OidcProvider;
+
+  /// Signs the id tokens of the mock provider, it protects nothing
+  const SIGNING_KEY: &str = include_str!("oidc_test_key.pem");
+
+  /// What the mock provider needs to issue an id token for the pending authorization request
+  struct MockProvider {
+    issuer: String,
+    nonce: Mutex<String>,
+  }
+
+  fn signing_key() -> CoreRsaPrivateSigningKey {
+    CoreRsaPrivateSigningKey::from_pem(SIGNING_KEY, Some(JsonWebKeyId::new("test".to_owned())))
+      .unwrap()
+  }
+
+  /// Serves the discovery document, the token endpoint and the keys of a provider that accepts every code
+  fn start_mock_provider() -> web::Data<MockProvider> {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mock = web::Data::new(MockProvider {
+      issuer: format!("http://{}", listener.local_addr().unwrap()),
+      nonce: Mutex::new(String::new()),
+    });
+
+    let data = mock.clone();
+    let server = HttpServer::new(move || {
+      App::new()
+        .app_data(data.clone())
+        .route(
+          "/.well-known/openid-configuration",
+          web::get().to(|mock: web::Data<MockProvider>| async move {
+            HttpResponse::Ok().json(json!({
+              "issuer": mock.issuer,
+              "authorization_endpoint": format!("{}/authorize", mock.issuer),
+              "token_endpoint": format!("{}/token", mock.issuer),
+              "jwks_uri": format!("{}/jwks", mock.issuer),
+              "response_types_supported": ["code"],
+              "subject_types_supported": ["public"],
+              "id_token_signing_alg_values_supported": ["RS256"],
+            }))
+          }),
+        )
+        .route(
+          "/token",
+          web::post().to(|mock: web::Data<MockProvider>| async move {
+            let now = super::now();
+            let claims: CoreIdTokenClaims = serde_json::from_value(json!({
+              "iss": mock.issuer,
+              "aud": ["client"],
+              "exp": now + 60,
+              "iat": now,
+              "sub": "external-subject",
+              "preferred_username": "oidc-user",
+              "nonce": *mock.nonce.lock().unwrap(),
+            }))
+            .unwrap();
+            let id_token = CoreIdToken::new(
+              claims,
+              &signing_key(),
+              CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256,
+              None,
+              None,
+            )
+            .unwrap();
+
+            HttpResponse::Ok().json(json!({
+              "access_token": "access-token",
+              "token_type": "bearer",
+              "id_token": id_token,
+            }))
+          }),
+        )
+        .route(
+          "/jwks",
+          web::get().to(|| async {
+            HttpResponse::Ok().json(CoreJsonWebKeySet::new(vec![
+              signing_key().as_verification_key()
+            ]))
+          }),
+        )
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    actix_web::rt::spawn(server);
+
+    mock
+  }
+
+  /// Runs the whole authorization code flow against the mock provider and returns the status and body of the callback
+  async fn login(create_accounts: bool) -> (StatusCode, web::Bytes) {
+    let mock = start_mock_provider();
+    let state = DbState::temporary();
+
+    let mut provider = OidcProvider::connect(
+      mock.issuer.clone(),
+      "client".to_owned(),
+      Some("secret".to_owned()),
+      "http://localhost/callback".to_owned(),
+      state.db.open_tree("oidc_link").unwrap(),
+    )
+    .await;
+    provider.create_accounts = create_accounts;
+
+    let app = test::init_service(
+      App::new()
+        .wrap(IdentityMiddleware::builder().build())
+        .wrap(SessionMiddleware::new(
+          CookieSessionStore::default(),
+          Key::generate(),
+        ))
+        .app_data(web::Data::new(state))
+        .app_data(web::Data::new(provider))
+        .service(login_oidc_v1)
+        .service(login_oidc_callback_v1),
+    )
+    .await;
+
+    let response = test::call_service(
+      &app,
+      test::TestRequest::post()
+        .uri("/login/oidc/v1")
+        .insert_header(("Accept", "application/json"))
+        .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let session = response.response().cookies().next().unwrap().into_owned();
+    let authorization: OidcAuthorization = test::read_body_json(response).await;
+
+    // the user would authenticate at the provider, which then redirects back with the state
+    let url = reqwest::Url::parse(&authorization.authorization_url).unwrap();
+    let parameter = |name: &str| {
+      url
+        .query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+        .unwrap()
+    };
+    *mock.nonce.lock().unwrap() = parameter("nonce");
+
+    let response = test::call_service(
+      &app,
+      test::TestRequest::post()
+        .uri("/login/oidc/callback/v1")
+        .insert_header(("Content-Type", "application/json"))
+        .insert_header(("Accept", "application/json"))
+        .cookie(session)
+        .set_payload(
+          serde_json::to_vec(&OidcCallbackV1 {
+            code: "code".to_owned(),
+            state: parameter("state"),
+          })
+          .unwrap(),
+        )
+        .to_request(),
+    )
+    .await;
+
+    (response.status(), test::read_body(response).await)
+  }
+
+  #[actix_web::test]
+  async fn callback_creates_account() {
+    let (status, body) = login(true).await;
+    assert_eq!(status, StatusCode::OK);
+
+    match serde_json::from_slice(&body).unwrap() {
+      LoginResponseV1::Authenticated(user) => assert_eq!(user.name, "oidc-user"),
+      LoginResponseV1::TotpRequired => panic!("no second factor is enrolled"),
+    }
+  }
+
+  #[actix_web::test]
+  async fn callback_rejects_unknown_identity() {
+    let (status, body) = login(false).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    // a failed exchange is rejected as unauthenticated instead
+    assert!(String::from_utf8_lossy(&body).contains("not authorized"));
+  }
+}
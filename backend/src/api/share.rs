@@ -0,0 +1,238 @@
+use actix_web::http::header;
+use actix_web::{delete, get, post, web, HttpResponse};
+use einkaufsliste::model::activity::ActivityKind;
+use einkaufsliste::model::item::Item;
+use einkaufsliste::model::list::{FlatItemsList, List};
+use einkaufsliste::model::requests::CreateShareLinkV1;
+use einkaufsliste::model::session::Session;
+use einkaufsliste::model::share::{CreatedShareLink, ShareLink, StoredShareLink};
+use einkaufsliste::model::user::User;
+use einkaufsliste::model::Permission;
+
+use super::item::load_flat_list;
+use crate::db::{self, DbError, DbState};
+use crate::response::{Response, ResponseError};
+use crate::util::identity_ext::AuthenticatedUser;
+use crate::util::secret::{format_token, generate_secret, hash_secret, parse_token};
+
+/// The token of the new link is only returned here, it cannot be retrieved later on.
+#[post("/itemList/{id}/share")]
+pub(crate) async fn create_share_link(
+  list_id: web::Path<u64>,
+  param: CreateShareLinkV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<CreatedShareLink> {
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Manage)?;
+
+  let id = state.db.generate_id()?;
+  let secret = generate_secret();
+
+  let link = ShareLink {
+    id,
+    list_id: *list_id,
+    created: Session::get_current_time(),
+    expires: param.expires,
+    allow_check: param.allow_check,
+  };
+  let stored = StoredShareLink {
+    link: link.clone(),
+    secret_hash: hash_secret(&secret),
+  };
+  // the links are listed per list rather than per user, so every owner can revoke them
+  state.store_listed(&stored, *list_id, id)?;
+
+  Response::from(CreatedShareLink {
+    link,
+    token: format_token(id, &secret),
+  })
+}
+
+#[get("/itemList/{id}/share")]
+pub(crate) async fn list_share_links(
+  list_id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<ShareLink>> {
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Manage)?;
+
+  let link_ids = <db::DbState as db::ObjectStore<StoredShareLink, sled::Tree, 512>>::object_list(
+    &state, *list_id,
+  )?;
+
+  let links = link_ids
+    .list
+    .into_iter()
+    .map(|id| {
+      state
+        .get_unchecked::<StoredShareLink>(id)
+        .map(|stored| stored.link)
+    })
+    .collect::<Result<Vec<ShareLink>, _>>()?;
+
+  Response::from(links)
+}
+
+#[delete("/itemList/{id}/share/{link_id}")]
+pub(crate) async fn revoke_share_link(
+  path: web::Path<(u64, u64)>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  let (list_id, link_id) = *path;
+  state.verify_access::<List, User>(list_id, user.id, Permission::Manage)?;
+
+  let stored: StoredShareLink = state.get_unchecked(link_id)?;
+  if stored.link.list_id != list_id {
+    return ResponseError::ErrorNotFound.into();
+  }
+
+  state.unlist::<StoredShareLink>(list_id, link_id)?;
+  state.delete::<StoredShareLink>(link_id)?;
+
+  Response::empty()
+}
+
+/// Same as [`super::item::get_item_list_flat`], but authenticated by the share link instead of an account.
+#[get("/share/{token}/flat")]
+pub(crate) async fn get_shared_list_flat(
+  token: web::Path<String>,
+  state: web::Data<DbState>,
+) -> Response<FlatItemsList> {
+  let link = resolve_share_link(&state, &token)?;
+
+  Response::from(load_flat_list(&state, link.list_id)?)
+}
+
+/// Minimal page for people without the app. It works without javascript, checking items is done with plain forms.
+#[get("/share/{token}")]
+pub(crate) async fn shared_list_page(
+  token: web::Path<String>,
+  state: web::Data<DbState>,
+) -> Result<HttpResponse, actix_web::Error> {
+  let link = resolve_share_link(&state, &token)?;
+  let list = load_flat_list(&state, link.list_id)?;
+
+  let items = list
+    .items
+    .iter()
+    .map(|item| {
+      let name = match item.display_amount() {
+        Some(amount) => escape_html(&format!("{amount} {}", item.name)),
+        None => escape_html(&item.name),
+      };
+      let name = match item.checked {
+        true => format!("<s>{name}</s>"),
+        false => name,
+      };
+
+      match link.allow_check {
+        true => format!(
+          "<li><form method=\"post\" action=\"/share/{}/item/{}/toggle\">{name} <button \
+           type=\"submit\">{}</button></form></li>",
+          escape_html(&token),
+          item.id,
+          if item.checked { "Uncheck" } else { "Check" },
+        ),
+        false => format!("<li>{name}</li>"),
+      }
+    })
+    .collect::<String>();
+
+  let page = format!(
+    "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"><meta name=\"viewport\" \
+     content=\"width=device-width, \
+     initial-scale=1\"><title>{name}</title></head><body><h1>{name}</h1><ul>{items}</ul></body></\
+     html>",
+    name = escape_html(&list.name),
+  );
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type(mime::TEXT_HTML_UTF_8)
+      .body(page),
+  )
+}
+
+/// Redirects back to [`shared_list_page`], so reloading the page does not submit the form again.
+#[post("/share/{token}/item/{item_id}/toggle")]
+pub(crate) async fn toggle_shared_item(
+  path: web::Path<(String, u64)>,
+  state: web::Data<DbState>,
+) -> Result<HttpResponse, actix_web::Error> {
+  let (token, item_id) = path.into_inner();
+
+  let link = resolve_share_link(&state, &token)?;
+  if !link.allow_check {
+    return Err(ResponseError::ErrorUnauthorized.into());
+  }
+
+  // the link only grants access to the items of its own list
+  let list: List = state
+    .get_unchecked(link.list_id)
+    .map_err(ResponseError::from)?;
+  if !list.items.contains(&item_id) {
+    return Err(ResponseError::ErrorNotFound.into());
+  }
+
+  let mut item: Item = state.get_unchecked(item_id).map_err(ResponseError::from)?;
+  item.checked = !item.checked;
+  state
+    .store_unlisted(&item, item_id)
+    .map_err(ResponseError::from)?;
+  if item.checked {
+    state
+      .record_check_off(link.list_id, &item)
+      .map_err(ResponseError::from)?;
+  }
+  state
+    .record_activity(
+      link.list_id,
+      None,
+      ActivityKind::ItemChecked {
+        item_id,
+        name: item.name,
+        checked: item.checked,
+      },
+    )
+    .map_err(ResponseError::from)?;
+
+  Ok(
+    HttpResponse::SeeOther()
+      .insert_header((header::LOCATION, format!("/share/{token}")))
+      .finish(),
+  )
+}
+
+fn resolve_share_link(state: &DbState, token: &str) -> Result<ShareLink, ResponseError> {
+  let (id, secret) = parse_token(token).ok_or(ResponseError::ErrorNotFound)?;
+
+  let stored: StoredShareLink = match state.get_unchecked(id) {
+    Ok(stored) => stored,
+    // revoked and invalid links are indistinguishable for the visitor
+    Err(DbError::NotFound) => return Err(ResponseError::ErrorNotFound),
+    Err(e) => return Err(e.into()),
+  };
+
+  if stored.secret_hash != hash_secret(secret) {
+    return Err(ResponseError::ErrorNotFound);
+  }
+  if stored
+    .link
+    .expires
+    .is_some_and(|expires| expires < Session::get_current_time())
+  {
+    return Err(ResponseError::ErrorNotFound);
+  }
+
+  Ok(stored.link)
+}
+
+fn escape_html(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&#39;")
+}
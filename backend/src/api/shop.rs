@@ -1,18 +1,31 @@
+use std::collections::HashMap;
+
 use actix_web::*;
-use einkaufsliste::model::shop::Shop;
+use einkaufsliste::model::item::Item;
+use einkaufsliste::model::list::FlatItemsList;
+use einkaufsliste::model::patch::ShopPatchV1;
+use einkaufsliste::model::requests::{TransferOwnershipV1, UpdateShopLayoutV1};
+use einkaufsliste::model::shop::{CheckOff, Shop};
 use einkaufsliste::model::user::User;
+use einkaufsliste::model::{Permission, Role};
 
-use crate::response::Response;
+use super::category::item_category_id;
+use super::user::find_user_id;
+use crate::db::DbError;
+use crate::response::{Response, ResponseError};
 use crate::util::identity_ext::AuthenticatedUser;
 use crate::DbState;
 
+/// Seconds between two check-offs after which they count as separate trips through the shop
+const TRIP_BREAK: i64 = 60 * 60;
+
 #[get("/shop/{id}")]
 pub async fn get_shop(
   id: web::Path<u64>,
   state: web::Data<DbState>,
   user: AuthenticatedUser,
 ) -> Response<Shop> {
-  state.verify_access::<Shop, User>(*id, user.id)?;
+  state.verify_access::<Shop, User>(*id, user.id, Permission::Read)?;
 
   let shop: Shop = state.get_unchecked(*id)?;
 
@@ -34,3 +47,147 @@ pub async fn store_shop(
 
   id.into()
 }
+
+#[put("/shop/{id}/layout")]
+pub async fn update_shop_layout(
+  id: web::Path<u64>,
+  param: UpdateShopLayoutV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  state.verify_access::<Shop, User>(*id, user.id, Permission::Write)?;
+
+  let mut shop: Shop = state.get_unchecked(*id)?;
+  shop.layout = param.layout;
+  state.store_unlisted(&shop, *id)?;
+
+  Response::empty()
+}
+
+#[patch("/shop/{id}")]
+pub async fn patch_shop(
+  id: web::Path<u64>,
+  param: ShopPatchV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Shop> {
+  state.verify_access::<Shop, User>(*id, user.id, Permission::Write)?;
+
+  let (_, shop) = state.modify(*id, |shop: &mut Shop| param.apply(shop))?;
+
+  Response::from(shop)
+}
+
+/// The previous owner stays on as editor of the shop.
+#[put("/shop/{id}/owner")]
+pub async fn transfer_shop(
+  id: web::Path<u64>,
+  param: TransferOwnershipV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  state.verify_access::<Shop, User>(*id, user.id, Permission::Manage)?;
+
+  let new_owner = find_user_id(&state, &param.new_owner)?;
+  state.transfer_ownership::<Shop>(*id, new_owner, Role::Editor)?;
+
+  Response::empty()
+}
+
+/**
+Sorts the items by the section of the shop of the list they belong to, items the layout does not cover come last.
+Lists without a shop keep their order.
+*/
+pub(crate) fn sort_by_layout(
+  state: &DbState,
+  list: &mut FlatItemsList,
+) -> Result<(), ResponseError> {
+  let Some(shop_id) = list.shop else {
+    return Ok(());
+  };
+  let shop: Shop = match state.get_unchecked(shop_id) {
+    Ok(shop) => shop,
+    Err(DbError::NotFound) => return Ok(()),
+    Err(e) => return Err(e.into()),
+  };
+
+  let mut article_categories = HashMap::new();
+  let mut sections = HashMap::new();
+  for item in &list.items {
+    let category_id = item_category_id(state, item, &mut article_categories)?;
+    let section = shop
+      .section_of(item.article_id, category_id)
+      .unwrap_or(usize::MAX);
+    sections.insert(item.id, section);
+  }
+
+  // the sort is stable, so items of the same section keep the order of the list
+  list.items.sort_by_key(|item| sections[&item.id]);
+  // the groups follow the order of the categories rather than the walking order
+  list.groups.clear();
+
+  Ok(())
+}
+
+/**
+Sorts the items by the order their articles were checked off in the shop of the list before.
+Items whose article was never checked off there come last, lists without a shop keep their order.
+*/
+pub(crate) fn sort_by_learned_order(
+  state: &DbState,
+  list: &mut FlatItemsList,
+) -> Result<(), ResponseError> {
+  let Some(shop_id) = list.shop else {
+    return Ok(());
+  };
+  let ranks = learned_order(&state.shop_check_offs(shop_id)?);
+
+  list.items.sort_by(|a, b| {
+    let rank = |item: &Item| {
+      item
+        .article_id
+        .and_then(|article_id| ranks.get(&article_id).copied())
+        .unwrap_or(f64::INFINITY)
+    };
+    rank(a).total_cmp(&rank(b))
+  });
+  list.groups.clear();
+
+  Ok(())
+}
+
+/**
+The average relative position of each article within the trips through the shop, from 0 for the first to 1 for the
+last article checked off. A trip is a run of check-offs on the same list without longer breaks.
+*/
+fn learned_order(check_offs: &[CheckOff]) -> HashMap<u64, f64> {
+  let mut totals: HashMap<u64, (f64, u32)> = HashMap::new();
+
+  let trips =
+    check_offs.chunk_by(|a, b| a.list_id == b.list_id && b.timestamp - a.timestamp <= TRIP_BREAK);
+  for trip in trips {
+    // articles checked off again later in the same trip were most likely forgotten at first
+    let mut articles: Vec<u64> = Vec::with_capacity(trip.len());
+    for check_off in trip {
+      if !articles.contains(&check_off.article_id) {
+        articles.push(check_off.article_id);
+      }
+    }
+    // a single article says nothing about the order
+    if articles.len() < 2 {
+      continue;
+    }
+
+    let last = (articles.len() - 1) as f64;
+    for (position, article_id) in articles.into_iter().enumerate() {
+      let total = totals.entry(article_id).or_default();
+      total.0 += position as f64 / last;
+      total.1 += 1;
+    }
+  }
+
+  totals
+    .into_iter()
+    .map(|(article_id, (sum, count))| (article_id, sum / count as f64))
+    .collect()
+}
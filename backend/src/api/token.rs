@@ -0,0 +1,93 @@
+use actix_web::{delete, get, post, web};
+use einkaufsliste::model::requests::CreateApiTokenV1;
+use einkaufsliste::model::session::Session;
+use einkaufsliste::model::token::{ApiToken, CreatedApiToken, StoredApiToken};
+
+use crate::db::{self, DbState};
+use crate::response::{Response, ResponseError};
+use crate::util::identity_ext::AuthenticatedUser;
+use crate::util::secret::{format_token, generate_secret, hash_secret};
+
+/// The secret of the new token is only returned here, it cannot be retrieved later on.
+#[post("/token")]
+pub(crate) async fn create_api_token(
+  param: CreateApiTokenV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<CreatedApiToken> {
+  // tokens may not manage tokens - a leaked read-only token could be used to create unrestricted ones otherwise
+  if user.api_token_id.is_some() {
+    return ResponseError::ErrorUnauthorized.into();
+  }
+
+  let id = state.db.generate_id()?;
+
+  let secret = generate_secret();
+
+  let token = ApiToken {
+    id,
+    name: param.name,
+    created: Session::get_current_time(),
+    expires: param.expires,
+    read_only: param.read_only,
+  };
+  let stored = StoredApiToken {
+    token: token.clone(),
+    user_id: user.id,
+    secret_hash: hash_secret(&secret),
+  };
+  state.store_listed(&stored, user.id, id)?;
+
+  Response::from(CreatedApiToken {
+    token,
+    secret: format_token(id, &secret),
+  })
+}
+
+#[get("/token")]
+pub(crate) async fn list_api_tokens(
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<ApiToken>> {
+  if user.api_token_id.is_some() {
+    return ResponseError::ErrorUnauthorized.into();
+  }
+
+  let token_ids = <db::DbState as db::ObjectStore<StoredApiToken, sled::Tree, 512>>::object_list(
+    &state, user.id,
+  )?;
+
+  let tokens = token_ids
+    .list
+    .into_iter()
+    .map(|id| {
+      state
+        .get_unchecked::<StoredApiToken>(id)
+        .map(|stored| stored.token)
+    })
+    .collect::<Result<Vec<ApiToken>, _>>()?;
+
+  Response::from(tokens)
+}
+
+#[delete("/token/{id}")]
+pub(crate) async fn revoke_api_token(
+  id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  if user.api_token_id.is_some() {
+    return ResponseError::ErrorUnauthorized.into();
+  }
+
+  let stored: StoredApiToken = state.get_unchecked(*id)?;
+  // do not reveal the existence of other users tokens
+  if stored.user_id != user.id {
+    return ResponseError::ErrorNotFound.into();
+  }
+
+  state.unlist::<StoredApiToken>(user.id, *id)?;
+  state.delete::<StoredApiToken>(*id)?;
+
+  Response::empty()
+}
@@ -0,0 +1,239 @@
+use actix_session::Session;
+use actix_web::{delete, post, web, HttpMessage, HttpRequest};
+use einkaufsliste::model::requests::VerifyTotpV1;
+use einkaufsliste::model::totp::{RecoveryCodes, TotpCredentials, TotpEnrolment};
+use einkaufsliste::model::user::User;
+use rand::rngs::OsRng;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, TOTP};
+
+use super::user::login_user;
+use crate::db::DbError;
+use crate::response::{Response, ResponseError};
+use crate::util::errors::{bad_request, error};
+use crate::util::identity_ext::AuthenticatedUser;
+use crate::util::login_throttle::LoginThrottle;
+use crate::DbState;
+
+const ISSUER: &str = "Einkaufsliste";
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Session key remembering which user entered a correct password but still has to verify the second factor
+const PENDING_TOTP_KEY: &str = "pending_totp";
+/// Seconds a user has to enter the code after the password was accepted
+const PENDING_TOTP_TIMEOUT: i64 = 5 * 60;
+
+/// Starts the enrolment by generating a new secret. It is not enforced on login until it has been confirmed with a valid code.
+#[post("/totp/enrol")]
+pub(crate) async fn enrol_totp(
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<TotpEnrolment> {
+  match state.get_unchecked::<TotpCredentials>(user.id) {
+    Ok(credentials) if credentials.confirmed => {
+      return bad_request("Second factor is already enrolled").into()
+    }
+    Ok(_) | Err(DbError::NotFound) => {}
+    Err(e) => return ResponseError::from(e).into(),
+  }
+
+  let mut secret = vec![0; 20];
+  OsRng.fill(secret.as_mut_slice());
+
+  let profile: User = state.get_unchecked(user.id)?;
+  // colons are used as separator between issuer and account name in the uri
+  let totp = build_totp(secret.clone(), profile.name.replace(':', ""))?;
+
+  let credentials = TotpCredentials {
+    secret,
+    confirmed: false,
+    last_used_step: 0,
+    recovery_codes: vec![],
+  };
+  state.store_unlisted(&credentials, user.id)?;
+
+  Response::from(TotpEnrolment {
+    secret: totp.get_secret_base32(),
+    otpauth_uri: totp.get_url(),
+  })
+}
+
+/// Enforces the enrolled second factor from now on and hands out the recovery codes.
+#[post("/totp/confirm")]
+pub(crate) async fn confirm_totp(
+  param: VerifyTotpV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<RecoveryCodes> {
+  let mut credentials: TotpCredentials = state.get_unchecked(user.id)?;
+  if credentials.confirmed {
+    return bad_request("Second factor is already enrolled").into();
+  }
+
+  let step = match verify_code(&credentials, param.code.trim())? {
+    Some(step) => step,
+    None => return bad_request("Invalid TOTP code").into(),
+  };
+
+  let codes = (0..RECOVERY_CODE_COUNT)
+    .map(|_| generate_recovery_code())
+    .collect::<Vec<_>>();
+
+  credentials.confirmed = true;
+  credentials.last_used_step = step;
+  credentials.recovery_codes = codes.iter().map(|code| hash_recovery_code(code)).collect();
+  state.store_unlisted(&credentials, user.id)?;
+
+  Response::from(RecoveryCodes { codes })
+}
+
+#[delete("/totp")]
+pub(crate) async fn disable_totp(
+  param: VerifyTotpV1,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  let mut credentials: TotpCredentials = state.get_unchecked(user.id)?;
+
+  // an unconfirmed enrolment does not protect anything yet, so it may be discarded without a code
+  if credentials.confirmed && !verify_second_factor(&mut credentials, &param.code)? {
+    return bad_request("Invalid TOTP code").into();
+  }
+
+  state.delete::<TotpCredentials>(user.id)?;
+
+  Response::empty()
+}
+
+/// Second login stage for users with a second factor; only succeeds after the password has been accepted in the same session.
+#[post("/login/totp/v1")]
+pub(crate) async fn login_totp_v1(
+  param: VerifyTotpV1,
+  state: web::Data<DbState>,
+  throttle: web::Data<LoginThrottle>,
+  session: Session,
+  request: HttpRequest,
+) -> Response<User> {
+  let (user_id, deadline) = match session.get::<(u64, i64)>(PENDING_TOTP_KEY).map_err(error)? {
+    Some(pending) => pending,
+    None => return ResponseError::ErrorUnauthenticated.into(),
+  };
+  if deadline < now() {
+    session.remove(PENDING_TOTP_KEY);
+    return ResponseError::ErrorUnauthenticated.into();
+  }
+
+  let profile: User = state.get_unchecked(user_id)?;
+
+  // codes are a lot easier to guess than passwords, so failures count towards the same lockout
+  let user_key = LoginThrottle::user_key(&profile.name);
+  let peer_key = LoginThrottle::peer_key(&request);
+  throttle.register_attempt(&[&user_key, &peer_key])?;
+
+  let mut credentials: TotpCredentials = state.get_unchecked(user_id)?;
+  if !verify_second_factor(&mut credentials, &param.code)? {
+    return ResponseError::ErrorBadRequest.into();
+  }
+  throttle.release(&peer_key)?;
+  // persist the used step or the consumed recovery code
+  state.store_unlisted(&credentials, user_id)?;
+
+  throttle.reset(&user_key)?;
+  session.remove(PENDING_TOTP_KEY);
+  login_user(&request.extensions(), user_id)?;
+
+  Response::from(profile)
+}
+
+/// Checks whether the user has to verify a second factor and if so remembers the user in the session for [`login_totp_v1`].
+pub(crate) fn require_second_factor(
+  state: &DbState,
+  session: &Session,
+  user_id: u64,
+) -> Result<bool, ResponseError> {
+  match state.get_unchecked::<TotpCredentials>(user_id) {
+    Ok(credentials) if credentials.confirmed => {
+      session
+        .insert(PENDING_TOTP_KEY, (user_id, now() + PENDING_TOTP_TIMEOUT))
+        .map_err(error)?;
+
+      Ok(true)
+    }
+    Ok(_) | Err(DbError::NotFound) => Ok(false),
+    Err(e) => Err(e.into()),
+  }
+}
+
+/// Accepts either a current code or a recovery code, which is consumed. The caller has to store the updated credentials.
+fn verify_second_factor(
+  credentials: &mut TotpCredentials,
+  code: &str,
+) -> Result<bool, ResponseError> {
+  let code = code.trim();
+
+  if let Some(step) = verify_code(credentials, code)? {
+    credentials.last_used_step = step;
+    return Ok(true);
+  }
+
+  let hash = hash_recovery_code(code);
+  match credentials
+    .recovery_codes
+    .iter()
+    .position(|stored| *stored == hash)
+  {
+    Some(index) => {
+      credentials.recovery_codes.remove(index);
+      Ok(true)
+    }
+    None => Ok(false),
+  }
+}
+
+/// Returns the time step the code is valid for, unless it is invalid or has already been used.
+fn verify_code(credentials: &TotpCredentials, code: &str) -> Result<Option<u64>, ResponseError> {
+  let totp = build_totp(credentials.secret.clone(), String::new())?;
+  let current_step = now() as u64 / totp.step;
+  let skew = u64::from(totp.skew);
+
+  Ok(
+    (current_step.saturating_sub(skew)..=current_step + skew)
+      .filter(|step| *step > credentials.last_used_step)
+      .find(|step| totp.generate(step * totp.step) == code),
+  )
+}
+
+fn build_totp(secret: Vec<u8>, account_name: String) -> Result<TOTP, ResponseError> {
+  // these are the parameters every authenticator app supports
+  TOTP::new(
+    Algorithm::SHA1,
+    6,
+    1,
+    30,
+    secret,
+    Some(ISSUER.to_owned()),
+    account_name,
+  )
+  .map_err(error)
+}
+
+fn generate_recovery_code() -> String {
+  let code = format!("{:016x}", OsRng.gen::<u64>());
+
+  format!("{}-{}", &code[..8], &code[8..])
+}
+
+fn hash_recovery_code(code: &str) -> Vec<u8> {
+  // the codes are random and long enough that a fast hash suffices - unlike passwords
+  let normalized = code
+    .chars()
+    .filter(char::is_ascii_alphanumeric)
+    .collect::<String>()
+    .to_ascii_lowercase();
+
+  Sha256::digest(normalized.as_bytes()).to_vec()
+}
+
+fn now() -> i64 {
+  einkaufsliste::model::session::Session::get_current_time()
+}
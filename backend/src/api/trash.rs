@@ -0,0 +1,133 @@
+use actix_web::{delete, get, post, web};
+use einkaufsliste::model::activity::ActivityKind;
+use einkaufsliste::model::household::Household;
+use einkaufsliste::model::list::List;
+use einkaufsliste::model::trash::{TrashEntry, TrashedObject};
+use einkaufsliste::model::user::User;
+use einkaufsliste::model::Permission;
+
+use crate::db::{self, DbError, DbState};
+use crate::response::{Response, ResponseError};
+use crate::util::identity_ext::AuthenticatedUser;
+
+/// Moves the list with all of its items into the trash, see [`DbState::trash_list`]
+#[delete("/itemList/{id}")]
+pub(crate) async fn delete_item_list(
+  list_id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Manage)?;
+
+  state.trash_list(*list_id, user.id)?;
+
+  Response::empty()
+}
+
+/// Deleted lists of the user and of the households the user manages
+#[get("/trash")]
+pub(crate) async fn get_trashed_lists(
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<TrashEntry>> {
+  let mut entry_ids =
+    <db::DbState as db::ObjectStore<TrashEntry, sled::Tree, 512>>::object_list(&state, user.id)?
+      .list;
+
+  let household_ids =
+    <db::DbState as db::ObjectStore<Household, sled::Tree, 512>>::object_list(&state, user.id)?;
+  for household_id in household_ids.list {
+    let household_entries =
+      <db::DbState as db::ObjectStore<TrashEntry, sled::Tree, 512>>::object_list(
+        &state,
+        household_id,
+      )?;
+    entry_ids.extend(household_entries.list);
+  }
+
+  let mut entries = Vec::with_capacity(entry_ids.len());
+  for id in entry_ids {
+    match state.verify_access::<List, User>(id, user.id, Permission::Manage) {
+      Ok(()) => entries.push(state.get_unchecked::<TrashEntry>(id)?),
+      Err(DbError::Mismatch) => continue,
+      Err(e) => return ResponseError::from(e).into(),
+    }
+  }
+
+  Response::from(entries)
+}
+
+/// Deleted items of the list, in the order they were deleted in
+#[get("/itemList/{id}/trash")]
+pub(crate) async fn get_trashed_items(
+  list_id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<TrashEntry>> {
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Read)?;
+
+  let entry_ids =
+    <db::DbState as db::ObjectStore<TrashEntry, sled::Tree, 512>>::object_list(&state, *list_id)?;
+
+  let entries = entry_ids
+    .list
+    .into_iter()
+    .map(|id| state.get_unchecked(id))
+    .collect::<Result<Vec<TrashEntry>, _>>()?;
+
+  Response::from(entries)
+}
+
+#[post("/trash/{id}/restore")]
+pub(crate) async fn restore_trash(
+  entry_id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  let entry = load_entry(&state, *entry_id, user.id)?;
+
+  state.restore_trash(&entry)?;
+
+  if let TrashedObject::Item { item, list_id, .. } = entry.object {
+    state.record_activity(
+      list_id,
+      Some(user.id),
+      ActivityKind::ItemRestored {
+        item_id: entry.id,
+        name: item.name,
+      },
+    )?;
+  }
+
+  Response::empty()
+}
+
+/// Deletes the entry for good instead of waiting for the retention period to pass
+#[delete("/trash/{id}")]
+pub(crate) async fn purge_trash(
+  entry_id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  let entry = load_entry(&state, *entry_id, user.id)?;
+
+  state.purge_trash(&entry)?;
+
+  Response::empty()
+}
+
+/// Items require the same permission as deleting them, lists require to be allowed to manage them
+fn load_entry(state: &DbState, entry_id: u64, user_id: u64) -> Result<TrashEntry, ResponseError> {
+  let entry: TrashEntry = state.get_unchecked(entry_id)?;
+
+  match &entry.object {
+    TrashedObject::Item { list_id, .. } => {
+      state.verify_access::<List, User>(*list_id, user_id, Permission::Write)?
+    }
+    TrashedObject::List(_) => {
+      state.verify_access::<List, User>(entry_id, user_id, Permission::Manage)?
+    }
+  }
+
+  Ok(entry)
+}
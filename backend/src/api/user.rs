@@ -1,24 +1,39 @@
 use std::fmt::Display;
 
 use actix_identity::Identity;
+use actix_session::Session;
 use actix_web::dev::Extensions;
 use actix_web::{self, get, post, web, HttpMessage, HttpRequest};
+use einkaufsliste::model::household::Household;
 use einkaufsliste::model::list::List;
 use einkaufsliste::model::requests::{LoginUserV1, RegisterUserV1};
-use einkaufsliste::model::user::{User, UserWithPassword};
+use einkaufsliste::model::user::{LoginResponseV1, User, UserWithPassword};
 use einkaufsliste::model::Identifiable;
 
+use super::invite::{apply_invite, redeem_invite};
+use super::totp::require_second_factor;
+use crate::db::DbError;
 use crate::response::*;
+use crate::util::config::{BackendConfig, RegistrationMode};
 use crate::util::errors::{bad_request, error};
 use crate::util::identity_ext::AuthenticatedUser;
+use crate::util::login_throttle::LoginThrottle;
 use crate::{db, DbState};
 
 #[post("/register/v1")]
 pub(crate) async fn register_v1(
   parameter: RegisterUserV1,
   data: web::Data<DbState>,
+  config: web::Data<BackendConfig>,
   request: HttpRequest,
 ) -> Response<User> {
+  if config.registration == RegistrationMode::Closed {
+    return ResponseError::ErrorUnauthorized.into();
+  }
+  if config.registration == RegistrationMode::InviteOnly && parameter.invite_code.is_none() {
+    return bad_request("Registration requires an invite code").into();
+  }
+
   // validate registration request- kekw
   if parameter.password.len() < 8 {
     return bad_request("Password too short").into();
@@ -27,7 +42,13 @@ pub(crate) async fn register_v1(
     return bad_request("User already exists").into();
   }
 
-  let hashed_pw = DbState::hash_password(&parameter.password)?;
+  // only consume the invite once the request is known to be valid otherwise
+  let invite = match &parameter.invite_code {
+    Some(code) => Some(redeem_invite(&data, code)?),
+    None => None,
+  };
+
+  let hashed_pw = data.hash_password(parameter.password.clone()).await?;
   let id = data.db.generate_id().map_err(error)?;
 
   let value = UserWithPassword {
@@ -42,6 +63,10 @@ pub(crate) async fn register_v1(
   data.new_user(&value)?;
   data.store_unlisted(&value.user, id)?;
 
+  if let Some(invite) = &invite {
+    apply_invite(&data, invite, id)?;
+  }
+
   // there isn't really a point in not logging the user in here
   login_user(&request.extensions(), id)?;
 
@@ -53,14 +78,33 @@ pub(crate) async fn register_v1(
 pub(crate) async fn login_v1(
   login_request: LoginUserV1,
   state: web::Data<DbState>,
+  throttle: web::Data<LoginThrottle>,
+  session: Session,
   request: HttpRequest,
-) -> Response<User> {
-  let user = state.check_password(&login_request)?;
+) -> Response<LoginResponseV1> {
+  let user_key = LoginThrottle::user_key(&login_request.name);
+  let peer_key = LoginThrottle::peer_key(&request);
+  throttle.register_attempt(&[&user_key, &peer_key])?;
+
+  let user = match state.check_password(login_request).await {
+    Err(DbError::Mismatch) => return ResponseError::ErrorBadRequest.into(),
+    result => result?,
+  };
+  // the peer is not reset, as logging into an own account must not allow guessing other accounts passwords
+  throttle.release(&peer_key)?;
+
+  // the lockout is only reset once the second factor has been verified as well
+  if require_second_factor(&state, &session, user.user.id)? {
+    throttle.release(&user_key)?;
+    return Response::from(LoginResponseV1::TotpRequired);
+  }
+
+  throttle.reset(&user_key)?;
 
   // remember user id for session
   login_user(&request.extensions(), user.user.id)?;
 
-  Response::from(user.user)
+  Response::from(LoginResponseV1::Authenticated(user.user))
 }
 
 #[allow(clippy::enum_variant_names)] // this is an error enum
@@ -109,11 +153,22 @@ pub(crate) async fn get_users_lists(
   user: AuthenticatedUser,
 ) -> Response<Vec<List>> {
   // read ObjectList from DB
-  let list_ids =
-    <db::DbState as db::ObjectStore<List, sled::Tree, 512>>::object_list(&state, user.id)?;
+  let mut list_ids =
+    <db::DbState as db::ObjectStore<List, sled::Tree, 512>>::object_list(&state, user.id)?.list;
+
+  // lists of households are listed under the household instead of the individual members
+  let household_ids =
+    <db::DbState as db::ObjectStore<Household, sled::Tree, 512>>::object_list(&state, user.id)?;
+  for household_id in household_ids.list {
+    let household_lists =
+      <db::DbState as db::ObjectStore<List, sled::Tree, 512>>::object_list(&state, household_id)?;
+    list_ids.extend(household_lists.list);
+  }
+  // a list moved into a household may still be listed for members it was shared with directly
+  let mut seen = std::collections::HashSet::new();
+  list_ids.retain(|id| seen.insert(*id));
 
   let lists = list_ids
-    .list
     .into_iter()
     .map(|id| state.get_unchecked(id))
     .collect::<Result<Vec<List>, _>>()?;
@@ -121,6 +176,15 @@ pub(crate) async fn get_users_lists(
   Response::from(lists)
 }
 
+/// Looks up the user a request refers to by name, as ids of other users are not known to clients
+pub(crate) fn find_user_id(state: &DbState, name: &str) -> Result<u64, ResponseError> {
+  match state.get_user(name) {
+    Ok(user) => Ok(user.user.id),
+    Err(DbError::Mismatch) => Err(ResponseError::ErrorNotFound),
+    Err(e) => Err(e.into()),
+  }
+}
+
 pub fn login_user(
   exts: &Extensions,
   id: <User as Identifiable>::Id,
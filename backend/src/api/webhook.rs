@@ -0,0 +1,141 @@
+use actix_web::{delete, get, post, web};
+use einkaufsliste::model::list::List;
+use einkaufsliste::model::requests::CreateWebhookV1;
+use einkaufsliste::model::session::Session;
+use einkaufsliste::model::user::User;
+use einkaufsliste::model::webhook::{CreatedWebhook, StoredWebhook, Webhook, WebhookDelivery};
+use einkaufsliste::model::Permission;
+
+use crate::db::{self, DbState};
+use crate::response::{Response, ResponseError};
+use crate::util::config::BackendConfig;
+use crate::util::errors::bad_request;
+use crate::util::identity_ext::AuthenticatedUser;
+use crate::util::secret::generate_secret;
+use crate::util::webhook::resolve_webhook_url;
+
+/// Number of delivery attempts returned by [`get_webhook_deliveries`]
+const DELIVERY_LOG_LIMIT: usize = 50;
+
+/// The secret used to sign the payloads is only returned here, it cannot be retrieved later on.
+#[post("/webhook")]
+pub(crate) async fn create_webhook(
+  param: CreateWebhookV1,
+  state: web::Data<DbState>,
+  config: web::Data<BackendConfig>,
+  user: AuthenticatedUser,
+) -> Response<CreatedWebhook> {
+  let url = match reqwest::Url::parse(&param.url) {
+    Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+    _ => return bad_request("Webhook url has to be an http or https url").into(),
+  };
+  // checked again on every delivery, as the host may resolve differently later on
+  if let Err(e) = resolve_webhook_url(&url, config.webhook_allow_private).await {
+    return bad_request(e).into();
+  }
+  if let Some(list_id) = param.list_id {
+    state.verify_access::<List, User>(list_id, user.id, Permission::Manage)?;
+  }
+
+  let id = state.db.generate_id()?;
+  let hook = Webhook {
+    id,
+    url: param.url,
+    list_id: param.list_id,
+    created: Session::get_current_time(),
+  };
+  let stored = StoredWebhook {
+    hook: hook.clone(),
+    owner: user.id,
+    secret: generate_secret(),
+  };
+  // webhooks of a list are listed under the list, so every owner of the list can manage them
+  state.store_listed(&stored, param.list_id.unwrap_or(user.id), id)?;
+
+  Response::from(CreatedWebhook {
+    hook,
+    secret: stored.secret,
+  })
+}
+
+/// Webhooks notified about changes of any list of the user
+#[get("/webhook")]
+pub(crate) async fn list_webhooks(
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<Webhook>> {
+  Response::from(load_webhooks(&state, user.id)?)
+}
+
+#[get("/itemList/{id}/webhook")]
+pub(crate) async fn list_item_list_webhooks(
+  list_id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<Webhook>> {
+  state.verify_access::<List, User>(*list_id, user.id, Permission::Manage)?;
+
+  Response::from(load_webhooks(&state, *list_id)?)
+}
+
+#[delete("/webhook/{id}")]
+pub(crate) async fn delete_webhook(
+  webhook_id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<()> {
+  let webhook = load_managed_webhook(&state, *webhook_id, user.id)?;
+
+  state.delete_webhook(&webhook)?;
+
+  Response::empty()
+}
+
+/// The latest delivery attempts, newest first
+#[get("/webhook/{id}/deliveries")]
+pub(crate) async fn get_webhook_deliveries(
+  webhook_id: web::Path<u64>,
+  state: web::Data<DbState>,
+  user: AuthenticatedUser,
+) -> Response<Vec<WebhookDelivery>> {
+  load_managed_webhook(&state, *webhook_id, user.id)?;
+
+  Response::from(state.list_deliveries(*webhook_id, DELIVERY_LOG_LIMIT)?)
+}
+
+fn load_webhooks(state: &DbState, listed_under: u64) -> Result<Vec<Webhook>, ResponseError> {
+  let webhook_ids = <db::DbState as db::ObjectStore<StoredWebhook, sled::Tree, 512>>::object_list(
+    state,
+    listed_under,
+  )?;
+
+  let webhooks = webhook_ids
+    .list
+    .into_iter()
+    .map(|id| {
+      state
+        .get_unchecked::<StoredWebhook>(id)
+        .map(|stored| stored.hook)
+    })
+    .collect::<Result<Vec<Webhook>, _>>()?;
+
+  Ok(webhooks)
+}
+
+/// Webhooks of a list can be managed by the owners of the list, those of an account only by the account itself
+fn load_managed_webhook(
+  state: &DbState,
+  webhook_id: u64,
+  user_id: u64,
+) -> Result<StoredWebhook, ResponseError> {
+  let webhook: StoredWebhook = state.get_unchecked(webhook_id)?;
+
+  match webhook.hook.list_id {
+    Some(list_id) => state.verify_access::<List, User>(list_id, user_id, Permission::Manage)?,
+    None if webhook.owner == user_id => {}
+    // do not reveal the existence of webhooks of other users
+    None => return Err(ResponseError::ErrorNotFound),
+  }
+
+  Ok(webhook)
+}
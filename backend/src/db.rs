@@ -1,22 +1,49 @@
-use argon2::Argon2;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use actix_web::error::BlockingError;
+use actix_web::web;
+use argon2::password_hash::{self, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use einkaufsliste::model::activity::{ActivityEvent, ActivityKind};
 use einkaufsliste::model::article::Article;
+use einkaufsliste::model::category::Category;
+use einkaufsliste::model::household::Household;
 use einkaufsliste::model::item::Item;
 use einkaufsliste::model::list::List;
+use einkaufsliste::model::patch::ListPatchV1;
 use einkaufsliste::model::requests::LoginUserV1;
-use einkaufsliste::model::shop::Shop;
+use einkaufsliste::model::session::Session;
+use einkaufsliste::model::share::StoredShareLink;
+use einkaufsliste::model::shop::{CheckOff, Shop};
+use einkaufsliste::model::token::StoredApiToken;
+use einkaufsliste::model::totp::TotpCredentials;
+use einkaufsliste::model::trash::{TrashEntry, TrashedObject};
 use einkaufsliste::model::user::{ObjectList, Password, User, UserWithPassword, UsersObjectLists};
-use einkaufsliste::model::{AccessControlList, HasTypeDenominator, Identifiable};
+use einkaufsliste::model::webhook::{
+  PendingDelivery, StoredWebhook, WebhookDelivery, WebhookPayload,
+};
+use einkaufsliste::model::{
+  AccessControlList, HasTypeDenominator, Identifiable, Member, Permission, Role,
+};
 use einkaufsliste::ApiObject;
 use rand::{thread_rng, Rng};
 use rkyv::de::deserializers::{SharedDeserializeMap, SharedDeserializeMapError};
 use rkyv::ser::serializers::{AllocScratchError, CompositeSerializerError};
 use rkyv::Archive;
-use sled::transaction::{TransactionError, TransactionalTree, UnabortableTransactionError};
+use sled::transaction::{
+  ConflictableTransactionResult, TransactionError, Transactional, TransactionalTree,
+  UnabortableTransactionError,
+};
+use tokio::sync::Notify;
 use tracing::debug;
 use zerocopy::AsBytes;
 
 use crate::util::errors::abort_error;
 
+/// Number of check-offs kept per shop to learn its walking order from
+const CHECK_OFF_HISTORY: usize = 1000;
+
 #[derive(Clone)]
 pub struct DbState {
   pub db: sled::Db,
@@ -28,70 +55,134 @@ pub struct DbState {
   pub user_db: sled::Tree,
   pub login_db: sled::Tree,
   pub object_list_db: sled::Tree,
+  pub totp_db: sled::Tree,
+  pub api_token_db: sled::Tree,
+  pub invite_db: sled::Tree,
+  pub household_db: sled::Tree,
+  pub share_link_db: sled::Tree,
+  pub activity_db: sled::Tree,
+  /// Check-offs per shop, keyed like the activity log
+  pub check_off_db: sled::Tree,
+  pub trash_db: sled::Tree,
+  pub category_db: sled::Tree,
+  pub webhook_db: sled::Tree,
+  /// Deliveries keyed by the time they are due, see [`DbState::schedule_delivery`]
+  pub webhook_queue_db: sled::Tree,
+  pub webhook_delivery_db: sled::Tree,
+  /// Wakes up the webhook dispatcher as soon as a delivery is scheduled
+  pub webhook_notify: Arc<Notify>,
+  pub argon2_params: Params,
 }
 
 impl DbState {
-  pub fn check_password(&self, login: &LoginUserV1) -> Result<UserWithPassword, DbError> {
-    let user = self.get_user(&login.name)?;
-    let request_pw_hash = Self::hash_password_with_salt(&login.password, &user.password.salt);
+  /// Verifies the login on a blocking thread and transparently rehashes the password if it was hashed with outdated parameters.
+  pub async fn check_password(&self, login: LoginUserV1) -> Result<UserWithPassword, DbError> {
+    let mut user = self.get_user(&login.name)?;
+
+    let stored = user.password.clone();
+    let password = login.password.clone();
+    let params = self.argon2_params.clone();
+    let outdated = web::block(move || match &stored {
+      Password::Phc(hash) => Self::verify_password(&password, hash, &params),
+      // legacy hashes are always replaced, as they lack the parameters they were created with
+      Password::Legacy { hash, salt } => {
+        Self::verify_legacy_password(&password, hash, salt).map(|_| true)
+      }
+    })
+    .await?
+    .map_err(|e| match e {
+      password_hash::Error::Password => {
+        debug!("Password validation error for user {}", user.user.id);
+        DbError::Mismatch
+      }
+      e => DbError::Encoding(e.to_string().into()),
+    })?;
 
-    if Self::hash_password_with_salt(&login.password, &user.password.salt)? == user.password.hash {
-      Ok(user)
-    } else {
-      debug!(
-        "Password validation error: {:?}, {:?}",
-        request_pw_hash, user.password.hash
-      );
-      Err(DbError::Mismatch)
+    if outdated {
+      debug!("Rehashing password of user {} with current parameters", user.user.id);
+      user.password = self.hash_password(login.password).await?;
+      self.new_user(&user)?;
     }
+
+    Ok(user)
   }
 
-  pub(crate) fn hash_password_with_salt(password: &str, salt: &[u8]) -> Result<Vec<u8>, DbError> {
-    // Hash length does not matter much for password verification - 256 bit is definitely fine
-    let mut bytes = vec![0; 32];
+  /// Returns whether the stored hash was created with different parameters than the currently configured ones.
+  fn verify_password(
+    password: &str,
+    phc_hash: &str,
+    params: &Params,
+  ) -> Result<bool, password_hash::Error> {
+    let hash = PasswordHash::new(phc_hash)?;
+
+    // the parameters of the stored hash are used for verification, not the configured ones
+    Argon2::default().verify_password(password.as_bytes(), &hash)?;
+
+    let outdated = match Params::try_from(&hash) {
+      Ok(stored) => {
+        hash.algorithm != Algorithm::Argon2id.ident() ||
+          stored.m_cost() != params.m_cost() ||
+          stored.t_cost() != params.t_cost() ||
+          stored.p_cost() != params.p_cost()
+      }
+      Err(_) => true,
+    };
 
-    Argon2::default()
-      .hash_password_into(password.as_bytes(), salt, &mut bytes)
-      .map_err(|e| DbError::IO(e.to_string().into()))?;
+    Ok(outdated)
+  }
+
+  /// Verifies a [`Password::Legacy`] hash, which was created with the default parameters and a 32 byte output.
+  fn verify_legacy_password(
+    password: &str,
+    hash: &[u8],
+    salt: &[u8],
+  ) -> Result<(), password_hash::Error> {
+    let computed = Self::hash_password_with_salt(password, salt)?;
+
+    // outputs are compared in constant time
+    if password_hash::Output::new(&computed)? == password_hash::Output::new(hash)? {
+      Ok(())
+    } else {
+      Err(password_hash::Error::Password)
+    }
+  }
 
+  fn hash_password_with_salt(password: &str, salt: &[u8]) -> Result<Vec<u8>, argon2::Error> {
+    let mut bytes = vec![0; 32];
+    Argon2::default().hash_password_into(password.as_bytes(), salt, &mut bytes)?;
     Ok(bytes)
   }
 
-  pub(crate) fn hash_password(password: &str) -> Result<Password, DbError> {
-    // there is no need for the salt to be securely generated as even a normal random number prevents rainbow-table attacks
-    let mut salt = [0; 32];
-    thread_rng().fill(&mut salt);
+  /// Hashes the password on a blocking thread as Argon2 is far too slow to run on the async executor.
+  pub(crate) async fn hash_password(&self, password: String) -> Result<Password, DbError> {
+    let params = self.argon2_params.clone();
 
-    let hash = Self::hash_password_with_salt(password, &salt)?;
+    let hash = web::block(move || {
+      // there is no need for the salt to be securely generated as even a normal random number prevents rainbow-table attacks
+      let mut salt = [0; 16];
+      thread_rng().fill(&mut salt);
+      let salt = SaltString::encode_b64(&salt)?;
 
-    Ok(Password {
-      hash,
-      salt: salt.to_vec(),
+      Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
     })
+    .await?
+    .map_err(|e| DbError::Encoding(e.to_string().into()))?;
+
+    Ok(Password::Phc(hash))
   }
 
-  pub(crate) fn verify_access<Object: Identifiable, User: Identifiable>(
+  /// Fails with [`DbError::Mismatch`] unless the user has a role on the object that grants the permission
+  pub(crate) fn verify_access<Object: Identifiable, User: Identifiable<Id = u64>>(
     &self,
     object_id: <Object as Identifiable>::Id,
     user_id: <User as Identifiable>::Id,
+    permission: Permission,
   ) -> Result<(), DbError>
-  // don't ask me bro... thanks to rust analyzer we dont have to ask bro
   where
-    <<Object as einkaufsliste::model::Identifiable>::Id as rkyv::Archive>::Archived:
-      rkyv::Deserialize<
-        <Object as einkaufsliste::model::Identifiable>::Id,
-        rkyv::de::deserializers::SharedDeserializeMap,
-      >,
-    [<<User as einkaufsliste::model::Identifiable>::Id as rkyv::Archive>::Archived]:
-      rkyv::DeserializeUnsized<
-        [<User as einkaufsliste::model::Identifiable>::Id],
-        rkyv::de::deserializers::SharedDeserializeMap,
-      >,
-    <<User as einkaufsliste::model::Identifiable>::Id as rkyv::Archive>::Archived:
-      rkyv::Deserialize<
-        <User as einkaufsliste::model::Identifiable>::Id,
-        rkyv::de::deserializers::SharedDeserializeMap,
-      >,
+    <AccessControlList<Object, User> as rkyv::Archive>::Archived:
+      rkyv::Deserialize<AccessControlList<Object, User>, SharedDeserializeMap>,
   {
     let acl = self
       .acl_db
@@ -101,27 +192,183 @@ impl DbState {
     let acl =
       unsafe { rkyv::from_bytes_unchecked::<AccessControlList<Object, User>>(acl.as_bytes()) }?;
 
-    match acl.owner == user_id || acl.allowed_user_ids.contains(&user_id) {
+    if acl.permits(&user_id, permission) {
+      return Ok(());
+    }
+
+    // the household is only looked up if the role of the user on the object itself does not suffice
+    let household_role = match acl.household {
+      Some(household_id) => self
+        .get_unchecked::<Household>(household_id)?
+        .role_of(user_id),
+      None => None,
+    };
+
+    match household_role.is_some_and(|role| role.permits(permission)) {
       true => Ok(()),
       false => Err(DbError::Mismatch),
     }
   }
 
+  /// Like [`DbState::verify_access`], but items of a trashed list are not found until the list is restored
+  pub(crate) fn verify_item_access(
+    &self,
+    item_id: u64,
+    user_id: u64,
+    permission: Permission,
+  ) -> Result<(), DbError> {
+    self.verify_access::<Item, User>(item_id, user_id, permission)?;
+
+    // the items keep the ACL of their list while it is in the trash, so it can be restored with it
+    match self
+      .list_db
+      .contains_key(self.list_of_item(item_id)?.as_bytes())?
+    {
+      true => Ok(()),
+      false => Err(DbError::NotFound),
+    }
+  }
+
   pub fn copy_acl<List: Identifiable, Item: Identifiable>(
     &self,
     list_id: <List as Identifiable>::Id,
     item_id: <Item as Identifiable>::Id,
   ) -> Result<(), DbError> {
-    let list_acl = self
+    self
       .acl_db
+      .transaction(|tx_db| Self::copy_acl_in::<List, Item>(tx_db, &list_id, &item_id))
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  fn copy_acl_in<List: Identifiable, Item: Identifiable>(
+    tx_db: &TransactionalTree,
+    list_id: &<List as Identifiable>::Id,
+    item_id: &<Item as Identifiable>::Id,
+  ) -> ConflictableTransactionResult<(), DbError> {
+    let list_acl = tx_db
       .get(list_id.as_bytes())?
-      .ok_or(DbError::NotFound)?;
+      .ok_or(abort_error(DbError::NotFound))?;
 
-    self.acl_db.insert(item_id.as_bytes(), list_acl)?;
+    tx_db.insert(item_id.as_bytes(), list_acl)?;
 
     Ok(())
   }
 
+  /**
+  Moves the item to the position within the target list, which may be the list it is in already.
+  Positions past the end append the item. Moving it to another list re-copies the ACL of that list onto the item.
+  */
+  pub fn move_item(
+    &self,
+    from_list_id: u64,
+    to_list_id: u64,
+    item_id: u64,
+    position: Option<u64>,
+  ) -> Result<(), DbError> {
+    (&self.list_db, &self.acl_db)
+      .transaction(|(list_tx, acl_tx)| unsafe {
+        let mut source =
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, from_list_id)
+            .map_err(abort_error)?;
+        let index = source
+          .items
+          .iter()
+          .position(|&id| id == item_id)
+          .ok_or(abort_error(DbError::NotFound))?;
+        source.items.remove(index);
+
+        let mut target = match from_list_id == to_list_id {
+          true => source,
+          false => {
+            <&TransactionalTree as RawRkyvStore<List, 4096>>::store_unlisted(
+              &list_tx,
+              from_list_id,
+              &source,
+            )
+            .map_err(abort_error)?;
+            Self::copy_acl_in::<List, Item>(acl_tx, &to_list_id, &item_id)?;
+
+            <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, to_list_id)
+              .map_err(abort_error)?
+          }
+        };
+
+        let position = position.map_or(target.items.len(), |position| {
+          (position as usize).min(target.items.len())
+        });
+        target.items.insert(position, item_id);
+        <&TransactionalTree as RawRkyvStore<List, 4096>>::store_unlisted(
+          &list_tx, to_list_id, &target,
+        )
+        .map_err(abort_error)?;
+
+        Ok(())
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /**
+  Stores the items under new ids and appends them to the list in one transaction, returns what happened to each of
+  them in order. With `merge`, items are added to an unchecked duplicate instead where possible, see
+  `merge_into_duplicate`, which includes the items added before them.
+  */
+  pub fn add_items(
+    &self,
+    list_id: u64,
+    items: &[Item],
+    merge: bool,
+  ) -> Result<Vec<AddedItem>, DbError> {
+    let item_ids = items
+      .iter()
+      .map(|_| self.db.generate_id())
+      .collect::<Result<Vec<_>, _>>()?;
+
+    (&self.item_db, &self.list_db, &self.acl_db)
+      .transaction(|(item_tx, list_tx, acl_tx)| unsafe {
+        let mut list =
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, list_id)
+            .map_err(abort_error)?;
+
+        let mut added = Vec::with_capacity(items.len());
+        for (item, &item_id) in items.iter().zip(&item_ids) {
+          if merge {
+            if let Some(duplicate) = Self::merge_into_duplicate_in(item_tx, &list, item)? {
+              added.push(AddedItem::Merged(duplicate));
+              continue;
+            }
+          }
+
+          let item = Item {
+            id: item_id,
+            ..item.clone()
+          };
+          <&TransactionalTree as RawRkyvStore<Item, 4096>>::store_unlisted(
+            &item_tx, item_id, &item,
+          )
+          .map_err(abort_error)?;
+          // ensure that we can get items independent of their corresponding list
+          Self::copy_acl_in::<List, Item>(acl_tx, &list_id, &item_id)?;
+          list.items.push(item_id);
+          added.push(AddedItem::Created(item_id));
+        }
+
+        <&TransactionalTree as RawRkyvStore<List, 4096>>::store_unlisted(&list_tx, list_id, &list)
+          .map_err(abort_error)?;
+
+        Ok(added)
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
   /// Generates and stores new AccessControlList
   pub fn create_acl<Object: Identifiable, User: Identifiable>(
     &self,
@@ -152,7 +399,8 @@ impl DbState {
   {
     let new_acl = AccessControlList::<Object, User> {
       object_id: object_id.clone(),
-      allowed_user_ids: vec![],
+      members: vec![],
+      household: None,
       owner: user_id,
     };
 
@@ -225,6 +473,44 @@ impl DbState {
     Ok(())
   }
 
+  /**
+  Reads the object, modifies it and stores it again in one transaction,
+  so changes made to the object in the meantime are not overwritten. Returns the previous and the modified object.
+
+  This is how patches are applied: only the fields set in the patch are changed, so concurrent changes to other fields
+  are kept.
+  */
+  pub fn modify<T: ApiObject<'static> + Clone>(
+    &self,
+    id: u64,
+    modify: impl Fn(&mut T),
+  ) -> Result<(T, T), DbError>
+  where
+    <T as rkyv::Archive>::Archived:
+      rkyv::Deserialize<T, rkyv::de::deserializers::SharedDeserializeMap>,
+    <T as rkyv::Archive>::Archived:
+      rkyv::CheckBytes<rkyv::validation::validators::DefaultValidator<'static>>,
+    Self: ObjectTree<T>,
+  {
+    let tree = <Self as ObjectTree<T>>::get_tree(self);
+
+    tree
+      .transaction(|tx| unsafe {
+        let previous = <&TransactionalTree as RawRkyvStore<T, 4096>>::get_unchecked(&tx, id)
+          .map_err(abort_error)?;
+        let mut object = previous.clone();
+        modify(&mut object);
+        <&TransactionalTree as RawRkyvStore<T, 4096>>::store_unlisted(&tx, id, &object)
+          .map_err(abort_error)?;
+
+        Ok((previous, object))
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
   pub fn store_listed<T: ApiObject<'static> + HasTypeDenominator>(
     &self,
     value: &T,
@@ -244,6 +530,1456 @@ impl DbState {
 
     Ok(())
   }
+
+  /// Adds the id to the users object list without touching the object itself
+  pub fn enlist<T: HasTypeDenominator>(&self, user_id: u64, id: u64) -> Result<(), DbError> {
+    self
+      .object_list_db
+      .transaction(|tx_db| Self::enlist_in(tx_db, T::DENOMINATOR, user_id, id))
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  fn enlist_in(
+    tx_db: &TransactionalTree,
+    denominator: u64,
+    user_id: u64,
+    id: u64,
+  ) -> ConflictableTransactionResult<(), DbError> {
+    let mut current_ol = match tx_db.get(user_id.to_ne_bytes())? {
+      Some(bytes) => unsafe {
+        rkyv::from_bytes_unchecked::<UsersObjectLists>(&bytes).map_err(abort_error)?
+      },
+      None => UsersObjectLists { lists: vec![] },
+    };
+
+    match current_ol
+      .lists
+      .iter_mut()
+      .find(|list| list.typ == denominator)
+    {
+      Some(ol) if ol.list.contains(&id) => return Ok(()),
+      Some(ol) => ol.list.push(id),
+      None => {
+        let mut new_ol = ObjectList::new(denominator);
+        new_ol.list.push(id);
+        current_ol.lists.push(new_ol);
+      }
+    }
+
+    tx_db.insert(
+      &user_id.to_ne_bytes(),
+      &*rkyv::to_bytes::<_, 512>(&current_ol).map_err(abort_error)?,
+    )?;
+
+    Ok(())
+  }
+
+  /**
+  Shares the list with the user and adds it to the users object list so it shows up among the users lists.
+  Existing members keep their role, changing it is up to the owner.
+  */
+  pub fn grant_list_access(&self, list_id: u64, user_id: u64, role: Role) -> Result<(), DbError> {
+    self.update_list_acls(list_id, |acl| {
+      if acl.role_of(&user_id).is_none() {
+        acl.members.push(Member { user_id, role });
+      }
+    })?;
+
+    self.enlist::<List>(user_id, list_id)
+  }
+
+  /// Items carry a copy of the list ACL, so they are updated along with the list in a single transaction.
+  pub fn update_list_acls(
+    &self,
+    list_id: u64,
+    update: impl Fn(&mut AccessControlList<List, User>),
+  ) -> Result<(), DbError> {
+    let list: List = self.get_unchecked(list_id)?;
+
+    self
+      .acl_db
+      .transaction(|tx_db| {
+        Self::update_list_acls_in(
+          tx_db,
+          std::iter::once(list_id).chain(list.items.iter().copied()),
+          &update,
+        )
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// Objects without an ACL are skipped
+  fn update_list_acls_in(
+    tx_db: &TransactionalTree,
+    object_ids: impl IntoIterator<Item = u64>,
+    update: &impl Fn(&mut AccessControlList<List, User>),
+  ) -> ConflictableTransactionResult<(), DbError> {
+    for object_id in object_ids {
+      let mut acl = match tx_db.get(object_id.as_bytes())? {
+        Some(bytes) => unsafe {
+          rkyv::from_bytes_unchecked::<AccessControlList<List, User>>(&bytes)
+            .map_err(abort_error)?
+        },
+        None => continue,
+      };
+
+      update(&mut acl);
+
+      tx_db.insert(
+        object_id.as_bytes(),
+        &*rkyv::to_bytes::<_, 256>(&acl).map_err(abort_error)?,
+      )?;
+    }
+
+    Ok(())
+  }
+
+  /**
+  Creates the list with its ACL in one transaction. The household itself owns the list,
+  so its members get access according to their role in the household.
+  */
+  pub fn create_household_list(&self, list: &List, household_id: u64) -> Result<(), DbError> {
+    let acl = AccessControlList::<List, User> {
+      object_id: list.id,
+      owner: household_id,
+      members: vec![],
+      household: Some(household_id),
+    };
+
+    (&self.list_db, &self.acl_db, &self.object_list_db)
+      .transaction(|(list_tx, acl_tx, object_list_tx)| {
+        unsafe {
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::store_unlisted(&list_tx, list.id, list)
+        }
+        .map_err(abort_error)?;
+        acl_tx.insert(
+          list.id.as_bytes(),
+          &*rkyv::to_bytes::<_, 256>(&acl).map_err(abort_error)?,
+        )?;
+        Self::enlist_in(object_list_tx, List::DENOMINATOR, household_id, list.id)?;
+
+        Ok(())
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /**
+  Moves the list into the household, out of the object list of whoever it was listed under so far.
+  Lists owned by another household become owned by this one. Direct members of the list keep their access.
+  */
+  pub fn move_list_to_household(&self, list_id: u64, household_id: u64) -> Result<(), DbError> {
+    (&self.list_db, &self.acl_db, &self.object_list_db)
+      .transaction(|(list_tx, acl_tx, object_list_tx)| {
+        let list = unsafe {
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, list_id)
+        }
+        .map_err(abort_error)?;
+        let acl = Self::list_acl_in(acl_tx, list_id)?;
+        // see `list_holders`, members stay listed as they keep their access
+        let holder = acl.household.unwrap_or(acl.owner);
+
+        // items in the trash keep their ACL as well
+        let trashed_item_ids = Self::listed_in(object_list_tx, TrashEntry::DENOMINATOR, list_id)?;
+        Self::update_list_acls_in(
+          acl_tx,
+          std::iter::once(list_id)
+            .chain(list.items)
+            .chain(trashed_item_ids),
+          &|acl| {
+            if Some(acl.owner) == acl.household {
+              acl.owner = household_id;
+            }
+            acl.household = Some(household_id);
+          },
+        )?;
+        Self::unlist_in(object_list_tx, List::DENOMINATOR, holder, list_id)?;
+        Self::enlist_in(object_list_tx, List::DENOMINATOR, household_id, list_id)?;
+
+        Ok(())
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /**
+  Makes the user the owner of the list and all of its items and adds the list to the object list of the new owner.
+  The previous owner stays on as member, so the list is only removed from their object list once they lose access.
+  Everything is updated in a single transaction, so the list can not get lost.
+  */
+  pub fn transfer_list_ownership(
+    &self,
+    list_id: u64,
+    new_owner: u64,
+    previous_owner_role: Role,
+  ) -> Result<(), DbError> {
+    let list: List = self.get_unchecked(list_id)?;
+
+    (&self.acl_db, &self.object_list_db)
+      .transaction(|(acl_tx, object_list_tx)| {
+        let mut previous_owner = None;
+        let mut previous_owner_keeps_access = false;
+        let mut household = None;
+
+        for object_id in std::iter::once(list_id).chain(list.items.iter().copied()) {
+          let mut acl = match acl_tx.get(object_id.as_bytes())? {
+            Some(bytes) => unsafe {
+              rkyv::from_bytes_unchecked::<AccessControlList<List, User>>(&bytes)
+                .map_err(abort_error)?
+            },
+            None => continue,
+          };
+
+          let owner = acl.owner;
+          acl.transfer_ownership(new_owner, previous_owner_role);
+          // a household owning the list is no user, its members keep their access through the household anyway
+          let acl_household = acl.household;
+          acl
+            .members
+            .retain(|member| Some(member.user_id) != acl_household);
+
+          if object_id == list_id {
+            previous_owner = Some(owner);
+            previous_owner_keeps_access = acl.role_of(&owner).is_some();
+            household = acl_household;
+          }
+
+          acl_tx.insert(
+            object_id.as_bytes(),
+            &*rkyv::to_bytes::<_, 256>(&acl).map_err(abort_error)?,
+          )?;
+        }
+
+        let previous_owner = previous_owner.ok_or_else(|| abort_error(DbError::NotFound))?;
+        // lists of a household are listed under the household, which is not affected
+        if household.is_none() {
+          // members are listed like the owner, see `grant_list_access`
+          if !previous_owner_keeps_access {
+            Self::unlist_in(object_list_tx, List::DENOMINATOR, previous_owner, list_id)?;
+          }
+          Self::enlist_in(object_list_tx, List::DENOMINATOR, new_owner, list_id)?;
+        }
+
+        Ok(())
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// For objects that are neither listed nor have ACLs derived from them, like shops and articles
+  pub fn transfer_ownership<Object: Identifiable<Id = u64>>(
+    &self,
+    object_id: u64,
+    new_owner: u64,
+    previous_owner_role: Role,
+  ) -> Result<(), DbError>
+  where
+    AccessControlList<Object, User>: rkyv::Serialize<
+      rkyv::ser::serializers::AllocSerializer<256>,
+    >,
+    <AccessControlList<Object, User> as rkyv::Archive>::Archived:
+      rkyv::Deserialize<AccessControlList<Object, User>, SharedDeserializeMap>,
+  {
+    self
+      .acl_db
+      .transaction(|tx_db| {
+        let mut acl = match tx_db.get(object_id.as_bytes())? {
+          Some(bytes) => unsafe {
+            rkyv::from_bytes_unchecked::<AccessControlList<Object, User>>(&bytes)
+              .map_err(abort_error)?
+          },
+          None => return Err(abort_error(DbError::NotFound)),
+        };
+
+        acl.transfer_ownership(new_owner, previous_owner_role);
+
+        tx_db.insert(
+          object_id.as_bytes(),
+          &*rkyv::to_bytes::<_, 256>(&acl).map_err(abort_error)?,
+        )?;
+
+        Ok(())
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// Appends the event to the activity log of the list. The actor is `None` for changes made through share links.
+  pub fn record_activity(
+    &self,
+    list_id: u64,
+    actor_id: Option<u64>,
+    kind: ActivityKind,
+  ) -> Result<(), DbError> {
+    let actor_name = match actor_id {
+      Some(actor_id) => Some(self.get_unchecked::<User>(actor_id)?.name),
+      None => None,
+    };
+    // sled ids are monotonic, so they order the events of a list
+    let id = self.db.generate_id()?;
+
+    let event = ActivityEvent {
+      id,
+      actor_id,
+      actor_name,
+      timestamp: Session::get_current_time(),
+      kind,
+    };
+
+    self.activity_db.insert(
+      Self::activity_key(list_id, id),
+      &*rkyv::to_bytes::<_, 256>(&event)?,
+    )?;
+
+    self.enqueue_webhooks(list_id, event)
+  }
+
+  /// Schedules a delivery of the event for the webhooks of the list and of everyone with access to it
+  fn enqueue_webhooks(&self, list_id: u64, event: ActivityEvent) -> Result<(), DbError> {
+    let mut webhook_ids =
+      <Self as ObjectStore<StoredWebhook, sled::Tree, 512>>::object_list(self, list_id)?.list;
+    // webhooks of accounts are listed under the user, those of single lists under the list
+    for user_id in self.list_audience(list_id)? {
+      webhook_ids.extend(
+        <Self as ObjectStore<StoredWebhook, sled::Tree, 512>>::object_list(self, user_id)?.list,
+      );
+    }
+
+    let now = Session::get_current_time();
+    for webhook_id in webhook_ids {
+      let delivery_id = self.db.generate_id()?;
+      let payload = WebhookPayload {
+        delivery_id,
+        webhook_id,
+        list_id,
+        event: event.clone(),
+      };
+      let pending = PendingDelivery {
+        delivery_id,
+        webhook_id,
+        payload: serde_json::to_string(&payload).map_err(|e| DbError::Encoding(e.into()))?,
+        attempt: 0,
+      };
+
+      self.schedule_delivery(now, &pending)?;
+    }
+
+    Ok(())
+  }
+
+  /// Everyone with access to the list, including the members of its household
+  fn list_audience(&self, list_id: u64) -> Result<Vec<u64>, DbError> {
+    let acl = self.list_acl(list_id)?;
+
+    let mut user_ids: Vec<u64> = std::iter::once(acl.owner)
+      .chain(acl.members.iter().map(|member| member.user_id))
+      .collect();
+    if let Some(household_id) = acl.household {
+      let household: Household = self.get_unchecked(household_id)?;
+      user_ids.extend(household.members.iter().map(|member| member.user_id));
+    }
+    user_ids.sort_unstable();
+    user_ids.dedup();
+
+    Ok(user_ids)
+  }
+
+  pub fn schedule_delivery(&self, due_at: i64, pending: &PendingDelivery) -> Result<(), DbError> {
+    self.webhook_queue_db.insert(
+      Self::delivery_queue_key(due_at, pending.delivery_id),
+      &*rkyv::to_bytes::<_, 1024>(pending)?,
+    )?;
+    self.webhook_notify.notify_one();
+
+    Ok(())
+  }
+
+  /**
+  Deliveries due at the given time, along with their key in the queue.
+  They stay queued until [`DbState::finish_delivery`] is called, so they are retried if the server stops in between.
+  */
+  pub fn due_deliveries(&self, now: i64) -> Result<Vec<(sled::IVec, PendingDelivery)>, DbError> {
+    self
+      .webhook_queue_db
+      .range(..Self::delivery_queue_key(now + 1, 0))
+      .map(|entry| {
+        let (key, bytes) = entry?;
+        Ok((key, unsafe {
+          rkyv::from_bytes_unchecked::<PendingDelivery>(&bytes)
+        }?))
+      })
+      .collect()
+  }
+
+  /// Removes the delivery from the queue and logs the attempt
+  pub fn finish_delivery(
+    &self,
+    queue_key: &[u8],
+    delivery: &WebhookDelivery,
+  ) -> Result<(), DbError> {
+    self.webhook_queue_db.remove(queue_key)?;
+
+    let id = self.db.generate_id()?;
+    self.webhook_delivery_db.insert(
+      Self::activity_key(delivery.webhook_id, id),
+      &*rkyv::to_bytes::<_, 256>(delivery)?,
+    )?;
+
+    Ok(())
+  }
+
+  /// The latest attempts to deliver payloads to the webhook, newest first
+  pub fn list_deliveries(
+    &self,
+    webhook_id: u64,
+    limit: usize,
+  ) -> Result<Vec<WebhookDelivery>, DbError> {
+    self
+      .webhook_delivery_db
+      .scan_prefix(webhook_id.to_be_bytes())
+      .rev()
+      .take(limit)
+      .map(|entry| {
+        let (_, bytes) = entry?;
+        Ok(unsafe { rkyv::from_bytes_unchecked::<WebhookDelivery>(&bytes) }?)
+      })
+      .collect()
+  }
+
+  /// Deletes the webhook along with its delivery log. Pending deliveries are dropped by the dispatcher.
+  pub fn delete_webhook(&self, webhook: &StoredWebhook) -> Result<(), DbError> {
+    let listed_under = webhook.hook.list_id.unwrap_or(webhook.owner);
+    self.unlist::<StoredWebhook>(listed_under, webhook.hook.id)?;
+    self.delete::<StoredWebhook>(webhook.hook.id)?;
+
+    for entry in self
+      .webhook_delivery_db
+      .scan_prefix(webhook.hook.id.to_be_bytes())
+    {
+      let (key, _) = entry?;
+      self.webhook_delivery_db.remove(key)?;
+    }
+
+    Ok(())
+  }
+
+  fn delivery_queue_key(due_at: i64, delivery_id: u64) -> [u8; 16] {
+    // big endian, so the queue is ordered by due time
+    let mut key = [0; 16];
+    key[..8].copy_from_slice(&(due_at.max(0) as u64).to_be_bytes());
+    key[8..].copy_from_slice(&delivery_id.to_be_bytes());
+    key
+  }
+
+  /// Returns up to `limit` events of the list older than `before`, newest first
+  pub fn list_activity(
+    &self,
+    list_id: u64,
+    before: Option<u64>,
+    limit: usize,
+  ) -> Result<Vec<ActivityEvent>, DbError> {
+    let start = Self::activity_key(list_id, 0);
+    let end = Self::activity_key(list_id, before.unwrap_or(u64::MAX));
+
+    self
+      .activity_db
+      .range(start..end)
+      .rev()
+      .take(limit)
+      .map(|entry| {
+        let (_, bytes) = entry?;
+        Ok(unsafe { rkyv::from_bytes_unchecked::<ActivityEvent>(&bytes) }?)
+      })
+      .collect()
+  }
+
+  /// Remembers that the item was checked off if its list is associated with a shop
+  pub fn record_check_off(&self, list_id: u64, item: &Item) -> Result<(), DbError> {
+    let list: List = self.get_unchecked(list_id)?;
+    let (Some(shop_id), Some(article_id)) = (list.shop, item.article_id) else {
+      return Ok(());
+    };
+
+    let check_off = CheckOff {
+      list_id,
+      article_id,
+      timestamp: Session::get_current_time(),
+    };
+    let id = self.db.generate_id()?;
+    self.check_off_db.insert(
+      Self::activity_key(shop_id, id),
+      &*rkyv::to_bytes::<_, 64>(&check_off)?,
+    )?;
+
+    // only the most recent check-offs are used, so older ones are dropped
+    let start = Self::activity_key(shop_id, 0);
+    let end = Self::activity_key(shop_id, u64::MAX);
+    let outdated = self
+      .check_off_db
+      .range(start..end)
+      .keys()
+      .rev()
+      .skip(CHECK_OFF_HISTORY);
+    for key in outdated {
+      self.check_off_db.remove(key?)?;
+    }
+
+    Ok(())
+  }
+
+  /// The check-offs in the shop, oldest first
+  pub fn shop_check_offs(&self, shop_id: u64) -> Result<Vec<CheckOff>, DbError> {
+    let start = Self::activity_key(shop_id, 0);
+    let end = Self::activity_key(shop_id, u64::MAX);
+
+    self
+      .check_off_db
+      .range(start..end)
+      .map(|entry| {
+        let (_, bytes) = entry?;
+        Ok(unsafe { rkyv::from_bytes_unchecked::<CheckOff>(&bytes) }?)
+      })
+      .collect()
+  }
+
+  fn activity_key(list_id: u64, event_id: u64) -> [u8; 16] {
+    // big endian, so the events of a list are stored consecutively and in order
+    let mut key = [0; 16];
+    key[..8].copy_from_slice(&list_id.to_be_bytes());
+    key[8..].copy_from_slice(&event_id.to_be_bytes());
+    key
+  }
+
+  /// Item ACLs are copies of the ACL of their list, so they refer to the list rather than the item itself.
+  pub fn list_of_item(&self, item_id: u64) -> Result<u64, DbError> {
+    Ok(self.list_acl(item_id)?.object_id)
+  }
+
+  fn list_acl_in(
+    acl_tx: &TransactionalTree,
+    object_id: u64,
+  ) -> ConflictableTransactionResult<AccessControlList<List, User>, DbError> {
+    let acl = acl_tx
+      .get(object_id.as_bytes())?
+      .ok_or_else(|| abort_error(DbError::NotFound))?;
+
+    unsafe { rkyv::from_bytes_unchecked::<AccessControlList<List, User>>(&acl) }
+      .map_err(abort_error)
+  }
+
+  pub(crate) fn list_acl(&self, object_id: u64) -> Result<AccessControlList<List, User>, DbError> {
+    let acl = self
+      .acl_db
+      .get(object_id.as_bytes())?
+      .ok_or(DbError::NotFound)?;
+
+    Ok(unsafe { rkyv::from_bytes_unchecked::<AccessControlList<List, User>>(&acl) }?)
+  }
+
+  /**
+  Moves the item from its list into the trash, remembering its position so it can be put back later on.
+  The ACL of the item is kept until the entry is purged.
+  */
+  pub fn trash_item(&self, list_id: u64, item_id: u64, deleted_by: u64) -> Result<Item, DbError> {
+    let deleted_at = Session::get_current_time();
+
+    (
+      &self.item_db,
+      &self.list_db,
+      &self.trash_db,
+      &self.object_list_db,
+    )
+      .transaction(|(item_tx, list_tx, trash_tx, object_list_tx)| {
+        Self::trash_item_in(
+          (item_tx, list_tx, trash_tx, object_list_tx),
+          list_id,
+          item_id,
+          deleted_by,
+          deleted_at,
+        )
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// Takes the item, list, trash and object list trees
+  fn trash_item_in(
+    (item_tx, list_tx, trash_tx, object_list_tx): (
+      &TransactionalTree,
+      &TransactionalTree,
+      &TransactionalTree,
+      &TransactionalTree,
+    ),
+    list_id: u64,
+    item_id: u64,
+    deleted_by: u64,
+    deleted_at: i64,
+  ) -> ConflictableTransactionResult<Item, DbError> {
+    unsafe {
+      let item = <&TransactionalTree as RawRkyvStore<Item, 4096>>::get_unchecked(&item_tx, item_id)
+        .map_err(abort_error)?;
+      let mut list =
+        <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, list_id)
+          .map_err(abort_error)?;
+      let position = list
+        .items
+        .iter()
+        .position(|&id| id == item_id)
+        .ok_or(abort_error(DbError::NotFound))?;
+      list.items.remove(position);
+
+      let entry = TrashEntry {
+        id: item_id,
+        deleted_at,
+        deleted_by,
+        object: TrashedObject::Item {
+          item: item.clone(),
+          list_id,
+          position: position as u64,
+        },
+      };
+
+      item_tx.remove(item_id.as_bytes())?;
+      <&TransactionalTree as RawRkyvStore<List, 4096>>::store_unlisted(&list_tx, list_id, &list)
+        .map_err(abort_error)?;
+      <&TransactionalTree as RawRkyvStore<TrashEntry, 4096>>::store_unlisted(
+        &trash_tx, item_id, &entry,
+      )
+      .map_err(abort_error)?;
+      // trashed items are listed under their list
+      Self::enlist_in(object_list_tx, TrashEntry::DENOMINATOR, list_id, item_id)?;
+
+      Ok(item)
+    }
+  }
+
+  /**
+  Applies the patch to the list in one transaction. The patched items have to be a subset of the current ones in any
+  order, the items left out are moved into the trash. Returns the previous and the patched list with the trashed items.
+  */
+  pub fn patch_list(
+    &self,
+    list_id: u64,
+    patch: &ListPatchV1,
+    deleted_by: u64,
+  ) -> Result<(List, List, Vec<Item>), DbError> {
+    let deleted_at = Session::get_current_time();
+
+    (
+      &self.item_db,
+      &self.list_db,
+      &self.trash_db,
+      &self.object_list_db,
+    )
+      .transaction(|(item_tx, list_tx, trash_tx, object_list_tx)| {
+        let previous = unsafe {
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, list_id)
+        }
+        .map_err(abort_error)?;
+        let mut list = previous.clone();
+        patch.apply(&mut list);
+
+        // items are added through their own endpoints, so they can not be listed twice or end up in multiple lists
+        if !is_subset(&list.items, &previous.items) {
+          return Err(abort_error(DbError::Mismatch));
+        }
+
+        let trashed = previous
+          .items
+          .iter()
+          .filter(|item_id| !list.items.contains(item_id))
+          .map(|item_id| {
+            Self::trash_item_in(
+              (item_tx, list_tx, trash_tx, object_list_tx),
+              list_id,
+              *item_id,
+              deleted_by,
+              deleted_at,
+            )
+          })
+          .collect::<Result<Vec<_>, _>>()?;
+
+        unsafe {
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::store_unlisted(&list_tx, list_id, &list)
+        }
+        .map_err(abort_error)?;
+
+        Ok((previous, list, trashed))
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /**
+  Merges the unchecked duplicates of the list into the first of them and moves the others into the trash.
+  Returns the merged items together with the ids of the items merged into them.
+  */
+  pub fn merge_duplicates(
+    &self,
+    list_id: u64,
+    deleted_by: u64,
+  ) -> Result<Vec<(Item, Vec<u64>)>, DbError> {
+    let deleted_at = Session::get_current_time();
+
+    (
+      &self.item_db,
+      &self.list_db,
+      &self.trash_db,
+      &self.object_list_db,
+    )
+      .transaction(|(item_tx, list_tx, trash_tx, object_list_tx)| {
+        let list = unsafe {
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, list_id)
+        }
+        .map_err(abort_error)?;
+
+        let mut merged: Vec<(Item, Vec<u64>)> = Vec::new();
+        'items: for item_id in list.items {
+          let item = unsafe {
+            <&TransactionalTree as RawRkyvStore<Item, 4096>>::get_unchecked(&item_tx, item_id)
+          }
+          .map_err(abort_error)?;
+          if item.checked {
+            continue;
+          }
+
+          for (target, merged_item_ids) in merged.iter_mut() {
+            if target.is_duplicate_of(&item) && target.merge(&item) {
+              merged_item_ids.push(item.id);
+              continue 'items;
+            }
+          }
+          merged.push((item, Vec::new()));
+        }
+        merged.retain(|(_, merged_item_ids)| !merged_item_ids.is_empty());
+
+        for (target, merged_item_ids) in &merged {
+          unsafe {
+            <&TransactionalTree as RawRkyvStore<Item, 4096>>::store_unlisted(
+              &item_tx, target.id, target,
+            )
+          }
+          .map_err(abort_error)?;
+          for item_id in merged_item_ids {
+            Self::trash_item_in(
+              (item_tx, list_tx, trash_tx, object_list_tx),
+              list_id,
+              *item_id,
+              deleted_by,
+              deleted_at,
+            )?;
+          }
+        }
+
+        Ok(merged)
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// Adds the amount of the item to the first unchecked duplicate in the list and returns the updated duplicate
+  pub fn merge_into_duplicate(&self, list_id: u64, item: &Item) -> Result<Option<Item>, DbError> {
+    (&self.item_db, &self.list_db)
+      .transaction(|(item_tx, list_tx)| {
+        let list = unsafe {
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, list_id)
+        }
+        .map_err(abort_error)?;
+
+        Self::merge_into_duplicate_in(item_tx, &list, item)
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  fn merge_into_duplicate_in(
+    item_tx: &TransactionalTree,
+    list: &List,
+    item: &Item,
+  ) -> ConflictableTransactionResult<Option<Item>, DbError> {
+    for &duplicate_id in &list.items {
+      let mut duplicate = unsafe {
+        <&TransactionalTree as RawRkyvStore<Item, 4096>>::get_unchecked(&item_tx, duplicate_id)
+      }
+      .map_err(abort_error)?;
+      if duplicate.checked || !duplicate.is_duplicate_of(item) || !duplicate.merge(item) {
+        continue;
+      }
+
+      unsafe {
+        <&TransactionalTree as RawRkyvStore<Item, 4096>>::store_unlisted(
+          &item_tx,
+          duplicate_id,
+          &duplicate,
+        )
+      }
+      .map_err(abort_error)?;
+
+      return Ok(Some(duplicate));
+    }
+
+    Ok(None)
+  }
+
+  /**
+  Applies the operations in order within a single transaction, so either all or none of them take effect.
+  The permissions for the operations have to be verified by the caller.
+  */
+  pub fn execute_batch(&self, operations: &[BatchOperation]) -> Result<Vec<BatchChange>, DbError> {
+    let now = Session::get_current_time();
+
+    (
+      &self.item_db,
+      &self.list_db,
+      &self.acl_db,
+      &self.article_db,
+      &self.trash_db,
+      &self.object_list_db,
+    )
+      .transaction(|trees| {
+        operations
+          .iter()
+          .map(|operation| Self::apply_batch_operation(trees, operation, now))
+          .collect()
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// Takes the item, list, ACL, article, trash and object list trees
+  fn apply_batch_operation(
+    (item_tx, list_tx, acl_tx, article_tx, trash_tx, object_list_tx): &(
+      TransactionalTree,
+      TransactionalTree,
+      TransactionalTree,
+      TransactionalTree,
+      TransactionalTree,
+      TransactionalTree,
+    ),
+    operation: &BatchOperation,
+    now: i64,
+  ) -> ConflictableTransactionResult<BatchChange, DbError> {
+    let change = unsafe {
+      match operation {
+        BatchOperation::AddItem { list_id, item } => {
+          <&TransactionalTree as RawRkyvStore<Item, 4096>>::store_unlisted(&item_tx, item.id, item)
+            .map_err(abort_error)?;
+          let mut list =
+            <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, *list_id)
+              .map_err(abort_error)?;
+          list.items.push(item.id);
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::store_unlisted(
+            &list_tx, *list_id, &list,
+          )
+          .map_err(abort_error)?;
+          Self::copy_acl_in::<List, Item>(acl_tx, list_id, &item.id)?;
+
+          BatchChange::ItemAdded {
+            list_id: *list_id,
+            item: item.clone(),
+          }
+        }
+        BatchOperation::UpdateItem { item, only_checked } => {
+          let previous =
+            <&TransactionalTree as RawRkyvStore<Item, 4096>>::get_unchecked(&item_tx, item.id)
+              .map_err(abort_error)?;
+          let item = match only_checked {
+            true => Item {
+              checked: item.checked,
+              ..previous.clone()
+            },
+            false => item.clone(),
+          };
+          <&TransactionalTree as RawRkyvStore<Item, 4096>>::store_unlisted(
+            &item_tx, item.id, &item,
+          )
+          .map_err(abort_error)?;
+
+          BatchChange::ItemUpdated { previous, item }
+        }
+        BatchOperation::TrashItem {
+          list_id,
+          item_id,
+          deleted_by,
+        } => {
+          let item = Self::trash_item_in(
+            (item_tx, list_tx, trash_tx, object_list_tx),
+            *list_id,
+            *item_id,
+            *deleted_by,
+            now,
+          )?;
+
+          BatchChange::ItemTrashed {
+            list_id: *list_id,
+            item,
+          }
+        }
+        BatchOperation::UpdateList(list) => {
+          let previous =
+            <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, list.id)
+              .map_err(abort_error)?;
+
+          // the client can not know the ids of items added earlier in the batch, so the items are kept unless they are
+          // only reordered
+          let mut list = list.clone();
+          if !is_permutation(&list.items, &previous.items) {
+            list.items = previous.items.clone();
+          }
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::store_unlisted(
+            &list_tx, list.id, &list,
+          )
+          .map_err(abort_error)?;
+
+          BatchChange::ListUpdated { previous, list }
+        }
+        BatchOperation::AddArticle { article, owner } => {
+          <&TransactionalTree as RawRkyvStore<Article, 4096>>::store_unlisted(
+            &article_tx,
+            article.id,
+            article,
+          )
+          .map_err(abort_error)?;
+          let acl = AccessControlList::<Article, User> {
+            object_id: article.id,
+            members: vec![],
+            household: None,
+            owner: *owner,
+          };
+          acl_tx.insert(
+            article.id.as_bytes(),
+            &*rkyv::to_bytes::<_, 256>(&acl).map_err(abort_error)?,
+          )?;
+
+          BatchChange::ArticleStored
+        }
+        BatchOperation::UpdateArticle(article) => {
+          <&TransactionalTree as RawRkyvStore<Article, 4096>>::store_unlisted(
+            &article_tx,
+            article.id,
+            article,
+          )
+          .map_err(abort_error)?;
+
+          BatchChange::ArticleStored
+        }
+      }
+    };
+
+    Ok(change)
+  }
+
+  /**
+  Moves the list into the trash and removes it from the object lists of everyone it was shared with.
+  Its items stay attached to it, so restoring the list brings them back as well.
+  */
+  pub fn trash_list(&self, list_id: u64, deleted_by: u64) -> Result<List, DbError> {
+    let acl = self.list_acl(list_id)?;
+    let deleted_at = Session::get_current_time();
+
+    (&self.list_db, &self.trash_db, &self.object_list_db)
+      .transaction(|(list_tx, trash_tx, object_list_tx)| unsafe {
+        let list =
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, list_id)
+            .map_err(abort_error)?;
+
+        let entry = TrashEntry {
+          id: list_id,
+          deleted_at,
+          deleted_by,
+          object: TrashedObject::List(list.clone()),
+        };
+
+        list_tx.remove(list_id.as_bytes())?;
+        <&TransactionalTree as RawRkyvStore<TrashEntry, 4096>>::store_unlisted(
+          &trash_tx, list_id, &entry,
+        )
+        .map_err(abort_error)?;
+
+        for user_id in Self::list_holders(&acl) {
+          Self::unlist_in(object_list_tx, List::DENOMINATOR, user_id, list_id)?;
+        }
+        Self::enlist_in(
+          object_list_tx,
+          TrashEntry::DENOMINATOR,
+          Self::trash_holder(&acl),
+          list_id,
+        )?;
+
+        Ok(list)
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// Puts the object back where it was deleted from. Items can only be restored as long as their list exists.
+  pub fn restore_trash(&self, entry: &TrashEntry) -> Result<(), DbError> {
+    match &entry.object {
+      TrashedObject::Item {
+        item,
+        list_id,
+        position,
+      } => (
+        &self.item_db,
+        &self.list_db,
+        &self.trash_db,
+        &self.object_list_db,
+      )
+        .transaction(|(item_tx, list_tx, trash_tx, object_list_tx)| unsafe {
+          let mut list =
+            <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, *list_id)
+              .map_err(abort_error)?;
+          // the list may have shrunk in the meantime
+          let position = (*position as usize).min(list.items.len());
+          list.items.insert(position, entry.id);
+
+          <&TransactionalTree as RawRkyvStore<Item, 4096>>::store_unlisted(
+            &item_tx, entry.id, item,
+          )
+          .map_err(abort_error)?;
+          <&TransactionalTree as RawRkyvStore<List, 4096>>::store_unlisted(
+            &list_tx, *list_id, &list,
+          )
+          .map_err(abort_error)?;
+          trash_tx.remove(entry.id.as_bytes())?;
+          Self::unlist_in(object_list_tx, TrashEntry::DENOMINATOR, *list_id, entry.id)?;
+
+          Ok(())
+        }),
+      TrashedObject::List(list) => {
+        let acl = self.list_acl(entry.id)?;
+
+        (&self.list_db, &self.trash_db, &self.object_list_db).transaction(
+          |(list_tx, trash_tx, object_list_tx)| unsafe {
+            <&TransactionalTree as RawRkyvStore<List, 4096>>::store_unlisted(
+              &list_tx, entry.id, list,
+            )
+            .map_err(abort_error)?;
+            trash_tx.remove(entry.id.as_bytes())?;
+
+            Self::unlist_in(
+              object_list_tx,
+              TrashEntry::DENOMINATOR,
+              Self::trash_holder(&acl),
+              entry.id,
+            )?;
+            for user_id in Self::list_holders(&acl) {
+              Self::enlist_in(object_list_tx, List::DENOMINATOR, user_id, entry.id)?;
+            }
+
+            Ok(())
+          },
+        )
+      }
+    }
+    .map_err(|e| match e {
+      TransactionError::Storage(e) => DbError::IO(e.into()),
+      TransactionError::Abort(e) => e,
+    })
+  }
+
+  /**
+  Deletes the trashed object for good, including its ACL.
+  Purging a list also deletes its items, its share links and webhooks, its activity and check-offs and any of its items
+  still in the trash.
+  */
+  pub fn purge_trash(&self, entry: &TrashEntry) -> Result<(), DbError> {
+    let mut activity_keys = Vec::new();
+    let mut check_off_keys = Vec::new();
+    if let TrashedObject::List(_) = entry.object {
+      for key in self.activity_db.scan_prefix(entry.id.to_be_bytes()).keys() {
+        activity_keys.push(key?);
+      }
+      // check-offs are keyed by shop, which the list may have changed in the meantime
+      for check_off in self.check_off_db.iter() {
+        let (key, bytes) = check_off?;
+        if unsafe { rkyv::from_bytes_unchecked::<CheckOff>(&bytes) }?.list_id == entry.id {
+          check_off_keys.push(key);
+        }
+      }
+    }
+
+    let (listed_under, object_ids, share_link_ids, webhook_ids) = match &entry.object {
+      TrashedObject::Item { list_id, .. } => (*list_id, vec![entry.id], vec![], vec![]),
+      TrashedObject::List(list) => {
+        let trashed_items =
+          <Self as ObjectStore<TrashEntry, sled::Tree, 512>>::object_list(self, entry.id)?;
+        let share_links =
+          <Self as ObjectStore<StoredShareLink, sled::Tree, 512>>::object_list(self, entry.id)?;
+        let webhooks =
+          <Self as ObjectStore<StoredWebhook, sled::Tree, 512>>::object_list(self, entry.id)?;
+        let object_ids = std::iter::once(entry.id)
+          .chain(list.items.iter().copied())
+          .chain(trashed_items.list)
+          .collect();
+
+        (
+          Self::trash_holder(&self.list_acl(entry.id)?),
+          object_ids,
+          share_links.list,
+          webhooks.list,
+        )
+      }
+    };
+
+    (
+      &self.item_db,
+      &self.acl_db,
+      &self.trash_db,
+      &self.share_link_db,
+      &self.webhook_db,
+      &self.object_list_db,
+      &self.activity_db,
+      &self.check_off_db,
+    )
+      .transaction(
+        |(
+          item_tx,
+          acl_tx,
+          trash_tx,
+          share_link_tx,
+          webhook_tx,
+          object_list_tx,
+          activity_tx,
+          check_off_tx,
+        )| {
+          for object_id in &object_ids {
+            item_tx.remove(object_id.as_bytes())?;
+            acl_tx.remove(object_id.as_bytes())?;
+            trash_tx.remove(object_id.as_bytes())?;
+          }
+          for share_link_id in &share_link_ids {
+            share_link_tx.remove(share_link_id.as_bytes())?;
+          }
+          for webhook_id in &webhook_ids {
+            webhook_tx.remove(webhook_id.as_bytes())?;
+          }
+          for key in &activity_keys {
+            activity_tx.remove(key)?;
+          }
+          for key in &check_off_keys {
+            check_off_tx.remove(key)?;
+          }
+
+          if let TrashedObject::List(_) = entry.object {
+            // share links, webhooks and trashed items are listed under the list
+            object_list_tx.remove(&entry.id.to_ne_bytes())?;
+          }
+          Self::unlist_in(
+            object_list_tx,
+            TrashEntry::DENOMINATOR,
+            listed_under,
+            entry.id,
+          )?;
+
+          Ok(())
+        },
+      )
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// Purges everything deleted before the given unix timestamp and returns the number of purged entries
+  pub fn purge_expired_trash(&self, deleted_before: i64) -> Result<usize, DbError> {
+    let expired = self
+      .trash_db
+      .iter()
+      .values()
+      .map(|bytes| Ok(unsafe { rkyv::from_bytes_unchecked::<TrashEntry>(&bytes?) }?))
+      .filter(|entry: &Result<TrashEntry, DbError>| {
+        entry
+          .as_ref()
+          .map_or(true, |entry| entry.deleted_at < deleted_before)
+      })
+      .collect::<Result<Vec<_>, DbError>>()?;
+
+    for entry in &expired {
+      match self.purge_trash(entry) {
+        // without its ACL nobody could restore the object anyway
+        Err(DbError::NotFound) => {
+          self.trash_db.remove(entry.id.as_bytes())?;
+        }
+        result => result?,
+      }
+    }
+
+    Ok(expired.len())
+  }
+
+  /// Everyone the list shows up for. Lists of a household are listed under the household instead of the owner.
+  fn list_holders(acl: &AccessControlList<List, User>) -> impl Iterator<Item = u64> + '_ {
+    std::iter::once(acl.household.unwrap_or(acl.owner))
+      .chain(acl.members.iter().map(|member| member.user_id))
+  }
+
+  /// Trashed lists are only listed for whoever is allowed to manage them
+  fn trash_holder(acl: &AccessControlList<List, User>) -> u64 {
+    acl.household.unwrap_or(acl.owner)
+  }
+
+  /// Adds the user to the household and its object list. Existing members keep their role.
+  pub fn join_household(&self, household_id: u64, user_id: u64, role: Role) -> Result<(), DbError> {
+    self.update_household(household_id, |household| {
+      if household.role_of(user_id).is_none() {
+        household.members.push(Member { user_id, role });
+      }
+
+      Ok(())
+    })?;
+
+    self.enlist::<Household>(user_id, household_id)
+  }
+
+  /**
+  Hands the lists of the household back to their owners, including the trashed ones, and deletes the household.
+  Lists created in the household are owned by it, so they are handed to one of its owners instead.
+  Everything is updated in a single transaction, so no list is left behind with a deleted household.
+  */
+  pub fn dissolve_household(&self, household_id: u64) -> Result<(), DbError> {
+    (
+      &self.acl_db,
+      &self.object_list_db,
+      &self.household_db,
+      &self.list_db,
+      &self.trash_db,
+    )
+      .transaction(
+        |(acl_tx, object_list_tx, household_tx, list_tx, trash_tx)| {
+          let household = unsafe {
+            <&TransactionalTree as RawRkyvStore<Household, 512>>::get_unchecked(
+              &household_tx,
+              household_id,
+            )
+          }
+          .map_err(abort_error)?;
+          let household_owner = household
+            .members
+            .iter()
+            .find(|member| member.role == Role::Owner)
+            .ok_or_else(|| abort_error(DbError::NotFound))?
+            .user_id;
+          let update = |acl: &mut AccessControlList<List, User>| {
+            acl.household = None;
+            if acl.owner == household_id {
+              acl.owner = household_owner;
+            }
+          };
+
+          // trashed lists are listed under the household as well, they have to be restorable by their new owner
+          for denominator in [List::DENOMINATOR, TrashEntry::DENOMINATOR] {
+            for list_id in Self::listed_in(object_list_tx, denominator, household_id)? {
+              let list = match denominator == List::DENOMINATOR {
+                true => unsafe {
+                  <&TransactionalTree as RawRkyvStore<List, 4096>>::get_unchecked(&list_tx, list_id)
+                }
+                .map_err(abort_error)?,
+                false => match unsafe {
+                  <&TransactionalTree as RawRkyvStore<TrashEntry, 4096>>::get_unchecked(
+                    &trash_tx, list_id,
+                  )
+                }
+                .map_err(abort_error)?
+                .object
+                {
+                  TrashedObject::List(list) => list,
+                  TrashedObject::Item { .. } => continue,
+                },
+              };
+              let owner = match Self::list_acl_in(acl_tx, list_id)?.owner {
+                owner if owner == household_id => household_owner,
+                owner => owner,
+              };
+
+              // items in the trash keep their ACL as well
+              let trashed_item_ids =
+                Self::listed_in(object_list_tx, TrashEntry::DENOMINATOR, list_id)?;
+              Self::update_list_acls_in(
+                acl_tx,
+                std::iter::once(list_id)
+                  .chain(list.items)
+                  .chain(trashed_item_ids),
+                &update,
+              )?;
+              Self::enlist_in(object_list_tx, denominator, owner, list_id)?;
+            }
+          }
+          // households share the id space with users, so their object lists are stored alongside
+          object_list_tx.remove(&household_id.to_ne_bytes())?;
+
+          for member in &household.members {
+            Self::unlist_in(
+              object_list_tx,
+              Household::DENOMINATOR,
+              member.user_id,
+              household_id,
+            )?;
+          }
+          household_tx.remove(household_id.as_bytes())?;
+
+          Ok(())
+        },
+      )
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// Updates the household atomically, as members may join and leave concurrently.
+  pub fn update_household(
+    &self,
+    household_id: u64,
+    update: impl Fn(&mut Household) -> Result<(), DbError>,
+  ) -> Result<Household, DbError> {
+    self
+      .household_db
+      .transaction(|tx_db| unsafe {
+        let mut household =
+          <&TransactionalTree as RawRkyvStore<Household, 512>>::get_unchecked(&tx_db, household_id)
+            .map_err(abort_error)?;
+
+        update(&mut household).map_err(abort_error)?;
+
+        <&TransactionalTree as RawRkyvStore<Household, 512>>::store_unlisted(
+          &tx_db,
+          household_id,
+          &household,
+        )
+        .map_err(abort_error)?;
+
+        Ok(household)
+      })
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// Removes the id from the users object list without touching the object itself
+  pub fn unlist<T: HasTypeDenominator>(&self, user_id: u64, id: u64) -> Result<(), DbError> {
+    self
+      .object_list_db
+      .transaction(|tx_db| Self::unlist_in(tx_db, T::DENOMINATOR, user_id, id))
+      .map_err(|e| match e {
+        TransactionError::Storage(e) => DbError::IO(e.into()),
+        TransactionError::Abort(e) => e,
+      })
+  }
+
+  /// The ids of the given type listed under the user
+  fn listed_in(
+    tx_db: &TransactionalTree,
+    denominator: u64,
+    user_id: u64,
+  ) -> ConflictableTransactionResult<Vec<u64>, DbError> {
+    let current_ol = match tx_db.get(user_id.to_ne_bytes())? {
+      Some(bytes) => unsafe {
+        rkyv::from_bytes_unchecked::<UsersObjectLists>(&bytes).map_err(abort_error)?
+      },
+      None => return Ok(vec![]),
+    };
+
+    Ok(
+      current_ol
+        .lists
+        .into_iter()
+        .find(|list| list.typ == denominator)
+        .map_or(vec![], |list| list.list),
+    )
+  }
+
+  fn unlist_in(
+    tx_db: &TransactionalTree,
+    denominator: u64,
+    user_id: u64,
+    id: u64,
+  ) -> ConflictableTransactionResult<(), DbError> {
+    let mut current_ol = match tx_db.get(user_id.to_ne_bytes())? {
+      Some(bytes) => unsafe {
+        rkyv::from_bytes_unchecked::<UsersObjectLists>(&bytes).map_err(abort_error)?
+      },
+      None => return Ok(()),
+    };
+
+    if let Some(ol) = current_ol
+      .lists
+      .iter_mut()
+      .find(|list| list.typ == denominator)
+    {
+      ol.list.retain(|&object_id| object_id != id);
+    }
+
+    tx_db.insert(
+      &user_id.to_ne_bytes(),
+      &*rkyv::to_bytes::<_, 512>(&current_ol).map_err(abort_error)?,
+    )?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+impl DbState {
+  /// A state backed by a temporary database, which is removed once it is dropped
+  pub(crate) fn temporary() -> Self {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let tree = |name: &str| db.open_tree(name).unwrap();
+
+    DbState {
+      article_db: tree("article"),
+      item_db: tree("item"),
+      shop_db: tree("shop"),
+      list_db: tree("list"),
+      acl_db: tree("list_acl"),
+      user_db: tree("user"),
+      login_db: tree("login"),
+      object_list_db: tree("ol"),
+      totp_db: tree("totp"),
+      api_token_db: tree("api_token"),
+      invite_db: tree("invite"),
+      household_db: tree("household"),
+      share_link_db: tree("share_link"),
+      activity_db: tree("activity"),
+      check_off_db: tree("check_off"),
+      trash_db: tree("trash"),
+      category_db: tree("category"),
+      webhook_db: tree("webhook"),
+      webhook_queue_db: tree("webhook_queue"),
+      webhook_delivery_db: tree("webhook_delivery"),
+      webhook_notify: Arc::new(Notify::new()),
+      // the minimum cost keeps tests fast
+      argon2_params: Params::new(Params::MIN_M_COST, Params::MIN_T_COST, 1, None).unwrap(),
+      db,
+    }
+  }
 }
 
 pub trait ObjectTree<T> {
@@ -298,6 +2034,48 @@ impl ObjectTree<ObjectList> for DbState {
   }
 }
 
+impl ObjectTree<TotpCredentials> for DbState {
+  fn get_tree(&self) -> &sled::Tree {
+    &self.totp_db
+  }
+}
+
+impl ObjectTree<StoredApiToken> for DbState {
+  fn get_tree(&self) -> &sled::Tree {
+    &self.api_token_db
+  }
+}
+
+impl ObjectTree<StoredShareLink> for DbState {
+  fn get_tree(&self) -> &sled::Tree {
+    &self.share_link_db
+  }
+}
+
+impl ObjectTree<Household> for DbState {
+  fn get_tree(&self) -> &sled::Tree {
+    &self.household_db
+  }
+}
+
+impl ObjectTree<TrashEntry> for DbState {
+  fn get_tree(&self) -> &sled::Tree {
+    &self.trash_db
+  }
+}
+
+impl ObjectTree<StoredWebhook> for DbState {
+  fn get_tree(&self) -> &sled::Tree {
+    &self.webhook_db
+  }
+}
+
+impl ObjectTree<Category> for DbState {
+  fn get_tree(&self) -> &sled::Tree {
+    &self.category_db
+  }
+}
+
 // The following traits are unsafe, because they do not validate the tree's content. You must manually ensure that you choose the correct tree for your type.
 // If these functions are only used through DbStates methods autochoosing the treex, they should be safe.
 pub trait ObjectStore<
@@ -508,6 +2286,74 @@ where
   }
 }
 
+/// An operation of a batch, see [`DbState::execute_batch`]
+pub enum BatchOperation {
+  /// The item already has its new id
+  AddItem {
+    list_id: u64,
+    item: Item,
+  },
+  /// Only the checked state is taken over for users who may check but not edit the item
+  UpdateItem {
+    item: Item,
+    only_checked: bool,
+  },
+  TrashItem {
+    list_id: u64,
+    item_id: u64,
+    deleted_by: u64,
+  },
+  /// Items are only reordered, see [`einkaufsliste::model::batch::BatchCommand::UpdateList`]
+  UpdateList(List),
+  /// The article already has its new id
+  AddArticle {
+    article: Article,
+    owner: u64,
+  },
+  UpdateArticle(Article),
+}
+
+/// What an operation of a batch changed, for recording the activity once the batch is committed
+pub enum BatchChange {
+  ItemAdded { list_id: u64, item: Item },
+  ItemUpdated { previous: Item, item: Item },
+  ItemTrashed { list_id: u64, item: Item },
+  ListUpdated { previous: List, list: List },
+  ArticleStored,
+}
+
+/// What happened to an item passed to [`DbState::add_items`]
+pub enum AddedItem {
+  Created(u64),
+  /// The updated duplicate the item was merged into
+  Merged(Item),
+}
+
+impl AddedItem {
+  pub fn id(&self) -> u64 {
+    match self {
+      AddedItem::Created(item_id) => *item_id,
+      AddedItem::Merged(duplicate) => duplicate.id,
+    }
+  }
+}
+
+/// Whether both contain the same ids, regardless of their order
+fn is_permutation(ids: &[u64], other: &[u64]) -> bool {
+  let mut ids = ids.to_vec();
+  let mut other = other.to_vec();
+  ids.sort_unstable();
+  other.sort_unstable();
+
+  ids == other
+}
+
+/// Whether every id is contained in the other ids without being repeated, regardless of their order
+fn is_subset(ids: &[u64], other: &[u64]) -> bool {
+  let mut seen = HashSet::new();
+  ids.iter().all(|id| other.contains(id) && seen.insert(*id))
+}
+
 #[derive(Debug)]
 pub enum DbError {
   /// Maps to [`ResponseError::ErrorInternalServerError`]
@@ -547,6 +2393,12 @@ impl From<AllocScratchError> for DbError {
   }
 }
 
+impl From<BlockingError> for DbError {
+  fn from(value: BlockingError) -> Self {
+    DbError::IO(value.into())
+  }
+}
+
 impl From<UnabortableTransactionError> for DbError {
   fn from(value: UnabortableTransactionError) -> Self {
     DbError::IO(value.into())
@@ -8,25 +8,57 @@ pub mod db;
 pub mod response;
 mod util;
 
+use std::sync::Arc;
 use std::time::Duration;
 
+use actix_governor::Governor;
 use actix_identity::IdentityMiddleware;
 use actix_session::config::{CookieContentSecurity, PersistentSession};
 use actix_session::SessionMiddleware;
 use actix_web::cookie::SameSite;
 use actix_web::middleware::Logger;
 use actix_web::HttpServer;
-use api::item::{get_item_list_flat, store_item_attached, store_item_list, update_item_attached, update_item_list, delete_item};
-use api::shop::{get_shop, store_shop};
+use api::batch::execute_batch;
+use api::category::{create_category, delete_category, get_categories};
+use api::household::{
+  create_household, dissolve_household, get_users_households, leave_household,
+  move_list_to_household, store_household_list,
+};
+use api::invite::{accept_invite, create_invite};
+use api::item::{
+  delete_item, get_item_list_activity, get_item_list_flat, get_item_list_sorted, merge_duplicates,
+  move_item, patch_item, patch_item_list, quick_add_items, reorder_item, store_item_attached,
+  store_item_list, store_items_mass, transfer_item_list, update_item_attached, update_item_list,
+};
+use api::oidc::{login_oidc_callback_v1, login_oidc_v1};
+use api::share::{
+  create_share_link, get_shared_list_flat, list_share_links, revoke_share_link, shared_list_page,
+  toggle_shared_item,
+};
+use api::shop::{get_shop, patch_shop, store_shop, transfer_shop, update_shop_layout};
+use api::token::{create_api_token, list_api_tokens, revoke_api_token};
+use api::totp::{confirm_totp, disable_totp, enrol_totp, login_totp_v1};
+use api::trash::{
+  delete_item_list, get_trashed_items, get_trashed_lists, purge_trash, restore_trash,
+};
 use api::user::{get_users_lists, login_v1, register_v1};
+use api::webhook::{
+  create_webhook, delete_webhook, get_webhook_deliveries, list_item_list_webhooks, list_webhooks,
+};
 use db::DbState;
+use einkaufsliste::model::session::Session;
 use mimalloc::MiMalloc;
 use rand::Rng;
+use tokio::sync::Notify;
 use tracing::subscriber::set_global_default;
 use tracing_log::LogTracer;
 use tracing_subscriber::filter::{LevelFilter, Targets};
 
+use crate::util::idempotency::IdempotencyStore;
+use crate::util::login_throttle::LoginThrottle;
+use crate::util::oidc::OidcProvider;
 use crate::util::session_store::SledSessionStore;
+use crate::util::webhook::WebhookDispatcher;
 
 // Use a reasonable global allocator to avoid performance problems due to rkyv serialization allocations
 #[global_allocator]
@@ -36,7 +68,10 @@ static GLOBAL: MiMalloc = MiMalloc;
 async fn main() -> std::io::Result<()> {
   setup_tracing();
 
+  let config = util::config::load_config().unwrap();
+
   let db = sled::open("./data.sled")?;
+  util::migration::migrate(&db).expect("Failed to convert the database to the current layout");
   let session_store = SledSessionStore {
     session_db: db.open_tree("sessions")?,
   };
@@ -49,6 +84,20 @@ async fn main() -> std::io::Result<()> {
     user_db: db.open_tree("user")?,
     login_db: db.open_tree("login")?,
     object_list_db: db.open_tree("ol")?,
+    totp_db: db.open_tree("totp")?,
+    api_token_db: db.open_tree("api_token")?,
+    invite_db: db.open_tree("invite")?,
+    household_db: db.open_tree("household")?,
+    share_link_db: db.open_tree("share_link")?,
+    activity_db: db.open_tree("activity")?,
+    check_off_db: db.open_tree("check_off")?,
+    trash_db: db.open_tree("trash")?,
+    category_db: db.open_tree("category")?,
+    webhook_db: db.open_tree("webhook")?,
+    webhook_queue_db: db.open_tree("webhook_queue")?,
+    webhook_delivery_db: db.open_tree("webhook_delivery")?,
+    webhook_notify: Arc::new(Notify::new()),
+    argon2_params: config.extract_argon2_params(),
     db,
   };
 
@@ -57,7 +106,37 @@ async fn main() -> std::io::Result<()> {
 
   let cookie_priv_key = actix_web::cookie::Key::from(&key);
 
-  let config = util::config::load_config().unwrap();
+  let login_throttle = LoginThrottle {
+    attempt_db: application_state.db.open_tree("login_attempts")?,
+    max_attempts: config.login_max_attempts,
+    base_lockout: config.login_lockout_base,
+    max_lockout: config.login_lockout_max,
+  };
+  let idempotency_store = IdempotencyStore {
+    key_db: application_state.db.open_tree("idempotency_key")?,
+    retention: config.idempotency_key_retention,
+  };
+  let oidc_provider = OidcProvider::discover(&config, application_state.db.open_tree("oidc_link")?)
+    .await
+    .map(actix_web::web::Data::new);
+  // the trash is purged periodically rather than on access, so entries expire even if nobody looks at them
+  let purge_state = application_state.clone();
+  let trash_retention = config.trash_retention as i64;
+  spawn_hourly_purge("the trash", move || {
+    purge_state.purge_expired_trash(Session::get_current_time() - trash_retention)
+  });
+  let purge_throttle = login_throttle.clone();
+  spawn_hourly_purge("expired login attempts", move || {
+    purge_throttle.purge_expired()
+  });
+  let purge_store = idempotency_store.clone();
+  spawn_hourly_purge("expired idempotency keys", move || {
+    purge_store.purge_expired()
+  });
+  actix_web::rt::spawn(WebhookDispatcher::new(application_state.clone(), &config).run());
+  // the limiter has to be shared between all workers
+  let rate_limiter = config.extract_rate_limiter();
+  let shared_config = actix_web::web::Data::new(config.clone());
   let __config = config.clone();
   HttpServer::new(move || {
     let cors = __config.extract_cors();
@@ -68,27 +147,89 @@ async fn main() -> std::io::Result<()> {
 
     let app = actix_web::App::new()
       .app_data(actix_web::web::Data::new(application_state.clone()))
+      .app_data(actix_web::web::Data::new(login_throttle.clone()))
+      .app_data(actix_web::web::Data::new(idempotency_store.clone()))
+      .app_data(shared_config.clone())
       // =========================== REGISTER ROUTES HERE ===========================
       .service(crate::api::article::store_article)
       .service(crate::api::article::get_article_by_id)
+      .service(crate::api::article::transfer_article)
+      .service(crate::api::article::patch_article)
       .service(crate::api::item::get_item_by_id)
       .service(update_item_attached)
+      .service(patch_item)
       .service(delete_item)
       .service(update_item_list)
+      .service(patch_item_list)
       .service(get_item_list_flat)
+      .service(get_item_list_sorted)
+      .service(merge_duplicates)
+      .service(quick_add_items)
+      .service(store_items_mass)
+      .service(get_item_list_activity)
       .service(store_item_list)
+      .service(transfer_item_list)
       .service(store_item_attached)
+      .service(reorder_item)
+      .service(move_item)
       .service(get_shop)
       .service(store_shop)
+      .service(transfer_shop)
+      .service(update_shop_layout)
+      .service(patch_shop)
       .service(register_v1)
       .service(login_v1)
+      .service(login_totp_v1)
+      .service(login_oidc_v1)
+      .service(login_oidc_callback_v1)
+      .service(enrol_totp)
+      .service(confirm_totp)
+      .service(disable_totp)
+      .service(create_api_token)
+      .service(list_api_tokens)
+      .service(revoke_api_token)
+      .service(create_invite)
+      .service(accept_invite)
+      .service(create_household)
+      .service(get_users_households)
+      .service(leave_household)
+      .service(dissolve_household)
+      .service(store_household_list)
+      .service(move_list_to_household)
+      .service(create_share_link)
+      .service(list_share_links)
+      .service(revoke_share_link)
+      .service(get_shared_list_flat)
+      .service(shared_list_page)
+      .service(toggle_shared_item)
+      .service(delete_item_list)
+      .service(get_trashed_lists)
+      .service(get_trashed_items)
+      .service(restore_trash)
+      .service(purge_trash)
+      .service(create_webhook)
+      .service(list_webhooks)
+      .service(list_item_list_webhooks)
+      .service(delete_webhook)
+      .service(get_webhook_deliveries)
+      .service(execute_batch)
+      .service(get_categories)
+      .service(create_category)
+      .service(delete_category)
       .service(get_users_lists);
     // =========================== REGISTER ROUTES HERE ===========================
 
+    // the oidc routes respond with not found if no provider is configured
+    let app = match &oidc_provider {
+      Some(provider) => app.app_data(provider.clone()),
+      None => app,
+    };
+
     #[cfg(feature = "serve_frontend")]
     let app = { app.service(crate::util::serve_frontend::serve_frontend) };
 
     app
+      .wrap(Governor::new(&rate_limiter))
       .wrap(cors)
       .wrap(Logger::default())
       .wrap(identity_mw)
@@ -109,6 +250,22 @@ async fn main() -> std::io::Result<()> {
   .await
 }
 
+/// Runs the purge once an hour for as long as the server is running, failures are only logged
+fn spawn_hourly_purge<T, E: std::fmt::Display>(
+  what: &'static str,
+  purge: impl Fn() -> Result<T, E> + 'static,
+) {
+  actix_web::rt::spawn(async move {
+    let mut interval = actix_web::rt::time::interval(Duration::from_secs(60 * 60));
+    loop {
+      interval.tick().await;
+      if let Err(e) = purge() {
+        tracing::error!("Purging {what} failed: {e}");
+      }
+    }
+  });
+}
+
 pub fn setup_tracing() {
   use tracing_subscriber::prelude::*;
 
@@ -5,10 +5,10 @@ use std::ops::{FromResidual, Try};
 use actix_session::storage::LoadError;
 use actix_web::body::BoxBody;
 use actix_web::error::{
-  ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound, ErrorUnauthorized,
-  PayloadError,
+  ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound, ErrorTooManyRequests,
+  ErrorUnauthorized, PayloadError,
 };
-use actix_web::http::header::ACCEPT;
+use actix_web::http::header::{ACCEPT, RETRY_AFTER};
 use actix_web::{HttpResponse, Responder};
 use anyhow::anyhow;
 use bytecheck::StructCheckError;
@@ -151,6 +151,9 @@ where
           ResponseError::ErrorNotFound => HttpResponse::NotFound().body(body),
           ResponseError::ErrorUnauthorized => HttpResponse::Unauthorized().body(body),
           ResponseError::ErrorUnauthenticated => HttpResponse::Unauthorized().body(body),
+          ResponseError::ErrorTooManyRequests(retry_after) => HttpResponse::TooManyRequests()
+            .insert_header((RETRY_AFTER, retry_after.to_string()))
+            .body(body),
         }
       }
     }
@@ -163,6 +166,8 @@ pub enum ResponseError {
   ErrorUnauthenticated,
   ErrorUnauthorized,
   ErrorNotFound,
+  /// Contains the number of seconds after which the client may retry
+  ErrorTooManyRequests(u64),
   ErrorInternalServerError(Box<dyn std::error::Error>),
 }
 
@@ -174,6 +179,7 @@ impl From<ResponseError> for actix_web::Error {
       ResponseError::ErrorInternalServerError { .. } => ErrorInternalServerError(val.to_string()),
       ResponseError::ErrorUnauthenticated => ErrorUnauthorized(val.to_string()),
       ResponseError::ErrorBadRequest => ErrorBadRequest(val.to_string()),
+      ResponseError::ErrorTooManyRequests(_) => ErrorTooManyRequests(val.to_string()),
     }
   }
 }
@@ -204,6 +210,9 @@ impl Display for ResponseError {
         "You are not authenticated. You must authenticate yourself to use this endpoint.".into()
       }
       ResponseError::ErrorBadRequest => "Bad request: Submitted data was malformed.".into(),
+      ResponseError::ErrorTooManyRequests(retry_after) => {
+        format!("Too many requests. Try again in {retry_after} seconds.")
+      }
     };
 
     write!(f, "{}", error)
@@ -312,6 +321,7 @@ impl From<ResponseError> for LoadError {
       ResponseError::ErrorUnauthenticated |
       ResponseError::ErrorUnauthorized |
       ResponseError::ErrorNotFound |
+      ResponseError::ErrorTooManyRequests(_) |
       ResponseError::ErrorInternalServerError(_) => {
         LoadError::Deserialization(anyhow!(e.to_string()))
       }
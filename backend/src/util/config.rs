@@ -3,7 +3,10 @@ use std::fmt::Display;
 use std::io::BufReader;
 
 use actix_cors::Cors;
+use actix_governor::governor::middleware::NoOpMiddleware;
+use actix_governor::{GovernorConfig, GovernorConfigBuilder, PeerIpKeyExtractor};
 use actix_web::http::header;
+use argon2::Params;
 use config::Config;
 use rustls::{Certificate, PrivateKey, ServerConfig};
 use rustls_pemfile::pkcs8_private_keys;
@@ -13,6 +16,70 @@ pub(crate) struct BackendConfig {
   pub tls_config: ServerConfig,
   pub cookie_timeout: u64,
   pub cors: Option<String>,
+  /// Sustained number of requests per second a single peer may send; 0 disables rate limiting
+  pub requests_per_second: u64,
+  /// Number of requests a single peer may send in a burst before being rate limited
+  pub request_burst_size: u32,
+  /// Number of failed logins per user name or peer that are tolerated before locking out further attempts
+  pub login_max_attempts: u32,
+  /// Duration of the first login lockout in seconds; it doubles with every further failed attempt
+  pub login_lockout_base: u64,
+  /// Upper bound for the login lockout in seconds
+  pub login_lockout_max: u64,
+  /// Memory cost of password hashing in KiB
+  pub argon2_memory_cost: u32,
+  /// Number of passes over the memory when hashing passwords
+  pub argon2_time_cost: u32,
+  /// Number of lanes used when hashing passwords
+  pub argon2_parallelism: u32,
+  pub registration: RegistrationMode,
+  /// Names of the users allowed to create invites if `invites_admin_only` is set
+  pub admins: Vec<String>,
+  /// Restricts the creation of invite codes to admins instead of all users
+  pub invites_admin_only: bool,
+  /// Default and maximum validity of invite codes in seconds
+  pub invite_validity: u64,
+  /// Seconds deleted items and lists are kept in the trash before they are purged
+  pub trash_retention: u64,
+  /// Seconds the response to a request with an `Idempotency-Key` header is replayed for retries
+  pub idempotency_key_retention: u64,
+  /// Number of attempts to deliver a webhook payload before giving up
+  pub webhook_max_attempts: u32,
+  /// Seconds to wait for a webhook receiver to respond
+  pub webhook_timeout: u64,
+  /// Allows webhooks to loopback, private and link-local addresses, e.g. for receivers on the same host
+  pub webhook_allow_private: bool,
+  /// Issuer of the OpenID Connect provider; login via OIDC is disabled if this is not set
+  pub oidc_issuer_url: Option<String>,
+  pub oidc_client_id: Option<String>,
+  /// Only required for confidential clients
+  pub oidc_client_secret: Option<String>,
+  /// Where the provider redirects the user to after authenticating; the client has to pass the query on to the backend
+  pub oidc_redirect_url: Option<String>,
+  /// Creates accounts for unknown subjects on their first login instead of rejecting them; only while `registration` is open
+  pub oidc_create_accounts: bool,
+}
+
+/// Who may create new accounts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RegistrationMode {
+  Open,
+  /// Accounts can only be created with a valid invite code
+  InviteOnly,
+  Closed,
+}
+
+impl std::str::FromStr for RegistrationMode {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "open" => Ok(RegistrationMode::Open),
+      "invite_only" => Ok(RegistrationMode::InviteOnly),
+      "closed" => Ok(RegistrationMode::Closed),
+      _ => Err(()),
+    }
+  }
 }
 
 impl BackendConfig {
@@ -38,6 +105,29 @@ impl BackendConfig {
         actix_cors::Cors::default()
       })
   }
+
+  /// The limiter state is shared through the returned config, so this must only be called once and not per worker.
+  pub fn extract_rate_limiter(&self) -> GovernorConfig<PeerIpKeyExtractor, NoOpMiddleware> {
+    let mut builder = GovernorConfigBuilder::default();
+
+    match self.requests_per_second {
+      0 => builder.permissive(true),
+      rate => builder.requests_per_second(rate),
+    }
+    .burst_size(self.request_burst_size)
+    .finish()
+    .expect("Invalid rate limiting configuration. Burst size must not be zero.")
+  }
+
+  pub fn extract_argon2_params(&self) -> Params {
+    Params::new(
+      self.argon2_memory_cost,
+      self.argon2_time_cost,
+      self.argon2_parallelism,
+      None,
+    )
+    .expect("Invalid password hashing parameters. Refusing to operate.")
+  }
 }
 
 pub(crate) fn load_config() -> Result<BackendConfig, LoadConfigError> {
@@ -58,7 +148,27 @@ pub(crate) fn load_config() -> Result<BackendConfig, LoadConfigError> {
     user_settings.add_source(config::File::from(local_config_file))
   };
 
-  let user_settings = match user_settings.set_default("cookie_timeout", 60 * 60 * 24 * 30) {
+  let user_settings = match user_settings
+    .set_default("cookie_timeout", 60 * 60 * 24 * 30)
+    .and_then(|settings| settings.set_default("requests_per_second", 50))
+    .and_then(|settings| settings.set_default("request_burst_size", 100))
+    .and_then(|settings| settings.set_default("login_max_attempts", 5))
+    .and_then(|settings| settings.set_default("login_lockout_base", 30))
+    .and_then(|settings| settings.set_default("login_lockout_max", 60 * 60))
+    .and_then(|settings| settings.set_default("argon2_memory_cost", Params::DEFAULT_M_COST))
+    .and_then(|settings| settings.set_default("argon2_time_cost", Params::DEFAULT_T_COST))
+    .and_then(|settings| settings.set_default("argon2_parallelism", Params::DEFAULT_P_COST))
+    .and_then(|settings| settings.set_default("registration", "open"))
+    .and_then(|settings| settings.set_default("admins", ""))
+    .and_then(|settings| settings.set_default("invites_admin_only", false))
+    .and_then(|settings| settings.set_default("invite_validity", 60 * 60 * 24 * 7))
+    .and_then(|settings| settings.set_default("trash_retention", 60 * 60 * 24 * 30))
+    .and_then(|settings| settings.set_default("idempotency_key_retention", 60 * 60 * 24))
+    .and_then(|settings| settings.set_default("webhook_max_attempts", 8))
+    .and_then(|settings| settings.set_default("webhook_timeout", 10))
+    .and_then(|settings| settings.set_default("webhook_allow_private", false))
+    .and_then(|settings| settings.set_default("oidc_create_accounts", false))
+  {
     Ok(val) => val,
     Err(_) => return Err(LoadConfigError::ConfigCrateError),
   };
@@ -86,14 +196,45 @@ pub(crate) fn load_config() -> Result<BackendConfig, LoadConfigError> {
   Ok(BackendConfig {
     cors,
     tls_config: server_config,
-    cookie_timeout: user_settings
-      .get("cookie_timeout")
-      .unwrap()
-      .parse()
-      .unwrap(),
+    cookie_timeout: parse_setting(&user_settings, "cookie_timeout"),
+    requests_per_second: parse_setting(&user_settings, "requests_per_second"),
+    request_burst_size: parse_setting(&user_settings, "request_burst_size"),
+    login_max_attempts: parse_setting(&user_settings, "login_max_attempts"),
+    login_lockout_base: parse_setting(&user_settings, "login_lockout_base"),
+    login_lockout_max: parse_setting(&user_settings, "login_lockout_max"),
+    argon2_memory_cost: parse_setting(&user_settings, "argon2_memory_cost"),
+    argon2_time_cost: parse_setting(&user_settings, "argon2_time_cost"),
+    argon2_parallelism: parse_setting(&user_settings, "argon2_parallelism"),
+    registration: parse_setting(&user_settings, "registration"),
+    admins: user_settings["admins"]
+      .split(',')
+      .map(str::trim)
+      .filter(|name| !name.is_empty())
+      .map(str::to_owned)
+      .collect(),
+    invites_admin_only: parse_setting(&user_settings, "invites_admin_only"),
+    invite_validity: parse_setting(&user_settings, "invite_validity"),
+    trash_retention: parse_setting(&user_settings, "trash_retention"),
+    idempotency_key_retention: parse_setting(&user_settings, "idempotency_key_retention"),
+    webhook_max_attempts: parse_setting(&user_settings, "webhook_max_attempts"),
+    webhook_timeout: parse_setting(&user_settings, "webhook_timeout"),
+    webhook_allow_private: parse_setting(&user_settings, "webhook_allow_private"),
+    oidc_issuer_url: user_settings.get("oidc_issuer_url").cloned(),
+    oidc_client_id: user_settings.get("oidc_client_id").cloned(),
+    oidc_client_secret: user_settings.get("oidc_client_secret").cloned(),
+    oidc_redirect_url: user_settings.get("oidc_redirect_url").cloned(),
+    oidc_create_accounts: parse_setting(&user_settings, "oidc_create_accounts"),
   })
 }
 
+/// All of these settings have defaults, so only malformed values in the configuration files can make this panic.
+fn parse_setting<T: std::str::FromStr>(settings: &HashMap<String, String>, key: &str) -> T {
+  settings
+    .get(key)
+    .and_then(|value| value.parse().ok())
+    .unwrap_or_else(|| panic!("Invalid value for setting {key}. Refusing to operate."))
+}
+
 fn load_rustls_config(
   cert_path: &std::path::Path,
   key_path: &std::path::Path,
@@ -0,0 +1,212 @@
+use std::future::{ready, Ready};
+
+use actix_web::FromRequest;
+use einkaufsliste::model::session::Session;
+use einkaufsliste::ApiObject;
+use rkyv::de::deserializers::SharedDeserializeMap;
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::{Archive, Deserialize, Serialize};
+use sled::CompareAndSwapError;
+
+use crate::response::ResponseError;
+use crate::util::errors::bad_request;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Seconds after which a request that never finished, e.g. because the server was stopped, no longer blocks its key
+const PENDING_TIMEOUT: i64 = 60;
+
+/// The response of the first request with an idempotency key
+#[derive(Archive, Serialize, Deserialize, Debug)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct StoredResponse {
+  /// Keys can not be reused for another endpoint, as the stored response would not match it
+  pub endpoint: String,
+  pub created_at: i64,
+  /// The archived response, `None` while the first request is still being handled
+  pub response: Option<Vec<u8>>,
+}
+
+/// The optional `Idempotency-Key` header of a request
+pub struct IdempotencyKey(pub Option<String>);
+
+impl FromRequest for IdempotencyKey {
+  type Error = ResponseError;
+
+  type Future = Ready<Result<Self, Self::Error>>;
+
+  fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+    let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+      None => Ok(IdempotencyKey(None)),
+      Some(header) => match header.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => {
+          Ok(IdempotencyKey(Some(key.to_owned())))
+        }
+        _ => Err(bad_request("Invalid idempotency key")),
+      },
+    };
+
+    ready(key)
+  }
+}
+
+/**
+Remembers the responses of requests with an idempotency key for a while, so that retrying a request whose response got
+lost returns the original response instead of creating another object.
+
+Keys are scoped to the user, so guessing the key of someone else does not reveal their response.
+*/
+#[derive(Clone)]
+pub struct IdempotencyStore {
+  pub(crate) key_db: sled::Tree,
+  /// Seconds a response is replayed for
+  pub retention: u64,
+}
+
+impl IdempotencyStore {
+  /**
+  Handles the request, unless a request with the same key has been handled before, in which case its response is
+  returned instead. Requests without a key are always handled.
+
+  A request whose first attempt is still being handled is rejected with [`ResponseError::ErrorTooManyRequests`],
+  failed requests are forgotten so that they can be retried with the same key.
+  */
+  pub fn run<T, F>(
+    &self,
+    key: &IdempotencyKey,
+    user_id: u64,
+    endpoint: &str,
+    handle: F,
+  ) -> Result<T, ResponseError>
+  where
+    T: ApiObject<'static>,
+    T::Archived:
+      rkyv::Deserialize<T, SharedDeserializeMap> + rkyv::CheckBytes<DefaultValidator<'static>>,
+    F: FnOnce() -> Result<T, ResponseError>,
+  {
+    let Some(key) = &key.0 else {
+      return handle();
+    };
+    let db_key = format!("{user_id}:{key}");
+
+    let pending = StoredResponse {
+      endpoint: endpoint.to_owned(),
+      created_at: Session::get_current_time(),
+      response: None,
+    };
+    if let Some(stored) = self.claim(&db_key, &pending)? {
+      return replay(stored, endpoint);
+    }
+
+    let result = handle();
+    match &result {
+      Ok(response) => {
+        let stored = StoredResponse {
+          response: Some(rkyv::to_bytes::<_, 4096>(response)?.to_vec()),
+          ..pending
+        };
+        self
+          .key_db
+          .insert(&db_key, &*rkyv::to_bytes::<_, 256>(&stored)?)?;
+      }
+      Err(_) => {
+        self.key_db.remove(&db_key)?;
+      }
+    }
+
+    result
+  }
+
+  /// Removes expired keys, so keys of requests that are never retried do not pile up
+  pub fn purge_expired(&self) -> Result<usize, ResponseError> {
+    let now = Session::get_current_time();
+    let mut purged = 0;
+
+    for entry in self.key_db.iter() {
+      let (key, bytes) = entry?;
+      let stored = unsafe { rkyv::from_bytes_unchecked::<StoredResponse>(&bytes) }?;
+
+      if self.is_expired(&stored, now) {
+        // the key may have been claimed again in the meantime
+        if self
+          .key_db
+          .compare_and_swap(&key, Some(&bytes), None as Option<&[u8]>)?
+          .is_ok()
+        {
+          purged += 1;
+        }
+      }
+    }
+
+    Ok(purged)
+  }
+
+  /// Stores the pending response under the key, or returns the stored response if the key is taken
+  fn claim(
+    &self,
+    db_key: &str,
+    pending: &StoredResponse,
+  ) -> Result<Option<StoredResponse>, ResponseError> {
+    let pending_bytes = rkyv::to_bytes::<_, 256>(pending)?;
+    let mut current = None;
+
+    loop {
+      let existing =
+        match self
+          .key_db
+          .compare_and_swap(db_key, current.as_ref(), Some(&*pending_bytes))?
+        {
+          Ok(()) => return Ok(None),
+          Err(CompareAndSwapError { current: None, .. }) => {
+            current = None;
+            continue;
+          }
+          Err(CompareAndSwapError {
+            current: Some(existing),
+            ..
+          }) => existing,
+        };
+
+      let stored = unsafe { rkyv::from_bytes_unchecked::<StoredResponse>(&existing) }?;
+      if !self.is_expired(&stored, pending.created_at) {
+        return Ok(Some(stored));
+      }
+      // expired keys can be claimed again
+      current = Some(existing);
+    }
+  }
+
+  fn is_expired(&self, stored: &StoredResponse, now: i64) -> bool {
+    let retention = match stored.response {
+      Some(_) => self.retention as i64,
+      None => PENDING_TIMEOUT,
+    };
+
+    stored.created_at + retention < now
+  }
+}
+
+fn replay<T>(stored: StoredResponse, endpoint: &str) -> Result<T, ResponseError>
+where
+  T: ApiObject<'static>,
+  T::Archived:
+    rkyv::Deserialize<T, SharedDeserializeMap> + rkyv::CheckBytes<DefaultValidator<'static>>,
+{
+  if stored.endpoint != endpoint {
+    return Err(bad_request("Idempotency key was used for another endpoint"));
+  }
+
+  match stored.response {
+    Some(response) => {
+      // the archive has to be aligned, which the deserialized vector is not guaranteed to be
+      let mut aligned = rkyv::AlignedVec::with_capacity(response.len());
+      aligned.extend_from_slice(&response);
+
+      Ok(unsafe { rkyv::from_bytes_unchecked::<T>(&aligned) }?)
+    }
+    // the first request is still being handled, its response can be replayed once it is done
+    None => Err(ResponseError::ErrorTooManyRequests(1)),
+  }
+}
@@ -1,12 +1,20 @@
 use std::future::{ready, Ready};
 
 use actix_identity::Identity;
-use actix_web::FromRequest;
+use actix_web::http::header::{HeaderValue, AUTHORIZATION};
+use actix_web::http::Method;
+use actix_web::{web, FromRequest, HttpRequest};
+use einkaufsliste::model::session::Session;
+use einkaufsliste::model::token::StoredApiToken;
 
+use crate::db::{DbError, DbState};
 use crate::response::ResponseError;
+use crate::util::secret::{hash_secret, parse_token};
 
 pub struct AuthenticatedUser {
   pub id: u64,
+  /// Set if the request was authenticated with a personal api token instead of the session identity
+  pub api_token_id: Option<u64>,
 }
 
 impl FromRequest for AuthenticatedUser {
@@ -18,13 +26,62 @@ impl FromRequest for AuthenticatedUser {
     req: &actix_web::HttpRequest,
     payload: &mut actix_web::dev::Payload,
   ) -> Self::Future {
+    // an explicitly provided token must not silently fall back to the session
+    if let Some(header) = req.headers().get(AUTHORIZATION) {
+      return ready(authenticate_bearer(req, header));
+    }
+
     if let Ok(identity) = Identity::from_request(req, payload).into_inner() {
       if let Ok(user_string) = identity.id() {
         if let Ok(user) = user_string.parse::<u64>() {
-          return ready(Ok(AuthenticatedUser { id: user }));
+          return ready(Ok(AuthenticatedUser {
+            id: user,
+            api_token_id: None,
+          }));
         }
       }
     }
     ready(Err(ResponseError::ErrorUnauthenticated))
   }
 }
+
+fn authenticate_bearer(
+  req: &HttpRequest,
+  header: &HeaderValue,
+) -> Result<AuthenticatedUser, ResponseError> {
+  let (id, secret) = header
+    .to_str()
+    .ok()
+    .and_then(|value| value.strip_prefix("Bearer "))
+    .and_then(parse_token)
+    .ok_or(ResponseError::ErrorUnauthenticated)?;
+
+  let state = req
+    .app_data::<web::Data<DbState>>()
+    .ok_or_else(|| ResponseError::ErrorInternalServerError("Missing application state".into()))?;
+
+  let stored: StoredApiToken = match state.get_unchecked(id) {
+    Ok(stored) => stored,
+    Err(DbError::NotFound) => return Err(ResponseError::ErrorUnauthenticated),
+    Err(e) => return Err(e.into()),
+  };
+
+  if stored.secret_hash != hash_secret(secret) {
+    return Err(ResponseError::ErrorUnauthenticated);
+  }
+  if stored
+    .token
+    .expires
+    .is_some_and(|expires| expires < Session::get_current_time())
+  {
+    return Err(ResponseError::ErrorUnauthenticated);
+  }
+  if stored.token.read_only && ![Method::GET, Method::HEAD].contains(req.method()) {
+    return Err(ResponseError::ErrorUnauthorized);
+  }
+
+  Ok(AuthenticatedUser {
+    id: stored.user_id,
+    api_token_id: Some(stored.token.id),
+  })
+}
@@ -0,0 +1,171 @@
+use std::cell::Cell;
+
+use einkaufsliste::model::session::Session;
+use rkyv::{Archive, Deserialize, Serialize};
+
+use crate::response::ResponseError;
+
+/// Login attempts recorded for a single user name or peer address, successful ones are taken back
+#[derive(Archive, Serialize, Deserialize, Debug, Default)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct FailedLogins {
+  pub count: u32,
+  pub last_attempt: i64,
+  pub locked_until: i64,
+}
+
+/**
+Tracks failed logins per user name and per peer address and locks out further attempts with exponential back-off.
+
+Locked out attempts are rejected before the password is hashed, so guessing passwords cannot tie up the workers with Argon2.
+*/
+#[derive(Clone)]
+pub struct LoginThrottle {
+  pub(crate) attempt_db: sled::Tree,
+  pub max_attempts: u32,
+  pub base_lockout: u64,
+  pub max_lockout: u64,
+}
+
+impl LoginThrottle {
+  pub fn user_key(user_name: &str) -> String {
+    format!("user:{user_name}")
+  }
+
+  pub fn peer_key(request: &actix_web::HttpRequest) -> String {
+    // the real ip headers can be set by clients at will, so only the actual peer is trustworthy here
+    let address = request
+      .peer_addr()
+      .map(|address| address.ip().to_string())
+      .unwrap_or_default();
+
+    format!("peer:{address}")
+  }
+
+  /**
+  Counts the login attempt for all keys before the credentials are verified, so that concurrent attempts can not all
+  pass before the first of them failed. If any of the keys is currently locked out, the attempt is not counted and
+  rejected with [`ResponseError::ErrorTooManyRequests`].
+
+  Attempts that turn out to be successful have to be taken back with [`Self::release`].
+  */
+  pub fn register_attempt(&self, keys: &[&str]) -> Result<(), ResponseError> {
+    let now = Session::get_current_time();
+    let mut retry_after = 0;
+    let mut counted = Vec::with_capacity(keys.len());
+
+    for key in keys {
+      match self.count_attempt(key, now)? {
+        0 => counted.push(*key),
+        secs => retry_after = retry_after.max(secs),
+      }
+    }
+
+    if retry_after > 0 {
+      for key in counted {
+        self.release(key)?;
+      }
+      return Err(ResponseError::ErrorTooManyRequests(retry_after as u64));
+    }
+
+    Ok(())
+  }
+
+  /// Takes back an attempt counted by [`Self::register_attempt`] that did not fail
+  pub fn release(&self, key: &str) -> Result<(), ResponseError> {
+    self.attempt_db.update_and_fetch(key, |old| {
+      let Some(mut attempts) =
+        old.and_then(|bytes| unsafe { rkyv::from_bytes_unchecked::<FailedLogins>(bytes) }.ok())
+      else {
+        return old.map(<[u8]>::to_vec);
+      };
+
+      attempts.count = attempts.count.saturating_sub(1);
+      if attempts.count == 0 {
+        return None;
+      }
+      // the attempt was not rejected, so the key was not locked out before it
+      attempts.locked_until = 0;
+
+      match rkyv::to_bytes::<_, 64>(&attempts) {
+        Ok(bytes) => Some(bytes.to_vec()),
+        Err(_) => old.map(<[u8]>::to_vec),
+      }
+    })?;
+
+    Ok(())
+  }
+
+  pub fn reset(&self, key: &str) -> Result<(), ResponseError> {
+    self.attempt_db.remove(key)?;
+
+    Ok(())
+  }
+
+  /// Removes the entries whose failures are forgotten, as the keys are chosen by whoever tries to log in
+  pub fn purge_expired(&self) -> Result<usize, ResponseError> {
+    let now = Session::get_current_time();
+    let mut purged = 0;
+
+    for entry in self.attempt_db.iter() {
+      let (key, bytes) = entry?;
+      let attempts = unsafe { rkyv::from_bytes_unchecked::<FailedLogins>(&bytes) }?;
+
+      if self.is_forgotten(&attempts, now) {
+        // the key may have been attempted again in the meantime
+        let removed =
+          self
+            .attempt_db
+            .compare_and_swap(&key, Some(&bytes), None as Option<&[u8]>)?;
+        if removed.is_ok() {
+          purged += 1;
+        }
+      }
+    }
+
+    Ok(purged)
+  }
+
+  /// Counts the attempt unless the key is locked out, in which case the seconds until the lockout ends are returned
+  fn count_attempt(&self, key: &str, now: i64) -> Result<i64, ResponseError> {
+    // the update is retried on conflicts, so only the outcome of the last run is kept
+    let locked_for = Cell::new(0);
+
+    // update atomically, as concurrent guesses would otherwise be undercounted
+    self.attempt_db.update_and_fetch(key, |old| {
+      let mut attempts = old
+        .and_then(|bytes| unsafe { rkyv::from_bytes_unchecked::<FailedLogins>(bytes) }.ok())
+        .filter(|attempts| !self.is_forgotten(attempts, now))
+        .unwrap_or_default();
+
+      if attempts.locked_until > now {
+        locked_for.set(attempts.locked_until - now);
+        return old.map(<[u8]>::to_vec);
+      }
+      locked_for.set(0);
+
+      attempts.count += 1;
+      attempts.last_attempt = now;
+
+      if attempts.count >= self.max_attempts {
+        let exponent = attempts.count - self.max_attempts;
+        let lockout = self
+          .base_lockout
+          .saturating_mul(2u64.saturating_pow(exponent))
+          .min(self.max_lockout);
+        attempts.locked_until = now + lockout as i64;
+      }
+
+      match rkyv::to_bytes::<_, 64>(&attempts) {
+        Ok(bytes) => Some(bytes.to_vec()),
+        Err(_) => old.map(<[u8]>::to_vec),
+      }
+    })?;
+
+    Ok(locked_for.get())
+  }
+
+  fn is_forgotten(&self, attempts: &FailedLogins, now: i64) -> bool {
+    now - attempts.last_attempt > self.max_lockout as i64
+  }
+}
@@ -0,0 +1,254 @@
+/*!
+Converts objects stored by earlier versions into the current layout on startup.
+
+Objects are read with [`rkyv::from_bytes_unchecked`], so reading an object stored with an outdated layout is undefined
+behaviour. Every tree therefore records the version of its layout, and the conversions from each version to the next one
+are run before the server starts. Outdated objects are read with validation, so that unexpected data aborts the startup
+instead of being misread.
+*/
+
+use einkaufsliste::model::article::Article;
+use einkaufsliste::model::item::Item;
+use einkaufsliste::model::list::List;
+use einkaufsliste::model::shop::Shop;
+use einkaufsliste::model::unit::{Amount, Unit};
+use einkaufsliste::model::user::{Password, User, UserWithPassword};
+use einkaufsliste::model::{AccessControlList, Member, Role};
+use rkyv::{Archive, Deserialize, Serialize};
+use sled::transaction::{ConflictableTransactionError, TransactionError};
+use sled::Transactional;
+use tracing::info;
+
+use crate::db::DbError;
+
+/// Converts an object stored with one layout of the tree into the next layout
+type Conversion = fn(&[u8]) -> Result<Vec<u8>, DbError>;
+
+/// The conversions of every tree whose layout changed, ordered by the version they convert from
+const MIGRATIONS: &[(&str, &[Conversion])] = &[
+  ("login", &[login_v0]),
+  ("list_acl", &[acl_v0]),
+  ("article", &[article_v0]),
+  ("shop", &[shop_v0]),
+  ("item", &[item_v0]),
+];
+
+/// Runs every conversion that has not been applied yet. Trees of a new database are empty and simply marked as current.
+pub fn migrate(db: &sled::Db) -> Result<(), DbError> {
+  for (tree_name, conversions) in MIGRATIONS {
+    let tree = db.open_tree(tree_name)?;
+    let version_key = format!("layout_version:{tree_name}");
+    let version = match db.get(&version_key)? {
+      Some(bytes) => u64::from_be_bytes(
+        bytes
+          .as_ref()
+          .try_into()
+          .map_err(|_| DbError::Encoding("invalid layout version".into()))?,
+      ),
+      None => 0,
+    };
+
+    for (from, convert) in conversions.iter().enumerate().skip(version as usize) {
+      info!("Converting tree {tree_name} from layout version {from}");
+
+      let converted = tree
+        .iter()
+        .map(|entry| {
+          let (key, bytes) = entry?;
+          Ok((key, convert(&bytes)?))
+        })
+        .collect::<Result<Vec<_>, DbError>>()?;
+
+      // the objects and the version are stored together, so an interrupted conversion is simply run again
+      (&**db, &tree)
+        .transaction(|(meta_tx, tree_tx)| {
+          for (key, bytes) in &converted {
+            tree_tx.insert(key, bytes.as_slice())?;
+          }
+          meta_tx.insert(version_key.as_bytes(), &(from as u64 + 1).to_be_bytes())?;
+
+          Ok::<_, ConflictableTransactionError<DbError>>(())
+        })
+        .map_err(|e| match e {
+          TransactionError::Storage(e) => DbError::IO(e.into()),
+          TransactionError::Abort(e) => e,
+        })?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Reads an object stored with an outdated layout, validating it as its layout can not be trusted
+fn read_legacy<T>(bytes: &[u8]) -> Result<T, DbError>
+where
+  T: Archive,
+  T::Archived: for<'a> rkyv::CheckBytes<rkyv::validation::validators::DefaultValidator<'a>>
+    + Deserialize<T, rkyv::de::deserializers::SharedDeserializeMap>,
+{
+  // the archive has to be aligned, which the bytes of sled are not guaranteed to be
+  let mut aligned = rkyv::AlignedVec::with_capacity(bytes.len());
+  aligned.extend_from_slice(bytes);
+
+  rkyv::from_bytes::<T>(&aligned).map_err(|e| DbError::Encoding(e.to_string().into()))
+}
+
+fn write<T: Serialize<rkyv::ser::serializers::AllocSerializer<4096>>>(
+  object: &T,
+) -> Result<Vec<u8>, DbError> {
+  Ok(rkyv::to_bytes::<_, 4096>(object)?.to_vec())
+}
+
+#[derive(Archive, Serialize, Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+struct UserV0 {
+  id: u64,
+  name: String,
+  profile_picture_id: Option<u64>,
+}
+
+#[derive(Archive, Serialize, Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+struct UserWithPasswordV0 {
+  user: UserV0,
+  password: PasswordV0,
+}
+
+/// A raw Argon2 hash with the default parameters
+#[derive(Archive, Serialize, Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+struct PasswordV0 {
+  hash: Vec<u8>,
+  salt: Vec<u8>,
+}
+
+/// Passwords became [`Password::Legacy`], they are rehashed as PHC strings on the next login
+fn login_v0(bytes: &[u8]) -> Result<Vec<u8>, DbError> {
+  let UserWithPasswordV0 { user, password } = read_legacy(bytes)?;
+
+  write(&UserWithPassword {
+    user: User {
+      id: user.id,
+      name: user.name,
+      profile_picture_id: user.profile_picture_id,
+    },
+    password: Password::Legacy {
+      hash: password.hash,
+      salt: password.salt,
+    },
+  })
+}
+
+#[derive(Archive, Serialize, Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+struct AccessControlListV0 {
+  object_id: u64,
+  owner: u64,
+  allowed_user_ids: Vec<u64>,
+}
+
+/// Allowed users became members with roles; they could change everything but the owner, like an editor
+fn acl_v0(bytes: &[u8]) -> Result<Vec<u8>, DbError> {
+  let acl: AccessControlListV0 = read_legacy(bytes)?;
+
+  write(&AccessControlList::<List, User> {
+    object_id: acl.object_id,
+    owner: acl.owner,
+    members: acl
+      .allowed_user_ids
+      .into_iter()
+      .filter(|user_id| *user_id != acl.owner)
+      .map(|user_id| Member {
+        user_id,
+        role: Role::Editor,
+      })
+      .collect(),
+    household: None,
+  })
+}
+
+#[derive(Archive, Serialize, Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+struct ArticleV0 {
+  id: u64,
+  name: String,
+  description: Option<String>,
+  image_id: Option<u32>,
+  shops: Option<Vec<u64>>,
+}
+
+/// Articles got a category, which existing ones do not have yet
+fn article_v0(bytes: &[u8]) -> Result<Vec<u8>, DbError> {
+  let article: ArticleV0 = read_legacy(bytes)?;
+
+  write(&Article {
+    id: article.id,
+    name: article.name,
+    description: article.description,
+    image_id: article.image_id,
+    shops: article.shops,
+    category_id: None,
+  })
+}
+
+#[derive(Archive, Serialize, Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+struct ShopV0 {
+  id: u64,
+  name: String,
+  image_id: Option<u32>,
+}
+
+/// Shops got a layout, which existing ones start without
+fn shop_v0(bytes: &[u8]) -> Result<Vec<u8>, DbError> {
+  let shop: ShopV0 = read_legacy(bytes)?;
+
+  write(&Shop {
+    id: shop.id,
+    name: shop.name,
+    image_id: shop.image_id,
+    layout: vec![],
+  })
+}
+
+#[derive(Archive, Serialize, Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+struct ItemV0 {
+  id: u64,
+  checked: bool,
+  name: String,
+  amount: Option<u64>,
+  unit: Option<UnitV0>,
+  article_id: Option<u64>,
+  alternative_article_ids: Option<Vec<u64>>,
+}
+
+#[derive(Archive, Serialize, Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+enum UnitV0 {
+  Gram,
+  KiloGram,
+  Pieces,
+  FreeForm(String),
+}
+
+/// Amounts became fractions and items got a category, which existing ones do not have yet
+fn item_v0(bytes: &[u8]) -> Result<Vec<u8>, DbError> {
+  let item: ItemV0 = read_legacy(bytes)?;
+
+  write(&Item {
+    id: item.id,
+    checked: item.checked,
+    name: item.name,
+    amount: item.amount.map(Amount::from),
+    unit: item.unit.map(|unit| match unit {
+      UnitV0::Gram => Unit::Gram,
+      UnitV0::KiloGram => Unit::KiloGram,
+      UnitV0::Pieces => Unit::Pieces,
+      UnitV0::FreeForm(name) => Unit::FreeForm(name),
+    }),
+    article_id: item.article_id,
+    alternative_article_ids: item.alternative_article_ids,
+    category_id: None,
+  })
+}
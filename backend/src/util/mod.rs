@@ -1,5 +1,11 @@
 pub mod config;
 pub mod errors;
+pub mod idempotency;
 pub mod identity_ext;
+pub mod login_throttle;
+pub mod migration;
+pub mod oidc;
+pub mod secret;
 pub(super) mod serve_frontend;
 pub mod session_store;
+pub mod webhook;
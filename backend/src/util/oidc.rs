@@ -0,0 +1,190 @@
+use openidconnect::core::{CoreClient, CoreProviderMetadata, CoreResponseType};
+use openidconnect::reqwest::async_http_client;
+use openidconnect::{
+  AuthenticationFlow, AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce,
+  PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope, TokenResponse,
+};
+use tracing::warn;
+
+use super::config::{BackendConfig, RegistrationMode};
+use super::errors::bad_request;
+use crate::response::ResponseError;
+
+/// Secrets of an authorization request that have to be remembered until the user returns from the provider
+pub struct PendingAuthorization {
+  pub csrf_state: String,
+  pub pkce_verifier: String,
+  pub nonce: String,
+}
+
+/// A user as asserted by the identity provider
+pub struct ExternalIdentity {
+  pub subject: String,
+  pub preferred_username: Option<String>,
+}
+
+/**
+Authorization code flow with PKCE against the configured OpenID Connect provider.
+
+External identities are linked to users by their subject, as that is the only claim guaranteed to be stable.
+*/
+#[derive(Clone)]
+pub struct OidcProvider {
+  client: CoreClient,
+  issuer: String,
+  /// Only set if both `oidc_create_accounts` is enabled and registration is open
+  pub create_accounts: bool,
+  pub(crate) link_db: sled::Tree,
+}
+
+impl OidcProvider {
+  /**
+  Returns `None` if OIDC is not configured. The provider metadata is only fetched once, so the provider must be reachable on startup.
+
+  Accounts are only created if registration is open as well, as there is no way to pass an invite code through the provider.
+  */
+  pub async fn discover(config: &BackendConfig, link_db: sled::Tree) -> Option<Self> {
+    let issuer = config.oidc_issuer_url.clone()?;
+
+    let client_id = config
+      .oidc_client_id
+      .clone()
+      .expect("Did not specify the OIDC client id.");
+    let redirect_url = config
+      .oidc_redirect_url
+      .clone()
+      .expect("Did not specify the OIDC redirect url.");
+
+    let create_accounts =
+      config.oidc_create_accounts && config.registration == RegistrationMode::Open;
+    if config.oidc_create_accounts && !create_accounts {
+      warn!("OIDC accounts are not created, as registration is not open");
+    }
+
+    let mut provider = Self::connect(
+      issuer,
+      client_id,
+      config.oidc_client_secret.clone(),
+      redirect_url,
+      link_db,
+    )
+    .await;
+    provider.create_accounts = create_accounts;
+
+    Some(provider)
+  }
+
+  /// Fetches the metadata of the provider; accounts are not created until `create_accounts` is set
+  pub(crate) async fn connect(
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: String,
+    link_db: sled::Tree,
+  ) -> Self {
+    let issuer_url =
+      IssuerUrl::new(issuer.clone()).expect("Invalid OIDC issuer url. Refusing to operate.");
+    let metadata = CoreProviderMetadata::discover_async(issuer_url, async_http_client)
+      .await
+      .expect("Could not discover the OIDC provider. Refusing to operate.");
+
+    let client = CoreClient::from_provider_metadata(
+      metadata,
+      ClientId::new(client_id),
+      client_secret.map(ClientSecret::new),
+    )
+    .set_redirect_uri(
+      RedirectUrl::new(redirect_url).expect("Invalid OIDC redirect url. Refusing to operate."),
+    );
+
+    Self {
+      client,
+      issuer,
+      create_accounts: false,
+      link_db,
+    }
+  }
+
+  /// Returns the url to send the user to
+  pub fn authorize(&self) -> (String, PendingAuthorization) {
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+    let (url, csrf_state, nonce) = self
+      .client
+      .authorize_url(
+        AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
+        CsrfToken::new_random,
+        Nonce::new_random,
+      )
+      .add_scope(Scope::new("profile".to_owned()))
+      .set_pkce_challenge(pkce_challenge)
+      .url();
+
+    let pending = PendingAuthorization {
+      csrf_state: csrf_state.secret().clone(),
+      pkce_verifier: pkce_verifier.secret().clone(),
+      nonce: nonce.secret().clone(),
+    };
+
+    (url.to_string(), pending)
+  }
+
+  /// Redeems the authorization code and verifies the returned id token
+  pub async fn exchange(
+    &self,
+    code: String,
+    pending: PendingAuthorization,
+  ) -> Result<ExternalIdentity, ResponseError> {
+    let response = self
+      .client
+      .exchange_code(AuthorizationCode::new(code))
+      .set_pkce_verifier(PkceCodeVerifier::new(pending.pkce_verifier))
+      .request_async(async_http_client)
+      .await
+      .map_err(|e| {
+        tracing::debug!("Could not redeem OIDC authorization code: {e}");
+        ResponseError::ErrorUnauthenticated
+      })?;
+
+    let id_token = response
+      .id_token()
+      .ok_or(ResponseError::ErrorUnauthenticated)?;
+    let claims = id_token
+      .claims(&self.client.id_token_verifier(), &Nonce::new(pending.nonce))
+      .map_err(|e| {
+        tracing::debug!("Rejected OIDC id token: {e}");
+        ResponseError::ErrorUnauthenticated
+      })?;
+
+    Ok(ExternalIdentity {
+      subject: claims.subject().to_string(),
+      preferred_username: claims.preferred_username().map(|name| name.to_string()),
+    })
+  }
+
+  pub fn linked_user(&self, subject: &str) -> Result<Option<u64>, ResponseError> {
+    let user_id = self.link_db.get(self.link_key(subject))?.and_then(|bytes| {
+      let bytes: [u8; 8] = bytes.as_ref().try_into().ok()?;
+      Some(u64::from_ne_bytes(bytes))
+    });
+
+    Ok(user_id)
+  }
+
+  /// Fails if the subject is already linked, so an identity can never silently be moved to another user
+  pub fn link(&self, subject: &str, user_id: u64) -> Result<(), ResponseError> {
+    self
+      .link_db
+      .compare_and_swap(
+        self.link_key(subject),
+        None as Option<&[u8]>,
+        Some(&user_id.to_ne_bytes()),
+      )?
+      .map_err(|_| bad_request("Identity is already linked to an account"))
+  }
+
+  fn link_key(&self, subject: &str) -> String {
+    // subjects are only unique per issuer
+    format!("{}\n{subject}", self.issuer)
+  }
+}
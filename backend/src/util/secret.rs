@@ -0,0 +1,27 @@
+use rand::rngs::OsRng;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+/// Generates a hex encoded secret from 256 bit of randomness, suitable for tokens and codes handed out to users
+pub fn generate_secret() -> String {
+  let mut secret = [0u8; 32];
+  OsRng.fill(&mut secret);
+
+  secret.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Secrets created by [`generate_secret`] are random and long enough that a fast hash suffices - unlike passwords
+pub fn hash_secret(secret: &str) -> Vec<u8> {
+  Sha256::digest(secret.as_bytes()).to_vec()
+}
+
+/// Tokens consist of the id of the stored object and the secret, so they can be looked up without scanning all objects.
+pub fn format_token(id: u64, secret: &str) -> String {
+  format!("{id}.{secret}")
+}
+
+pub fn parse_token(token: &str) -> Option<(u64, &str)> {
+  let (id, secret) = token.trim().split_once('.')?;
+
+  Some((id.parse().ok()?, secret))
+}
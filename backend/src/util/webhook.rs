@@ -0,0 +1,228 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use einkaufsliste::model::session::Session;
+use einkaufsliste::model::webhook::{PendingDelivery, StoredWebhook, WebhookDelivery};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::{debug, error};
+
+use super::config::BackendConfig;
+use crate::db::{DbError, DbState};
+
+/// Upper bound of the time between two checks of the queue, it is checked right away whenever a delivery is scheduled
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Delay before the first retry, doubled with every further attempt
+const RETRY_BASE_DELAY: i64 = 30;
+
+/**
+Posts the queued payloads to their webhooks, see [`DbState::schedule_delivery`].
+
+Deliveries are attempted at least once; receivers should use the delivery id to detect duplicates.
+Every attempt is logged, failed ones are retried with exponential back-off until `webhook_max_attempts` is reached.
+*/
+pub struct WebhookDispatcher {
+  state: DbState,
+  timeout: Duration,
+  max_attempts: u32,
+  allow_private: bool,
+}
+
+impl WebhookDispatcher {
+  pub fn new(state: DbState, config: &BackendConfig) -> Self {
+    Self {
+      state,
+      timeout: Duration::from_secs(config.webhook_timeout),
+      max_attempts: config.webhook_max_attempts,
+      allow_private: config.webhook_allow_private,
+    }
+  }
+
+  pub async fn run(self) {
+    loop {
+      if let Err(e) = self.deliver_due().await {
+        error!("Delivering webhooks failed: {e}");
+      }
+
+      let _ =
+        actix_web::rt::time::timeout(POLL_INTERVAL, self.state.webhook_notify.notified()).await;
+    }
+  }
+
+  async fn deliver_due(&self) -> Result<(), DbError> {
+    let now = Session::get_current_time();
+
+    for (queue_key, pending) in self.state.due_deliveries(now)? {
+      let webhook = match self
+        .state
+        .get_unchecked::<StoredWebhook>(pending.webhook_id)
+      {
+        Ok(webhook) => webhook,
+        // the webhook was deleted in the meantime
+        Err(DbError::NotFound) => {
+          self.state.webhook_queue_db.remove(queue_key)?;
+          continue;
+        }
+        Err(e) => return Err(e),
+      };
+
+      let attempt = pending.attempt + 1;
+      let (status, error) = match self.post(&webhook, &pending).await {
+        Ok(status) if status.is_success() => (Some(status.as_u16()), None),
+        Ok(status) => (
+          Some(status.as_u16()),
+          Some(format!("Unexpected status {status}")),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+      };
+      let retrying = error.is_some() && attempt < self.max_attempts;
+      debug!(
+        "Delivery {} to webhook {} finished with {:?}",
+        pending.delivery_id, pending.webhook_id, status
+      );
+
+      self.state.finish_delivery(
+        &queue_key,
+        &WebhookDelivery {
+          delivery_id: pending.delivery_id,
+          webhook_id: pending.webhook_id,
+          attempt,
+          timestamp: Session::get_current_time(),
+          status,
+          error,
+          retrying,
+        },
+      )?;
+
+      if retrying {
+        let delay = RETRY_BASE_DELAY << (attempt - 1).min(16);
+        self.state.schedule_delivery(
+          Session::get_current_time() + delay,
+          &PendingDelivery { attempt, ..pending },
+        )?;
+      }
+    }
+
+    Ok(())
+  }
+
+  async fn post(
+    &self,
+    webhook: &StoredWebhook,
+    pending: &PendingDelivery,
+  ) -> Result<reqwest::StatusCode, Box<dyn std::error::Error>> {
+    let url = reqwest::Url::parse(&webhook.hook.url)?;
+    let addresses = resolve_webhook_url(&url, self.allow_private).await?;
+
+    let mut client = reqwest::Client::builder()
+      .timeout(self.timeout)
+      // receivers could otherwise redirect the dispatcher to arbitrary urls
+      .redirect(reqwest::redirect::Policy::none())
+      // a proxy would resolve the host again
+      .no_proxy();
+    // the host is connected to at the checked addresses, so it can not resolve to another address in the meantime
+    if let Some(domain) = url.domain() {
+      client = client.resolve_to_addrs(domain, &addresses);
+    }
+
+    let response = client
+      .build()?
+      .post(url)
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .header("X-Einkaufsliste-Delivery", pending.delivery_id.to_string())
+      .header(
+        "X-Einkaufsliste-Signature",
+        sign_payload(&webhook.secret, &pending.payload),
+      )
+      .body(pending.payload.clone())
+      .send()
+      .await?;
+
+    Ok(response.status())
+  }
+}
+
+/// Hex encoded HMAC-SHA256 of the body, prefixed with the algorithm like `sha256=<hex>`
+pub fn sign_payload(secret: &str, payload: &str) -> String {
+  let mut mac =
+    Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+  mac.update(payload.as_bytes());
+
+  let signature: String = mac
+    .finalize()
+    .into_bytes()
+    .iter()
+    .map(|byte| format!("{byte:02x}"))
+    .collect();
+
+  format!("sha256={signature}")
+}
+
+/**
+Resolves the host of a webhook url. Fails if any of its addresses is not publicly routable, unless `allow_private` is
+set, as webhooks could otherwise be used to reach services on the host of the backend or within its network.
+*/
+pub async fn resolve_webhook_url(
+  url: &reqwest::Url,
+  allow_private: bool,
+) -> Result<Vec<SocketAddr>, String> {
+  let port = url
+    .port_or_known_default()
+    .ok_or("Webhook url has no port")?;
+  let addresses: Vec<SocketAddr> = match url.domain() {
+    Some(domain) => tokio::net::lookup_host((domain, port))
+      .await
+      .map_err(|e| format!("Could not resolve {domain}: {e}"))?
+      .collect(),
+    // hosts that are not domains are ip addresses, ipv6 addresses are enclosed in brackets
+    None => {
+      let ip: IpAddr = url
+        .host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+        .and_then(|host| host.parse().ok())
+        .ok_or("Webhook url has no host")?;
+      vec![SocketAddr::new(ip, port)]
+    }
+  };
+
+  if addresses.is_empty() {
+    return Err("Webhook host does not resolve to any address".to_owned());
+  }
+  if !allow_private && !addresses.iter().all(|address| is_public(address.ip())) {
+    return Err("Webhook host resolves to a private address".to_owned());
+  }
+
+  Ok(addresses)
+}
+
+/// Whether the address is publicly routable, i.e. not loopback, private, link-local, reserved or the like
+fn is_public(ip: IpAddr) -> bool {
+  match ip {
+    IpAddr::V4(ip) => {
+      let [a, b, ..] = ip.octets();
+      !(ip.is_unspecified() ||
+        ip.is_loopback() ||
+        ip.is_private() ||
+        ip.is_link_local() ||
+        ip.is_broadcast() ||
+        ip.is_documentation() ||
+        ip.is_multicast() ||
+        // "this network" 0.0.0.0/8, shared address space 100.64.0.0/10 and reserved 240.0.0.0/4
+        a == 0 ||
+        (a == 100 && (b & 0xc0) == 64) ||
+        a >= 240)
+    }
+    IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+      Some(ip) => is_public(IpAddr::V4(ip)),
+      None => {
+        let first = ip.segments()[0];
+        !(ip.is_unspecified() ||
+          ip.is_loopback() ||
+          ip.is_multicast() ||
+          // unique local fc00::/7 and link-local fe80::/10
+          (first & 0xfe00) == 0xfc00 ||
+          (first & 0xffc0) == 0xfe80)
+      }
+    },
+  }
+}
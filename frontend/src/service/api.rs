@@ -6,10 +6,27 @@ use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 
 use bytes::Bytes;
+use einkaufsliste::model::activity::{ActivityEvent, ActivityQuery};
+use einkaufsliste::model::article::Article;
+use einkaufsliste::model::batch::{BatchResult, BatchV1};
+use einkaufsliste::model::category::Category;
+use einkaufsliste::model::household::Household;
+use einkaufsliste::model::invite::InviteCode;
 use einkaufsliste::model::item::Item;
 use einkaufsliste::model::list::{FlatItemsList, List};
-use einkaufsliste::model::requests::{DeleteItem, LoginUserV1, RegisterUserV1, StoreItemAttached};
-use einkaufsliste::model::user::User;
+use einkaufsliste::model::patch::{ArticlePatchV1, ItemPatchV1, ListPatchV1, ShopPatchV1};
+use einkaufsliste::model::requests::{
+  CreateApiTokenV1, CreateCategoryV1, CreateHouseholdV1, CreateInviteV1, CreateShareLinkV1, CreateWebhookV1,
+  DeleteItem, LoginUserV1, MassStoreItems, MoveItemV1, OidcCallbackV1, QuickAddV1, RedeemInviteV1, RegisterUserV1,
+  ReorderItemV1, StoreItemAttached, TransferOwnershipV1, UpdateShopLayoutV1, VerifyTotpV1,
+};
+use einkaufsliste::model::share::{CreatedShareLink, ShareLink};
+use einkaufsliste::model::shop::{Shop, ShopOrder, ShopSection};
+use einkaufsliste::model::token::{ApiToken, CreatedApiToken};
+use einkaufsliste::model::totp::{RecoveryCodes, TotpEnrolment};
+use einkaufsliste::model::trash::TrashEntry;
+use einkaufsliste::model::user::{LoginResponseV1, OidcAuthorization, User};
+use einkaufsliste::model::webhook::{CreatedWebhook, Webhook, WebhookDelivery};
 use einkaufsliste::model::Identifiable;
 use einkaufsliste::{ApiObject, Encoding};
 use platform_dirs::AppDirs;
@@ -21,6 +38,8 @@ use rkyv::de::deserializers::SharedDeserializeMap;
 use rkyv::validation::validators::{CheckDeserializeError, DefaultValidator};
 use rkyv::CheckBytes;
 
+use super::batch::Batch;
+
 /*
  This file contains the API client and a reference counted Service for use in dioxus.
 
@@ -35,6 +54,9 @@ use rkyv::CheckBytes;
 pub static DEVELOPMENT_CERTIFICATE: &[u8] = include_bytes!("./rootCA.pem");
 
 static COOKIE_STORE_FILE_NAME: &str = "cookies.json";
+static IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+/// Number of times a request with an idempotency key is sent before the connection error is returned
+const IDEMPOTENT_ATTEMPTS: u32 = 3;
 // default configuration
 #[cfg(not(target_arch = "wasm32"))]
 lazy_static::lazy_static! {
@@ -249,17 +271,115 @@ impl ApiClient {
 
     response.error_for_status()?.bytes().await.map_err(Into::into)
   }
+
+  /**
+  Sends the request with a new `Idempotency-Key` and resends it with the same key if the connection fails,
+  so the server creates the object only once even if the response to an earlier attempt got lost.
+  */
+  async fn request_idempotent<T: ApiObject<'static>>(
+    &self,
+    url: &str,
+    method: reqwest::Method,
+    body: &T,
+  ) -> Result<Bytes, ApiError>
+  where
+    <T as rkyv::Archive>::Archived: rkyv::Deserialize<T, rkyv::de::deserializers::SharedDeserializeMap>,
+    <T as rkyv::Archive>::Archived: rkyv::CheckBytes<rkyv::validation::validators::DefaultValidator<'static>>,
+  {
+    let key = new_idempotency_key();
+    let body = self.encode(body)?;
+
+    let mut attempt = 1;
+    let response = loop {
+      let result = self
+        .client
+        .request(method.clone(), url)
+        .body(body.clone())
+        .headers(self.get_request_headers())
+        .header(IDEMPOTENCY_KEY, &key)
+        .send()
+        .await;
+
+      match result {
+        Err(e) if attempt < IDEMPOTENT_ATTEMPTS && (e.is_timeout() || e.is_connect() || e.is_request()) => {
+          tracing::debug!("Retrying request to {url} after: {e}");
+          attempt += 1;
+        }
+        result => break result?,
+      }
+    };
+
+    response.error_for_status()?.bytes().await.map_err(Into::into)
+  }
+  /// If the user has enrolled a second factor, the login has to be completed with [`Self::verify_totp`].
   #[tracing::instrument]
-  pub async fn login(&self, credentials: LoginUserV1) -> Result<User, ApiError> {
+  pub async fn login(&self, credentials: LoginUserV1) -> Result<LoginResponseV1, ApiError> {
     let url = format!("{}/login/v1", self.base_url);
 
     let response_body_bytes = self.request(&url, Method::POST, &credentials).await?;
 
-    let user = self.decode(&response_body_bytes)?;
+    let response = self.decode(&response_body_bytes)?;
+
+    Ok(response)
+  }
+
+  /// Completes a login that required a second factor. Accepts both TOTP and recovery codes.
+  #[tracing::instrument(skip_all)]
+  pub async fn verify_totp(&self, code: String) -> Result<User, ApiError> {
+    let url = format!("{}/login/totp/v1", self.base_url);
+
+    let body = self.request(&url, Method::POST, &VerifyTotpV1 { code }).await?;
+
+    let user = self.decode(&body)?;
 
     Ok(user)
   }
 
+  /// The user has to be sent to the returned url. The provider then redirects with the query for [`Self::complete_oidc_login`].
+  pub async fn start_oidc_login(&self) -> Result<OidcAuthorization, ApiError> {
+    let url = format!("{}/login/oidc/v1", self.base_url);
+
+    let body = self.request(&url, Method::POST, &()).await?;
+
+    self.decode(&body)
+  }
+
+  /// Links the identity to the current user instead if already logged in.
+  #[tracing::instrument(skip_all)]
+  pub async fn complete_oidc_login(&self, callback: OidcCallbackV1) -> Result<LoginResponseV1, ApiError> {
+    let url = format!("{}/login/oidc/callback/v1", self.base_url);
+
+    let body = self.request(&url, Method::POST, &callback).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn enrol_totp(&self) -> Result<TotpEnrolment, ApiError> {
+    let url = format!("{}/totp/enrol", self.base_url);
+
+    let body = self.request(&url, Method::POST, &()).await?;
+
+    self.decode(&body)
+  }
+
+  #[tracing::instrument(skip_all)]
+  pub async fn confirm_totp(&self, code: String) -> Result<RecoveryCodes, ApiError> {
+    let url = format!("{}/totp/confirm", self.base_url);
+
+    let body = self.request(&url, Method::POST, &VerifyTotpV1 { code }).await?;
+
+    self.decode(&body)
+  }
+
+  #[tracing::instrument(skip_all)]
+  pub async fn disable_totp(&self, code: String) -> Result<(), ApiError> {
+    let url = format!("{}/totp", self.base_url);
+
+    self.request(&url, Method::DELETE, &VerifyTotpV1 { code }).await?;
+
+    Ok(())
+  }
+
   #[tracing::instrument]
   pub async fn register(&self, credentials: RegisterUserV1) -> Result<User, ApiError> {
     let url = format!("{}/register/v1", self.base_url);
@@ -286,24 +406,117 @@ impl ApiClient {
   pub async fn create_list(&self, mut list: List) -> Result<List, ApiError> {
     let url = format!("{}/itemList", self.base_url);
 
-    let body = self.request(&url, Method::POST, &list).await?;
+    let body = self.request_idempotent(&url, Method::POST, &list).await?;
 
     list.id = self.decode(&body)?;
 
     Ok(list)
   }
 
+  pub async fn update_list(&self, list: List) -> Result<(), ApiError> {
+    let url = format!("{}/itemList", self.base_url);
+
+    self.request(&url, Method::PUT, &list).await?;
+
+    Ok(())
+  }
+
+  /// Changes only the fields set in the patch and returns the list afterwards
+  pub async fn patch_list(&self, list_id: <List as Identifiable>::Id, patch: ListPatchV1) -> Result<List, ApiError> {
+    let url = format!("{}/itemList/{}", self.base_url, list_id);
+
+    let body = self.request(&url, Method::PATCH, &patch).await?;
+
+    self.decode(&body)
+  }
+
   #[tracing::instrument(skip(self))]
   pub async fn new_item(&self, list_id: u64, item: Item) -> Result<u64, ApiError> {
     let url = format!("{}/item/attached", self.base_url);
 
     let body = self
-      .request(&url, Method::POST, &StoreItemAttached { list_id, item })
+      .request_idempotent(
+        &url,
+        Method::POST,
+        &StoreItemAttached {
+          list_id,
+          item,
+          merge: false,
+        },
+      )
       .await?;
 
     self.decode(&body)
   }
 
+  /**
+  Sends all commands of the batch in one request. The server applies either all of them or none,
+  and returns one result per command in the same order.
+  */
+  pub async fn execute_batch(&self, batch: Batch) -> Result<Vec<BatchResult>, ApiError> {
+    let url = format!("{}/batch", self.base_url);
+
+    let body = self
+      .request(
+        &url,
+        Method::POST,
+        &BatchV1 {
+          commands: batch.into_commands(),
+        },
+      )
+      .await?;
+
+    self.decode(&body)
+  }
+
+  /// Adds all items in one request and returns their ids in the same order
+  pub async fn new_items(&self, list_id: <List as Identifiable>::Id, items: Vec<Item>) -> Result<Vec<u64>, ApiError> {
+    let url = format!("{}/item/mass", self.base_url);
+
+    let body = self
+      .request(&url, Method::POST, &MassStoreItems { items, list_id })
+      .await?;
+
+    self.decode(&body)
+  }
+
+  /// Adds an item for each line of the text, e.g. `2kg Mehl`, and returns their ids
+  pub async fn quick_add(&self, list_id: <List as Identifiable>::Id, text: String) -> Result<Vec<u64>, ApiError> {
+    let url = format!("{}/itemList/{}/quickAdd", self.base_url, list_id);
+
+    let body = self
+      .request(&url, Method::POST, &QuickAddV1 { text, merge: false })
+      .await?;
+
+    self.decode(&body)
+  }
+
+  /// Merges unchecked duplicates in the list and returns the list afterwards
+  pub async fn merge_duplicates(&self, list_id: <List as Identifiable>::Id) -> Result<FlatItemsList, ApiError> {
+    let url = format!("{}/itemList/{}/merge", self.base_url, list_id);
+
+    let body = self.request(&url, Method::POST, &()).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn update_item(&self, item: Item) -> Result<(), ApiError> {
+    let url = format!("{}/item", self.base_url);
+
+    self.request(&url, Method::PUT, &item).await?;
+
+    Ok(())
+  }
+
+  /// Changes only the fields set in the patch and returns the item afterwards
+  pub async fn patch_item(&self, item_id: <Item as Identifiable>::Id, patch: ItemPatchV1) -> Result<Item, ApiError> {
+    let url = format!("{}/item/{}", self.base_url, item_id);
+
+    let body = self.request(&url, Method::PATCH, &patch).await?;
+
+    self.decode(&body)
+  }
+
   pub async fn delete_item(&self, command: DeleteItem) -> Result<(), ApiError> {
     let url = format!("{}/item", self.base_url);
 
@@ -312,6 +525,33 @@ impl ApiClient {
     Ok(())
   }
 
+  /// Moves the item to the position within its list, later items move down by one.
+  pub async fn reorder_item(
+    &self,
+    list_id: <List as Identifiable>::Id,
+    item_id: <Item as Identifiable>::Id,
+    position: u64,
+  ) -> Result<(), ApiError> {
+    let url = format!("{}/itemList/{}/item/{}/position", self.base_url, list_id, item_id);
+
+    self.request(&url, Method::PUT, &ReorderItemV1 { position }).await?;
+
+    Ok(())
+  }
+
+  pub async fn move_item(
+    &self,
+    list_id: <List as Identifiable>::Id,
+    item_id: <Item as Identifiable>::Id,
+    command: MoveItemV1,
+  ) -> Result<(), ApiError> {
+    let url = format!("{}/itemList/{}/item/{}/move", self.base_url, list_id, item_id);
+
+    self.request(&url, Method::POST, &command).await?;
+
+    Ok(())
+  }
+
   pub async fn fetch_list(&self, list_id: <List as Identifiable>::Id) -> Result<FlatItemsList, ApiError> {
     let url = format!("{}/itemList/{}/flat", self.base_url, list_id);
 
@@ -322,6 +562,354 @@ impl ApiClient {
     Ok(list)
   }
 
+  /// The items in the order the shop of the list is walked through, without category groups
+  pub async fn fetch_sorted_list(
+    &self,
+    list_id: <List as Identifiable>::Id,
+    order: ShopOrder,
+  ) -> Result<FlatItemsList, ApiError> {
+    let order = match order {
+      ShopOrder::Layout => "layout",
+      ShopOrder::Learned => "learned",
+    };
+    let url = format!("{}/itemList/{}/sorted?order={}", self.base_url, list_id, order);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn update_shop_layout(
+    &self,
+    shop_id: <Shop as Identifiable>::Id,
+    layout: Vec<ShopSection>,
+  ) -> Result<(), ApiError> {
+    let url = format!("{}/shop/{}/layout", self.base_url, shop_id);
+
+    self.request(&url, Method::PUT, &UpdateShopLayoutV1 { layout }).await?;
+
+    Ok(())
+  }
+
+  /// Changes only the fields set in the patch and returns the shop afterwards
+  pub async fn patch_shop(&self, shop_id: <Shop as Identifiable>::Id, patch: ShopPatchV1) -> Result<Shop, ApiError> {
+    let url = format!("{}/shop/{}", self.base_url, shop_id);
+
+    let body = self.request(&url, Method::PATCH, &patch).await?;
+
+    self.decode(&body)
+  }
+
+  /// Returns the id of the new article
+  pub async fn new_article(&self, article: Article) -> Result<u64, ApiError> {
+    let url = format!("{}/article", self.base_url);
+
+    let body = self.request_idempotent(&url, Method::POST, &article).await?;
+
+    self.decode(&body)
+  }
+
+  /// Changes only the fields set in the patch and returns the article afterwards
+  pub async fn patch_article(
+    &self,
+    article_id: <Article as Identifiable>::Id,
+    patch: ArticlePatchV1,
+  ) -> Result<Article, ApiError> {
+    let url = format!("{}/article/{}", self.base_url, article_id);
+
+    let body = self.request(&url, Method::PATCH, &patch).await?;
+
+    self.decode(&body)
+  }
+
+  /// Newest events first. Pass the id of the last event as `before` to fetch the next page.
+  pub async fn fetch_list_activity(
+    &self,
+    list_id: <List as Identifiable>::Id,
+    query: ActivityQuery,
+  ) -> Result<Vec<ActivityEvent>, ApiError> {
+    let mut url = format!("{}/itemList/{}/activity?", self.base_url, list_id);
+    if let Some(before) = query.before {
+      url.push_str(&format!("before={before}&"));
+    }
+    if let Some(limit) = query.limit {
+      url.push_str(&format!("limit={limit}"));
+    }
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  /// Hands the list over to the user with the given name. The current owner stays on as editor.
+  pub async fn transfer_list(&self, list_id: <List as Identifiable>::Id, new_owner: String) -> Result<(), ApiError> {
+    let url = format!("{}/itemList/{}/owner", self.base_url, list_id);
+
+    self
+      .request(&url, Method::PUT, &TransferOwnershipV1 { new_owner })
+      .await?;
+
+    Ok(())
+  }
+
+  /// Moves the list into the trash, from where it can be restored until the retention period passes.
+  pub async fn delete_list(&self, list_id: <List as Identifiable>::Id) -> Result<(), ApiError> {
+    let url = format!("{}/itemList/{}", self.base_url, list_id);
+
+    self.request(&url, Method::DELETE, &()).await?;
+
+    Ok(())
+  }
+
+  /// Deleted lists the user is allowed to restore.
+  pub async fn fetch_trashed_lists(&self) -> Result<Vec<TrashEntry>, ApiError> {
+    let url = format!("{}/trash", self.base_url);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn fetch_trashed_items(&self, list_id: <List as Identifiable>::Id) -> Result<Vec<TrashEntry>, ApiError> {
+    let url = format!("{}/itemList/{}/trash", self.base_url, list_id);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  /// Puts a deleted item back at its previous position, or a deleted list back among the users lists.
+  pub async fn restore_trash(&self, entry_id: <TrashEntry as Identifiable>::Id) -> Result<(), ApiError> {
+    let url = format!("{}/trash/{}/restore", self.base_url, entry_id);
+
+    self.request(&url, Method::POST, &()).await?;
+
+    Ok(())
+  }
+
+  pub async fn purge_trash(&self, entry_id: <TrashEntry as Identifiable>::Id) -> Result<(), ApiError> {
+    let url = format!("{}/trash/{}", self.base_url, entry_id);
+
+    self.request(&url, Method::DELETE, &()).await?;
+
+    Ok(())
+  }
+
+  /// The returned secret is only available once; it is the key of the HMAC-SHA256 signature of every payload.
+  pub async fn create_webhook(&self, request: CreateWebhookV1) -> Result<CreatedWebhook, ApiError> {
+    let url = format!("{}/webhook", self.base_url);
+
+    let body = self.request(&url, Method::POST, &request).await?;
+
+    self.decode(&body)
+  }
+
+  /// Webhooks notified about changes of any list of the user.
+  pub async fn fetch_webhooks(&self) -> Result<Vec<Webhook>, ApiError> {
+    let url = format!("{}/webhook", self.base_url);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn fetch_list_webhooks(&self, list_id: <List as Identifiable>::Id) -> Result<Vec<Webhook>, ApiError> {
+    let url = format!("{}/itemList/{}/webhook", self.base_url, list_id);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn delete_webhook(&self, webhook_id: <Webhook as Identifiable>::Id) -> Result<(), ApiError> {
+    let url = format!("{}/webhook/{}", self.base_url, webhook_id);
+
+    self.request(&url, Method::DELETE, &()).await?;
+
+    Ok(())
+  }
+
+  /// The latest delivery attempts of the webhook, newest first.
+  pub async fn fetch_webhook_deliveries(
+    &self,
+    webhook_id: <Webhook as Identifiable>::Id,
+  ) -> Result<Vec<WebhookDelivery>, ApiError> {
+    let url = format!("{}/webhook/{}/deliveries", self.base_url, webhook_id);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  /// The built-in categories followed by the custom ones of the user
+  pub async fn fetch_categories(&self) -> Result<Vec<Category>, ApiError> {
+    let url = format!("{}/category", self.base_url);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn create_category(&self, name: String) -> Result<Category, ApiError> {
+    let url = format!("{}/category", self.base_url);
+
+    let body = self.request(&url, Method::POST, &CreateCategoryV1 { name }).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn delete_category(&self, category_id: <Category as Identifiable>::Id) -> Result<(), ApiError> {
+    let url = format!("{}/category/{}", self.base_url, category_id);
+
+    self.request(&url, Method::DELETE, &()).await?;
+
+    Ok(())
+  }
+
+  /// Creates a single-use invite code, optionally granting access to a list or household.
+  pub async fn create_invite(&self, request: CreateInviteV1) -> Result<InviteCode, ApiError> {
+    let url = format!("{}/invite", self.base_url);
+
+    let body = self.request(&url, Method::POST, &request).await?;
+
+    self.decode(&body)
+  }
+
+  /// The returned token is only available once; the list can be opened without an account at `/share/<token>`.
+  pub async fn create_share_link(
+    &self,
+    list_id: <List as Identifiable>::Id,
+    request: CreateShareLinkV1,
+  ) -> Result<CreatedShareLink, ApiError> {
+    let url = format!("{}/itemList/{}/share", self.base_url, list_id);
+
+    let body = self.request(&url, Method::POST, &request).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn fetch_share_links(&self, list_id: <List as Identifiable>::Id) -> Result<Vec<ShareLink>, ApiError> {
+    let url = format!("{}/itemList/{}/share", self.base_url, list_id);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn revoke_share_link(
+    &self,
+    list_id: <List as Identifiable>::Id,
+    link_id: <ShareLink as Identifiable>::Id,
+  ) -> Result<(), ApiError> {
+    let url = format!("{}/itemList/{}/share/{}", self.base_url, list_id, link_id);
+
+    self.request(&url, Method::DELETE, &()).await?;
+
+    Ok(())
+  }
+
+  /// Fetches a list shared by link, which does not require being logged in.
+  pub async fn fetch_shared_list(&self, token: &str) -> Result<FlatItemsList, ApiError> {
+    let url = format!("{}/share/{}/flat", self.base_url, token);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  /// Accepts an invite to a list or household with an existing account.
+  pub async fn accept_invite(&self, code: String) -> Result<(), ApiError> {
+    let url = format!("{}/invite/redeem", self.base_url);
+
+    self.request(&url, Method::POST, &RedeemInviteV1 { code }).await?;
+
+    Ok(())
+  }
+
+  pub async fn create_household(&self, name: String) -> Result<Household, ApiError> {
+    let url = format!("{}/household", self.base_url);
+
+    let body = self.request(&url, Method::POST, &CreateHouseholdV1 { name }).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn fetch_households(&self) -> Result<Vec<Household>, ApiError> {
+    let url = format!("{}/household", self.base_url);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn leave_household(&self, household_id: <Household as Identifiable>::Id) -> Result<(), ApiError> {
+    let url = format!("{}/household/{}/member", self.base_url, household_id);
+
+    self.request(&url, Method::DELETE, &()).await?;
+
+    Ok(())
+  }
+
+  /// Moved lists are handed back to their owners, lists created in the household to one of its owners.
+  pub async fn dissolve_household(&self, household_id: <Household as Identifiable>::Id) -> Result<(), ApiError> {
+    let url = format!("{}/household/{}", self.base_url, household_id);
+
+    self.request(&url, Method::DELETE, &()).await?;
+
+    Ok(())
+  }
+
+  pub async fn create_household_list(
+    &self,
+    household_id: <Household as Identifiable>::Id,
+    mut list: List,
+  ) -> Result<List, ApiError> {
+    let url = format!("{}/household/{}/itemList", self.base_url, household_id);
+
+    let body = self.request(&url, Method::POST, &list).await?;
+
+    list.id = self.decode(&body)?;
+
+    Ok(list)
+  }
+
+  pub async fn move_list_to_household(
+    &self,
+    household_id: <Household as Identifiable>::Id,
+    list_id: <List as Identifiable>::Id,
+  ) -> Result<(), ApiError> {
+    let url = format!("{}/household/{}/itemList/{}", self.base_url, household_id, list_id);
+
+    self.request(&url, Method::PUT, &()).await?;
+
+    Ok(())
+  }
+
+  /// The returned secret is only available once and has to be sent as `Authorization: Bearer <secret>`.
+  pub async fn create_api_token(&self, request: CreateApiTokenV1) -> Result<CreatedApiToken, ApiError> {
+    let url = format!("{}/token", self.base_url);
+
+    let body = self.request(&url, Method::POST, &request).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn fetch_api_tokens(&self) -> Result<Vec<ApiToken>, ApiError> {
+    let url = format!("{}/token", self.base_url);
+
+    let body = self.request(&url, Method::GET, &()).await?;
+
+    self.decode(&body)
+  }
+
+  pub async fn revoke_api_token(&self, token_id: <ApiToken as Identifiable>::Id) -> Result<(), ApiError> {
+    let url = format!("{}/token/{}", self.base_url, token_id);
+
+    self.request(&url, Method::DELETE, &()).await?;
+
+    Ok(())
+  }
+
   pub fn get_img_url(&self, image_id: u64) -> String {
     format!("{}/image/{}", self.base_url, image_id)
   }
@@ -355,12 +943,21 @@ impl ApiClient {
   }
 }
 
+/// A random key identifying a request across its retries
+fn new_idempotency_key() -> String {
+  let mut bytes = [0u8; 16];
+  getrandom::getrandom(&mut bytes).expect("the operating system or browser to provide random numbers");
+
+  bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
 #[derive(Debug)]
 pub enum ApiError {
   Network(reqwest::Error),
   InternalServer,
   Unauthorized,
   Unauthenticated,
+  TooManyRequests,
   Encoding(String),
   Decoding(String),
   Unknown(String),
@@ -373,6 +970,7 @@ impl std::fmt::Display for ApiError {
       ApiError::InternalServer => write!(f, "An internal server error occured."),
       ApiError::Unauthorized => write!(f, "You are not authorized to access the requested resource."),
       ApiError::Unauthenticated => write!(f, "You must authenticate yourself to access the requested resource."),
+      ApiError::TooManyRequests => write!(f, "Too many requests. Please wait a moment and try again."),
       ApiError::Encoding(e) => write!(f, "An unexpected error occurred while encoding the request: {e}"),
       ApiError::Decoding(e) => write!(f, "An unexpected error occurred while decoding the response: {e}"),
       ApiError::Unknown(e) => write!(f, "Unknown error: {}", e),
@@ -395,6 +993,7 @@ impl From<reqwest::Error> for ApiError {
       Some(status) => match status.as_u16() {
         401 => ApiError::Unauthenticated,
         403 => ApiError::Unauthorized,
+        429 => ApiError::TooManyRequests,
         500 => ApiError::InternalServer,
         _ => ApiError::Unknown(format!("Unexpected status code: {} with message {e}", status,)),
       },
@@ -0,0 +1,71 @@
+use einkaufsliste::model::article::Article;
+use einkaufsliste::model::batch::{BatchCommand, BatchResult};
+use einkaufsliste::model::item::Item;
+use einkaufsliste::model::list::List;
+use einkaufsliste::model::requests::{DeleteItem, StoreItemAttached};
+use einkaufsliste::model::Identifiable;
+
+use super::api::{ApiClient, ApiError};
+
+/// Collects commands to send them to the server in a single request, see [`ApiClient::execute_batch`]
+#[derive(Debug, Default, Clone)]
+pub struct Batch {
+  commands: Vec<BatchCommand>,
+}
+
+impl Batch {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn new_item(mut self, list_id: <List as Identifiable>::Id, item: Item) -> Self {
+    self.commands.push(BatchCommand::StoreItem(StoreItemAttached {
+      item,
+      list_id,
+      merge: false,
+    }));
+    self
+  }
+
+  pub fn update_item(mut self, item: Item) -> Self {
+    self.commands.push(BatchCommand::UpdateItem(item));
+    self
+  }
+
+  pub fn delete_item(mut self, command: DeleteItem) -> Self {
+    self.commands.push(BatchCommand::DeleteItem(command));
+    self
+  }
+
+  pub fn update_list(mut self, list: List) -> Self {
+    self.commands.push(BatchCommand::UpdateList(list));
+    self
+  }
+
+  pub fn new_article(mut self, article: Article) -> Self {
+    self.commands.push(BatchCommand::StoreArticle(article));
+    self
+  }
+
+  pub fn update_article(mut self, article: Article) -> Self {
+    self.commands.push(BatchCommand::UpdateArticle(article));
+    self
+  }
+
+  pub fn len(&self) -> usize {
+    self.commands.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.commands.is_empty()
+  }
+
+  /// Shorthand for [`ApiClient::execute_batch`]
+  pub async fn send(self, client: &ApiClient) -> Result<Vec<BatchResult>, ApiError> {
+    client.execute_batch(self).await
+  }
+
+  pub(super) fn into_commands(self) -> Vec<BatchCommand> {
+    self.commands
+  }
+}
@@ -86,6 +86,8 @@ pub struct Toast {
   pub title: String,
   pub body: String,
   pub status: Status,
+  /// Shows an "Undo" button reverting the change with this id, see [`super::history::History`]
+  pub undo: Option<u64>,
 }
 
 /// A wrapper for displaying temporary messages to the user. Primarily used to show error messages.
@@ -104,19 +106,24 @@ where
     content: impl Into<Element<'a, Message>>,
     toasts: &'a [Toast],
     on_close: impl Fn(usize) -> Message + 'a,
+    on_undo: impl Fn(u64) -> Message + 'a,
   ) -> Self {
     let toasts = toasts
       .iter()
       .enumerate()
       .map(|(index, toast)| {
+        let header = row![text(toast.title.as_str()), horizontal_space(Length::Fill)];
+        let header = match toast.undo {
+          Some(change_id) => header.push(button("Undo").on_press((on_undo)(change_id)).padding(3)),
+          None => header,
+        };
+
         container(column![
           container(
-            row![
-              text(toast.title.as_str()),
-              horizontal_space(Length::Fill),
-              button("X").on_press((on_close)(index)).padding(3),
-            ]
-            .align_items(Alignment::Center)
+            header
+              .push(button("X").on_press((on_close)(index)).padding(3))
+              .spacing(3)
+              .align_items(Alignment::Center)
           )
           .width(Length::Fill)
           .padding(5)
@@ -0,0 +1,179 @@
+use einkaufsliste::model::item::Item;
+use einkaufsliste::model::list::List;
+use einkaufsliste::model::patch::ListPatchV1;
+use einkaufsliste::model::requests::DeleteItem;
+use einkaufsliste::model::Identifiable;
+
+use crate::service::api::{ApiError, ApiService};
+
+/// Number of changes that can be undone, older ones are dropped
+const HISTORY_LIMIT: usize = 50;
+
+/// A change to the data on the server, which can be replayed through the [`ApiService`]
+#[derive(Debug, Clone)]
+pub enum Operation {
+  AddItem {
+    list_id: <List as Identifiable>::Id,
+    item: Item,
+  },
+  /// Replaces the item, which also covers checking it
+  UpdateItem {
+    list_id: <List as Identifiable>::Id,
+    item: Item,
+  },
+  /// Moves the item into the trash
+  DeleteItem {
+    list_id: <List as Identifiable>::Id,
+    item_id: <Item as Identifiable>::Id,
+  },
+  /// Takes the item back out of the trash
+  RestoreItem {
+    list_id: <List as Identifiable>::Id,
+    item_id: <Item as Identifiable>::Id,
+  },
+  RenameList {
+    list_id: <List as Identifiable>::Id,
+    name: String,
+  },
+}
+
+impl Operation {
+  /**
+  Sends the operation to the server and returns the operation that repeats it.
+  This is the operation itself, except for new items, which are restored from the trash rather than created again
+  so that the id stays the same.
+  */
+  pub async fn apply(self, api_service: ApiService) -> Result<Operation, ApiError> {
+    match self {
+      Operation::AddItem { list_id, item } => {
+        let item_id = api_service.new_item(list_id, item).await?;
+
+        Ok(Operation::RestoreItem { list_id, item_id })
+      }
+      Operation::UpdateItem { list_id, item } => {
+        api_service.update_item(item.clone()).await?;
+
+        Ok(Operation::UpdateItem { list_id, item })
+      }
+      Operation::DeleteItem { list_id, item_id } => {
+        api_service.delete_item(DeleteItem { list_id, item_id }).await?;
+
+        Ok(Operation::DeleteItem { list_id, item_id })
+      }
+      Operation::RestoreItem { list_id, item_id } => {
+        // trash entries share the id of the deleted object
+        api_service.restore_trash(item_id).await?;
+
+        Ok(Operation::RestoreItem { list_id, item_id })
+      }
+      Operation::RenameList { list_id, name } => {
+        // only the name is sent, so items added in the meantime are not lost
+        let patch = ListPatchV1 {
+          name: Some(name.clone()),
+          ..Default::default()
+        };
+        api_service.patch_list(list_id, patch).await?;
+
+        Ok(Operation::RenameList { list_id, name })
+      }
+    }
+  }
+
+  /// The inverse of operations that do not depend on the previous state of the object
+  pub fn inverse(&self) -> Option<Operation> {
+    match *self {
+      Operation::DeleteItem { list_id, item_id } => Some(Operation::RestoreItem { list_id, item_id }),
+      Operation::RestoreItem { list_id, item_id } => Some(Operation::DeleteItem { list_id, item_id }),
+      _ => None,
+    }
+  }
+
+  /// Short description for the toast shown after the operation was performed
+  pub fn describe(&self) -> String {
+    match self {
+      Operation::AddItem { item, .. } => format!("Added {}", item.name),
+      Operation::UpdateItem { item, .. } => format!("Changed {}", item.name),
+      Operation::DeleteItem { .. } => "Deleted item".to_owned(),
+      Operation::RestoreItem { .. } => "Restored item".to_owned(),
+      Operation::RenameList { name, .. } => format!("Renamed list to {}", name),
+    }
+  }
+}
+
+/// An applied operation along with the operation reverting it
+#[derive(Debug, Clone)]
+pub struct Change {
+  /// Identifies the change for the undo button of its toast
+  pub id: u64,
+  pub description: String,
+  pub undo: Operation,
+  pub redo: Operation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  Undo,
+  Redo,
+}
+
+/// Undo and redo stacks of the changes made in this session
+#[derive(Debug, Default)]
+pub struct History {
+  undo: Vec<Change>,
+  redo: Vec<Change>,
+  next_id: u64,
+  /// Changes are replayed one at a time, so they reach the server in order
+  replaying: bool,
+}
+
+impl History {
+  /// Records a new change, which makes the undone changes unreachable
+  pub fn record(&mut self, description: String, undo: Operation, redo: Operation) -> u64 {
+    let id = self.next_id;
+    self.next_id += 1;
+
+    self.redo.clear();
+    self.undo.push(Change {
+      id,
+      description,
+      undo,
+      redo,
+    });
+    if self.undo.len() > HISTORY_LIMIT {
+      self.undo.remove(0);
+    }
+
+    id
+  }
+
+  /// Takes the next change to replay in the given direction, unless another one is still being replayed
+  pub fn start_replay(&mut self, direction: Direction) -> Option<Change> {
+    if self.replaying {
+      return None;
+    }
+
+    let change = match direction {
+      Direction::Undo => self.undo.pop(),
+      Direction::Redo => self.redo.pop(),
+    };
+    self.replaying = change.is_some();
+
+    change
+  }
+
+  /// Moves the change onto the opposite stack, or drops it if replaying it failed
+  pub fn finish_replay(&mut self, direction: Direction, change: Option<Change>) {
+    self.replaying = false;
+
+    match (direction, change) {
+      (Direction::Undo, Some(change)) => self.redo.push(change),
+      (Direction::Redo, Some(change)) => self.undo.push(change),
+      (_, None) => {}
+    }
+  }
+
+  /// The id of the change the next undo reverts
+  pub fn latest(&self) -> Option<u64> {
+    self.undo.last().map(|change| change.id)
+  }
+}
@@ -0,0 +1,437 @@
+use einkaufsliste::model::item::Item;
+use einkaufsliste::model::list::{FlatItemsList, List};
+use einkaufsliste::model::quick_add::parse_item;
+use einkaufsliste::model::shop::ShopOrder;
+use einkaufsliste::model::Identifiable;
+use iced::widget::{button, container, mouse_area, scrollable, text, text_input, Column, Row};
+use iced::{theme, Command, Element, Length};
+
+use super::error::GuiMessage;
+use super::history::Operation;
+use super::styles::DEFAULT_TEXT_SIZE;
+use super::MainMessage;
+use crate::service::api::ApiService;
+
+/// Shows the items of a single list, which can be reordered by dragging them by their handle
+pub(crate) struct ListView {
+  api_service: ApiService,
+  list: Option<FlatItemsList>,
+  /// The item currently being dragged
+  dragging: Option<<Item as Identifiable>::Id>,
+  order: ListOrder,
+  /// Text of the quick-add input, e.g. `2kg Mehl`
+  quick_add: String,
+  /// The item being edited along with the text of its input, which is parsed like the quick-add input
+  editing: Option<(<Item as Identifiable>::Id, String)>,
+  /// The name being typed into the name input, `None` while it shows the name of the list
+  name: Option<String>,
+}
+
+/// The order the items are shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ListOrder {
+  /// The order of the list, grouped by category, which can be changed by dragging items
+  Manual,
+  /// The order the aisles of the shop of the list are walked through
+  Shop,
+  /// The order items were checked off in the shop of the list before
+  Learned,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum ListMessage {
+  Loaded(FlatItemsList),
+  DragStart(<Item as Identifiable>::Id),
+  /// The dragged item was released on top of the item with this id and takes its place
+  Drop(<Item as Identifiable>::Id),
+  DragCancel,
+  ReorderFailed(GuiMessage),
+  OrderChanged(ListOrder),
+  MergeDuplicates,
+  Merged,
+  QuickAddChanged(String),
+  QuickAdd,
+  ToggleChecked(<Item as Identifiable>::Id),
+  Delete(<Item as Identifiable>::Id),
+  EditStart(<Item as Identifiable>::Id),
+  EditChanged(String),
+  EditSubmit,
+  NameChanged(String),
+  Rename,
+}
+
+impl ListView {
+  pub fn new(api_service: ApiService) -> Self {
+    Self {
+      api_service,
+      list: None,
+      dragging: None,
+      order: ListOrder::Manual,
+      quick_add: String::new(),
+      editing: None,
+      name: None,
+    }
+  }
+
+  pub fn load(&self, list_id: <List as Identifiable>::Id) -> Command<MainMessage> {
+    let api_service = self.api_service.clone();
+    let order = self.order;
+
+    Command::perform(
+      async move {
+        match order {
+          ListOrder::Manual => api_service.fetch_list(list_id).await,
+          ListOrder::Shop => api_service.fetch_sorted_list(list_id, ShopOrder::Layout).await,
+          ListOrder::Learned => api_service.fetch_sorted_list(list_id, ShopOrder::Learned).await,
+        }
+      },
+      |result| match result {
+        Ok(list) => MainMessage::List(ListMessage::Loaded(list)),
+        Err(e) => MainMessage::Toast(e.into()),
+      },
+    )
+  }
+
+  pub fn update(&mut self, message: ListMessage) -> Command<MainMessage> {
+    match message {
+      ListMessage::Loaded(list) => {
+        self.list = Some(list);
+        self.dragging = None;
+        self.editing = None;
+        self.name = None;
+
+        Command::none()
+      }
+      ListMessage::DragStart(item_id) => {
+        self.dragging = Some(item_id);
+
+        Command::none()
+      }
+      ListMessage::Drop(target_id) => {
+        let (Some(item_id), Some(list)) = (self.dragging.take(), self.list.as_mut()) else {
+          return Command::none();
+        };
+        let (Some(from), Some(to)) = (
+          list.items.iter().position(|item| item.id == item_id),
+          list.items.iter().position(|item| item.id == target_id),
+        ) else {
+          return Command::none();
+        };
+        if from == to {
+          return Command::none();
+        }
+
+        // the server applies the same removal and insertion, so the order is shown right away
+        let item = list.items.remove(from);
+        list.items.insert(to, item);
+        // the groups are shown instead of the items, they keep the order of the list within each category
+        let items = &list.items;
+        for group in &mut list.groups {
+          group
+            .item_ids
+            .sort_by_key(|item_id| items.iter().position(|item| item.id == *item_id));
+        }
+
+        let api_service = self.api_service.clone();
+        let list_id = list.id;
+        Command::perform(
+          async move { api_service.reorder_item(list_id, item_id, to as u64).await },
+          |result| match result {
+            Ok(()) => MainMessage::None,
+            Err(e) => MainMessage::List(ListMessage::ReorderFailed(e.into())),
+          },
+        )
+      }
+      ListMessage::DragCancel => {
+        self.dragging = None;
+
+        Command::none()
+      }
+      ListMessage::ReorderFailed(e) => {
+        // the local order no longer matches the one on the server
+        let reload = match &self.list {
+          Some(list) => self.load(list.id),
+          None => Command::none(),
+        };
+
+        Command::batch([Command::perform(async move { e }, MainMessage::Toast), reload])
+      }
+      ListMessage::OrderChanged(order) => {
+        self.order = order;
+
+        match &self.list {
+          Some(list) => self.load(list.id),
+          None => Command::none(),
+        }
+      }
+      ListMessage::MergeDuplicates => {
+        let Some(list) = &self.list else {
+          return Command::none();
+        };
+
+        let api_service = self.api_service.clone();
+        let list_id = list.id;
+        Command::perform(
+          async move { api_service.merge_duplicates(list_id).await },
+          |result| match result {
+            // the merged list comes back in its own order, which may not be the one shown
+            Ok(_) => MainMessage::List(ListMessage::Merged),
+            Err(e) => MainMessage::Toast(e.into()),
+          },
+        )
+      }
+      ListMessage::Merged => match &self.list {
+        Some(list) => self.load(list.id),
+        None => Command::none(),
+      },
+      ListMessage::QuickAddChanged(text) => {
+        self.quick_add = text;
+
+        Command::none()
+      }
+      ListMessage::QuickAdd => {
+        let Some(list) = &self.list else {
+          return Command::none();
+        };
+        let item = parse_item(&self.quick_add);
+        if item.name.is_empty() {
+          return Command::none();
+        }
+        self.quick_add.clear();
+
+        // added through the history, so adding the item can be undone
+        perform(Operation::AddItem { list_id: list.id, item })
+      }
+      ListMessage::ToggleChecked(item_id) => {
+        let Some((list_id, item)) = self.item(item_id) else {
+          return Command::none();
+        };
+
+        perform(Operation::UpdateItem {
+          list_id,
+          item: Item {
+            checked: !item.checked,
+            ..item.clone()
+          },
+        })
+      }
+      ListMessage::Delete(item_id) => {
+        let Some(list) = &self.list else {
+          return Command::none();
+        };
+
+        perform(Operation::DeleteItem {
+          list_id: list.id,
+          item_id,
+        })
+      }
+      ListMessage::EditStart(item_id) => {
+        self.editing = self.item(item_id).map(|(_, item)| {
+          let text = match item.display_amount() {
+            Some(amount) => format!("{amount} {}", item.name),
+            None => item.name.clone(),
+          };
+          (item_id, text)
+        });
+
+        Command::none()
+      }
+      ListMessage::EditChanged(text) => {
+        if let Some((_, editing)) = &mut self.editing {
+          *editing = text;
+        }
+
+        Command::none()
+      }
+      ListMessage::EditSubmit => {
+        let Some((item_id, text)) = self.editing.take() else {
+          return Command::none();
+        };
+        let Some((list_id, item)) = self.item(item_id) else {
+          return Command::none();
+        };
+        let parsed = parse_item(&text);
+        if parsed.name.is_empty() {
+          return Command::none();
+        }
+
+        // only what can be typed is replaced, the article and category are kept
+        perform(Operation::UpdateItem {
+          list_id,
+          item: Item {
+            name: parsed.name,
+            amount: parsed.amount,
+            unit: parsed.unit,
+            ..item.clone()
+          },
+        })
+      }
+      ListMessage::NameChanged(name) => {
+        self.name = Some(name);
+
+        Command::none()
+      }
+      ListMessage::Rename => {
+        let (Some(list), Some(name)) = (&self.list, self.name.take()) else {
+          return Command::none();
+        };
+        let name = name.trim().to_owned();
+        if name.is_empty() || name == list.name {
+          return Command::none();
+        }
+
+        perform(Operation::RenameList { list_id: list.id, name })
+      }
+    }
+  }
+
+  /// The shown item with the id of its list
+  fn item(&self, item_id: <Item as Identifiable>::Id) -> Option<(<List as Identifiable>::Id, &Item)> {
+    let list = self.list.as_ref()?;
+
+    list
+      .items
+      .iter()
+      .find(|item| item.id == item_id)
+      .map(|item| (list.id, item))
+  }
+
+  pub fn view(&self) -> Element<ListMessage> {
+    let Some(list) = &self.list else {
+      return text("Loading...").into();
+    };
+
+    // lists loaded from older servers are not grouped
+    let rows: Vec<_> = match list.groups.is_empty() {
+      true => list.items.iter().map(|item| self.item_row(item)).collect(),
+      false => list
+        .groups
+        .iter()
+        .flat_map(|group| {
+          let name = match &group.category {
+            Some(category) => category.name.as_str(),
+            None => "Uncategorized",
+          };
+          let header: Element<ListMessage> = text(name).size(DEFAULT_TEXT_SIZE * 1.2).into();
+
+          std::iter::once(header).chain(
+            group
+              .item_ids
+              .iter()
+              .filter_map(|item_id| list.items.iter().find(|item| item.id == *item_id))
+              .map(|item| self.item_row(item)),
+          )
+        })
+        .collect(),
+    };
+
+    let name = text_input("List name", self.name.as_ref().unwrap_or(&list.name))
+      .on_input(ListMessage::NameChanged)
+      .on_submit(ListMessage::Rename)
+      .width(Length::Fill)
+      .padding(5);
+
+    let quick_add = text_input("Add an item, e.g. 2kg Mehl", &self.quick_add)
+      .on_input(ListMessage::QuickAddChanged)
+      .on_submit(ListMessage::QuickAdd)
+      .width(Length::Fill)
+      .padding(5);
+    // shows how the input is understood before the item is added
+    let parsed = parse_item(&self.quick_add);
+    let preview = match parsed.display_amount() {
+      Some(amount) => text(format!("Adds {amount} of {}", parsed.name)),
+      None => text(""),
+    };
+
+    let mut header = Row::new()
+      .push(
+        button("Merge duplicates")
+          .on_press(ListMessage::MergeDuplicates)
+          .padding(3),
+      )
+      .spacing(5.0);
+    // only lists in a shop can be shown in its order
+    if list.shop.is_some() {
+      let orders = [
+        ("List order", ListOrder::Manual),
+        ("Shop layout", ListOrder::Shop),
+        ("Learned order", ListOrder::Learned),
+      ];
+      for (label, order) in orders {
+        let button = button(label).padding(3);
+        header = match order == self.order {
+          true => header.push(button),
+          false => header.push(button.on_press(ListMessage::OrderChanged(order))),
+        };
+      }
+    }
+
+    // releasing the item anywhere else cancels dragging it
+    let items = mouse_area(scrollable(Column::with_children(rows).spacing(5.0).width(Length::Fill)))
+      .on_release(ListMessage::DragCancel);
+
+    Column::new()
+      .push(name)
+      .push(header)
+      .push(quick_add)
+      .push(preview)
+      .push(items)
+      .spacing(10.0)
+      .into()
+  }
+
+  fn item_row<'a>(&'a self, item: &'a Item) -> Element<'a, ListMessage> {
+    // positions are only meaningful in the order of the list itself
+    let handle = match self.order {
+      ListOrder::Manual => {
+        mouse_area(text("=").size(DEFAULT_TEXT_SIZE * 1.5)).on_press(ListMessage::DragStart(item.id))
+      }
+      _ => mouse_area(text(" ").size(DEFAULT_TEXT_SIZE * 1.5)),
+    };
+    let checked = if item.checked { "[x]" } else { "[ ]" };
+    let label: Element<ListMessage> = match &self.editing {
+      Some((item_id, input)) if *item_id == item.id => text_input("e.g. 2kg Mehl", input)
+        .on_input(ListMessage::EditChanged)
+        .on_submit(ListMessage::EditSubmit)
+        .width(Length::Fill)
+        .into(),
+      _ => button(text(match item.display_amount() {
+        Some(amount) => format!("{amount} {}", item.name),
+        None => item.name.clone(),
+      }))
+      .on_press(ListMessage::EditStart(item.id))
+      .style(theme::Button::Text)
+      .width(Length::Fill)
+      .into(),
+    };
+
+    let row = container(
+      Row::with_children(vec![
+        handle.into(),
+        button(text(checked))
+          .on_press(ListMessage::ToggleChecked(item.id))
+          .style(theme::Button::Text)
+          .into(),
+        label,
+        button(text("x"))
+          .on_press(ListMessage::Delete(item.id))
+          .style(theme::Button::Text)
+          .into(),
+      ])
+      .spacing(10.0),
+    )
+    .width(Length::Fill)
+    .padding(5.0)
+    .style(match self.dragging == Some(item.id) {
+      true => theme::Container::Box,
+      false => theme::Container::Transparent,
+    });
+
+    mouse_area(row).on_release(ListMessage::Drop(item.id)).into()
+  }
+}
+
+/// Sends the operation through the history, so it can be undone
+fn perform(operation: Operation) -> Command<MainMessage> {
+  Command::perform(async move { operation }, MainMessage::Perform)
+}
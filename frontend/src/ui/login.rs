@@ -1,4 +1,5 @@
 use einkaufsliste::model::requests::{LoginUserV1, RegisterUserV1};
+use einkaufsliste::model::user::LoginResponseV1;
 use iced::widget::{button, row, text, text_input};
 use iced::{Command, Element, Length, Padding};
 
@@ -8,6 +9,11 @@ use crate::service::api::ApiService;
 pub struct LoginView {
   username: String,
   password: String,
+  /// Only required for registering if the server is invite-only
+  invite_code: String,
+  /// Set once the password has been accepted, but the account is protected by a second factor
+  awaiting_totp: bool,
+  totp_code: String,
   api_service: ApiService,
 }
 
@@ -15,7 +21,11 @@ pub struct LoginView {
 pub enum LoginMessage {
   UsernameChanged(String),
   PasswordChanged(String),
+  InviteCodeChanged(String),
+  TotpCodeChanged(String),
   Login,
+  TotpRequired,
+  VerifyTotp,
   Register,
 }
 
@@ -24,6 +34,9 @@ impl LoginView {
     Self {
       username: String::new(),
       password: String::new(),
+      invite_code: String::new(),
+      awaiting_totp: false,
+      totp_code: String::new(),
       api_service,
     }
   }
@@ -38,6 +51,14 @@ impl LoginView {
         self.password = password;
         Command::none()
       }
+      LoginMessage::InviteCodeChanged(code) => {
+        self.invite_code = code;
+        Command::none()
+      }
+      LoginMessage::TotpCodeChanged(code) => {
+        self.totp_code = code;
+        Command::none()
+      }
       LoginMessage::Login => {
         let api_service = self.api_service.clone();
         let param = LoginUserV1 {
@@ -46,15 +67,33 @@ impl LoginView {
         };
 
         Command::perform(async move { api_service.login(param).await }, |result| match result {
-          Ok(user) => MainMessage::UserChanged(user),
+          Ok(LoginResponseV1::Authenticated(user)) => MainMessage::UserChanged(user),
+          Ok(LoginResponseV1::TotpRequired) => MainMessage::Login(LoginMessage::TotpRequired),
           Err(e) => MainMessage::Toast(e.into()),
         })
       }
+      LoginMessage::TotpRequired => {
+        self.awaiting_totp = true;
+        Command::none()
+      }
+      LoginMessage::VerifyTotp => {
+        let api_service = self.api_service.clone();
+        let code = std::mem::take(&mut self.totp_code);
+
+        Command::perform(
+          async move { api_service.verify_totp(code).await },
+          |result| match result {
+            Ok(user) => MainMessage::UserChanged(user),
+            Err(e) => MainMessage::Toast(e.into()),
+          },
+        )
+      }
       LoginMessage::Register => {
         let api_service = self.api_service.clone();
         let param = RegisterUserV1 {
           name: self.username.clone(),
           password: self.password.clone(),
+          invite_code: Some(self.invite_code.trim().to_owned()).filter(|code| !code.is_empty()),
         };
         Command::perform(
           async move { api_service.register(param).await },
@@ -68,6 +107,10 @@ impl LoginView {
   }
 
   pub fn view(&self) -> Element<LoginMessage> {
+    if self.awaiting_totp {
+      return self.totp_view();
+    }
+
     let username_input = text_input("Username", &self.username)
       .on_input(LoginMessage::UsernameChanged)
       .on_submit(LoginMessage::Login)
@@ -83,6 +126,13 @@ impl LoginView {
       .padding(5)
       .into();
 
+    let invite_code_input = text_input("Only required for registering", &self.invite_code)
+      .on_input(LoginMessage::InviteCodeChanged)
+      .on_submit(LoginMessage::Register)
+      .width(Length::Fill)
+      .padding(5)
+      .into();
+
     let login_button = button("Login").on_press(LoginMessage::Login).into();
     let register_button = button("Register").on_press(LoginMessage::Register).into();
 
@@ -91,8 +141,23 @@ impl LoginView {
       username_input,
       text("Password:").into(),
       password_input,
+      text("Invite code:").into(),
+      invite_code_input,
       row(vec![login_button, register_button]).into(),
     ])
     .into()
   }
+
+  fn totp_view(&self) -> Element<LoginMessage> {
+    let code_input = text_input("Code from your authenticator app or a recovery code", &self.totp_code)
+      .on_input(LoginMessage::TotpCodeChanged)
+      .on_submit(LoginMessage::VerifyTotp)
+      .width(Length::Fill)
+      .padding(5)
+      .into();
+
+    let verify_button = button("Verify").on_press(LoginMessage::VerifyTotp).into();
+
+    iced::widget::column(vec![text("Authentication code:").into(), code_input, verify_button]).into()
+  }
 }
@@ -7,13 +7,16 @@ use einkaufsliste::model::list::{FlatItemsList, List};
 use einkaufsliste::model::shop::Shop;
 use einkaufsliste::model::user::User;
 use einkaufsliste::model::Identifiable;
+use iced::keyboard::{self, KeyCode};
 use iced::widget::{text, Column};
-use iced::{font, Application, Command};
+use iced::{event, font, subscription, Application, Command, Event, Subscription};
 
 use self::error::{GuiMessage, Toast};
+use self::history::{Change, Direction, History, Operation};
 use crate::service::api::{ApiError, ApiService};
 
 pub mod error;
+pub mod history;
 pub mod home;
 pub mod list;
 pub mod login;
@@ -26,9 +29,11 @@ pub struct Einkaufsliste {
 
   login_view: login::LoginView,
   home_view: home::HomeView,
+  list_view: list::ListView,
   current_page: Page,
 
   toasts: Vec<error::Toast>,
+  history: History,
   // TODO: abstract these into a repository struct
   /// This vector contains the metadata of each list
   lists: Arc<Vec<List>>,
@@ -56,6 +61,74 @@ impl Einkaufsliste {
       Arc::get_mut_unchecked(&mut self.shops)
     }
   }
+
+  /// The operation reverting `operation`, based on the locally cached state before it is applied
+  fn inverse_of(&self, operation: &Operation) -> Option<Operation> {
+    match operation {
+      Operation::UpdateItem { list_id, item } => self
+        .items
+        .get(list_id)?
+        .iter()
+        .find(|cached| cached.id == item.id)
+        .map(|previous| Operation::UpdateItem {
+          list_id: *list_id,
+          item: previous.clone(),
+        }),
+      Operation::RenameList { list_id, .. } => {
+        self
+          .lists
+          .iter()
+          .find(|list| list.id == *list_id)
+          .map(|list| Operation::RenameList {
+            list_id: *list_id,
+            name: list.name.clone(),
+          })
+      }
+      _ => operation.inverse(),
+    }
+  }
+
+  /// Keeps the cache in line with the server, so the inverse of the next operation is based on the current state
+  fn apply_to_cache(&mut self, operation: &Operation) {
+    match operation {
+      Operation::UpdateItem { list_id, item } => {
+        if let Some(cached) = self
+          .items
+          .get_mut(list_id)
+          .and_then(|items| items.iter_mut().find(|cached| cached.id == item.id))
+        {
+          *cached = item.clone();
+        }
+      }
+      Operation::DeleteItem { list_id, item_id } => {
+        if let Some(items) = self.items.get_mut(list_id) {
+          items.retain(|cached| cached.id != *item_id);
+        }
+      }
+      Operation::RenameList { list_id, name } => {
+        if let Some(list) = self.borrow_lists_mut().iter_mut().find(|list| list.id == *list_id) {
+          list.name = name.clone();
+        }
+      }
+      // the item data is not known locally, it is fetched with the next refresh
+      Operation::AddItem { .. } | Operation::RestoreItem { .. } => {}
+    }
+  }
+
+  fn replay(&mut self, direction: Direction) -> Command<MainMessage> {
+    let Some(change) = self.history.start_replay(direction) else {
+      return Command::none();
+    };
+    let operation = match direction {
+      Direction::Undo => change.undo.clone(),
+      Direction::Redo => change.redo.clone(),
+    };
+
+    Command::perform(operation.apply(self.api_service.clone()), move |result| match result {
+      Ok(_) => MainMessage::Replayed(direction, change),
+      Err(e) => MainMessage::ReplayFailed(direction, e.into()),
+    })
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -83,10 +156,24 @@ pub enum MainMessage {
   PageChanged(Page),
   Toast(GuiMessage),
   CloseToast(usize),
+  /// Sends the operation to the server and records it in the history
+  Perform(Operation),
+  Performed {
+    description: String,
+    undo: Operation,
+    redo: Operation,
+  },
+  Undo,
+  Redo,
+  /// Undoes the change with the given id if it is the latest one, sent by the undo button of a toast
+  UndoChange(u64),
+  Replayed(Direction, Change),
+  ReplayFailed(Direction, GuiMessage),
 
   // pass-through messages
   Login(login::LoginMessage),
   Home(home::HomeMessage),
+  List(list::ListMessage),
 }
 
 impl Application for Einkaufsliste {
@@ -108,9 +195,11 @@ impl Application for Einkaufsliste {
       Einkaufsliste {
         home_view: home::HomeView::new(lists.clone(), shops.clone(), api_service.clone()),
         login_view: login::LoginView::new(api_service.clone()),
+        list_view: list::ListView::new(api_service.clone()),
         api_service,
         user: None,
         toasts: Vec::new(),
+        history: History::default(),
         lists,
         items: HashMap::new(),
         articles: HashMap::new(),
@@ -187,13 +276,17 @@ impl Application for Einkaufsliste {
             Err(e) => MainMessage::Toast(e.into()),
           })
         }
+        Page::List(id) => self.list_view.load(id),
         _ => Command::none(),
       },
       MainMessage::ListMetaChanged(_) => todo!(),
       MainMessage::PageChanged(page) => {
         self.current_page = page;
 
-        Command::none()
+        match self.current_page {
+          Page::List(_) => self.update(MainMessage::Refresh),
+          _ => Command::none(),
+        }
       }
       MainMessage::Toast(e) => {
         match e {
@@ -206,7 +299,8 @@ impl Application for Einkaufsliste {
               self.toasts.push(error::Toast {
                 title: "You are not authenticated".to_owned(),
                 body: e.to_string(),
-                status: error::Status::Primary
+                status: error::Status::Primary,
+                undo: None,
               });
 
               self.update(MainMessage::PageChanged(Page::Login))
@@ -215,7 +309,8 @@ impl Application for Einkaufsliste {
               self.toasts.push(error::Toast {
                 title: "Network error".to_owned(),
                 body: e.to_string(),
-                status: error::Status::Primary
+                status: error::Status::Primary,
+                undo: None,
               });
 
               Command::none()
@@ -224,7 +319,8 @@ impl Application for Einkaufsliste {
               self.toasts.push(error::Toast {
                 title: "Unknown error".to_owned(),
                 body: e.to_string(),
-                status: error::Status::Danger
+                status: error::Status::Danger,
+                undo: None,
               });
 
               Command::none()
@@ -243,6 +339,75 @@ impl Application for Einkaufsliste {
 
         Command::none()
       }
+      MainMessage::Perform(operation) => {
+        let previous = self.inverse_of(&operation);
+        let description = operation.describe();
+
+        Command::perform(operation.apply(self.api_service.clone()), move |result| match result {
+          // new items can only be reverted once their id is known
+          Ok(redo) => match previous.or_else(|| redo.inverse()) {
+            Some(undo) => MainMessage::Performed {
+              description,
+              undo,
+              redo,
+            },
+            None => MainMessage::Refresh,
+          },
+          Err(e) => MainMessage::Toast(e.into()),
+        })
+      }
+      MainMessage::Performed {
+        description,
+        undo,
+        redo,
+      } => {
+        self.apply_to_cache(&redo);
+        let change_id = self.history.record(description.clone(), undo, redo);
+
+        self.toasts.push(error::Toast {
+          title: description,
+          body: "Press Ctrl+Z to undo".to_owned(),
+          status: error::Status::Success,
+          undo: Some(change_id),
+        });
+
+        self.update(MainMessage::Refresh)
+      }
+      MainMessage::Undo => self.replay(Direction::Undo),
+      MainMessage::Redo => self.replay(Direction::Redo),
+      MainMessage::UndoChange(change_id) => {
+        if self.history.latest() == Some(change_id) {
+          return self.replay(Direction::Undo);
+        }
+
+        self.update(MainMessage::Toast(GuiMessage::Other(Toast {
+          title: "Cannot undo".to_owned(),
+          body: "Only the latest change can be undone".to_owned(),
+          status: error::Status::Secondary,
+          undo: None,
+        })))
+      }
+      MainMessage::Replayed(direction, change) => {
+        match direction {
+          Direction::Undo => {
+            self.apply_to_cache(&change.undo);
+            // the change cannot be undone twice
+            for toast in self.toasts.iter_mut().filter(|toast| toast.undo == Some(change.id)) {
+              toast.undo = None;
+            }
+          }
+          Direction::Redo => self.apply_to_cache(&change.redo),
+        }
+        self.history.finish_replay(direction, Some(change));
+
+        self.update(MainMessage::Refresh)
+      }
+      MainMessage::ReplayFailed(direction, e) => {
+        // the change is dropped, as the state on the server no longer matches it
+        self.history.finish_replay(direction, None);
+
+        self.update(MainMessage::Toast(e))
+      }
       MainMessage::Login(message) => {
         //noop
 
@@ -255,6 +420,15 @@ impl Application for Einkaufsliste {
         //passthrough
         self.home_view.update(message)
       }
+      MainMessage::List(message) => {
+        // changes made in the list view are reverted based on the cached items
+        if let list::ListMessage::Loaded(list) = &message {
+          self.items.insert(list.id, list.items.clone());
+        }
+
+        //passthrough
+        self.list_view.update(message)
+      }
       MainMessage::None => Command::none(),
     }
   }
@@ -265,7 +439,7 @@ impl Application for Einkaufsliste {
     let content = match self.current_page {
       Page::Home => self.home_view.view().map(MainMessage::Home),
       Page::Login => self.login_view.view().map(MainMessage::Login),
-      Page::List(id) => todo!(),
+      Page::List(_) => self.list_view.view().map(MainMessage::List),
       Page::Article(id) => todo!(),
       Page::Shop(id) => todo!(),
       Page::Settings => todo!(),
@@ -273,11 +447,41 @@ impl Application for Einkaufsliste {
 
     let full_page = full_page.push(content);
 
-    let toast_handler = error::Manager::new(full_page, &self.toasts, MainMessage::CloseToast);
+    let toast_handler = error::Manager::new(
+      full_page,
+      &self.toasts,
+      MainMessage::CloseToast,
+      MainMessage::UndoChange,
+    );
 
     toast_handler.into()
   }
 
+  fn subscription(&self) -> Subscription<Self::Message> {
+    subscription::events_with(|event, status| match (event, status) {
+      // text inputs handle these shortcuts themselves
+      (_, event::Status::Captured) => None,
+      (
+        Event::Keyboard(keyboard::Event::KeyPressed {
+          key_code: KeyCode::Z,
+          modifiers,
+        }),
+        _,
+      ) if modifiers.command() => match modifiers.shift() {
+        true => Some(MainMessage::Redo),
+        false => Some(MainMessage::Undo),
+      },
+      (
+        Event::Keyboard(keyboard::Event::KeyPressed {
+          key_code: KeyCode::Y,
+          modifiers,
+        }),
+        _,
+      ) if modifiers.command() => Some(MainMessage::Redo),
+      _ => None,
+    })
+  }
+
   fn theme(&self) -> Self::Theme {
     iced::Theme::Dark
   }
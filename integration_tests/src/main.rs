@@ -1,15 +1,26 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::process::Command;
 use std::rc::Rc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use einkaufsliste::model::activity::ActivityKind;
+use einkaufsliste::model::batch::BatchResult;
 use einkaufsliste::model::list::List;
-use einkaufsliste::model::requests::LoginUserV1;
+use einkaufsliste::model::patch::{ItemPatchV1, ListPatchV1};
+use einkaufsliste::model::requests::{CreateWebhookV1, DeleteItem, LoginUserV1};
+use einkaufsliste::model::unit::{Amount, Unit};
+use einkaufsliste::model::webhook::WebhookPayload;
 use einkaufsliste::Encoding;
-use frontend::service::api::{ApiClient, ClientConfig};
+use frontend::service::api::{ApiClient, ApiError, ClientConfig};
+use frontend::service::batch::Batch;
 use futures::future::join_all;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
 use tokio::task::{spawn_local, LocalSet};
 
 #[tokio::main]
@@ -90,6 +101,281 @@ async fn main() {
   let now = Instant::now();
   many_new_items(client.clone()).await;
   println!("Item Mass creation test with rkyv took {:?}", now.elapsed());
+
+  println!("Bulk item creation test...");
+  mass_store_items(client.clone()).await;
+  println!("Bulk item creation test was successful");
+
+  println!("Batch test...");
+  batch(client.clone()).await;
+  println!("Batch test was successful");
+
+  println!("Patch test...");
+  patch_item(client.clone()).await;
+  println!("Patch test was successful");
+
+  println!("Webhook delivery test...");
+  if webhook_delivery(client.clone()).await {
+    println!("Webhook delivery test was successful");
+  } else {
+    println!(
+      "Webhook delivery test was skipped: the backend rejects webhooks to loopback addresses, set \
+       `webhook_allow_private = true` to run it"
+    );
+  }
+}
+
+/// Creates several items in one request and checks that they are appended to the list in order
+pub async fn mass_store_items(client: Rc<ApiClient>) {
+  let list = new_test_list(&client, "Bulk creation test").await;
+
+  let names = ["Mehl", "Zucker", "Eier"];
+  let items = names.iter().map(|name| test_item(name)).collect();
+  let item_ids = client
+    .new_items(list.id, items)
+    .await
+    .expect("new_items to be successful");
+
+  let stored = client.fetch_list(list.id).await.expect("fetch_list to be successful");
+  assert_eq!(stored.items.iter().map(|item| item.id).collect::<Vec<_>>(), item_ids);
+  assert_eq!(
+    stored.items.iter().map(|item| item.name.as_str()).collect::<Vec<_>>(),
+    names
+  );
+}
+
+/// Adds an item and renames the list in one batch, then checks that a failing batch changes nothing
+pub async fn batch(client: Rc<ApiClient>) {
+  let list = new_test_list(&client, "Batch test").await;
+  let item = test_item("Batch item");
+
+  let results = Batch::new()
+    .new_item(list.id, item.clone())
+    .update_list(List {
+      name: "Renamed batch test".to_string(),
+      ..list.clone()
+    })
+    .send(&client)
+    .await
+    .expect("batch to be successful");
+  let [BatchResult::Created(item_id), BatchResult::Done] = results[..] else {
+    panic!("unexpected batch results {results:?}");
+  };
+
+  let stored = client.fetch_list(list.id).await.expect("fetch_list to be successful");
+  assert_eq!(stored.name, "Renamed batch test");
+  assert_eq!(
+    stored.items.iter().map(|item| item.id).collect::<Vec<_>>(),
+    vec![item_id]
+  );
+
+  // deleting the item a second time fails within the transaction, which rolls back the whole batch
+  let delete = DeleteItem {
+    list_id: list.id,
+    item_id,
+  };
+  Batch::new()
+    .new_item(list.id, item)
+    .delete_item(delete.clone())
+    .delete_item(delete)
+    .send(&client)
+    .await
+    .expect_err("batch to fail");
+  let stored = client.fetch_list(list.id).await.expect("fetch_list to be successful");
+  assert_eq!(stored.items.len(), 1);
+}
+
+/// Changes single fields of an item and checks that the other fields are kept
+pub async fn patch_item(client: Rc<ApiClient>) {
+  let list = new_test_list(&client, "Patch test").await;
+  let item = einkaufsliste::model::item::Item {
+    amount: Some(Amount::from(2)),
+    unit: Some(Unit::KiloGram),
+    ..test_item("Mehl")
+  };
+  let item_id = client.new_item(list.id, item).await.expect("new_item to be successful");
+
+  let patched = client
+    .patch_item(item_id, ItemPatchV1::checked(true))
+    .await
+    .expect("patch_item to be successful");
+  assert!(patched.checked);
+  assert_eq!(patched.name, "Mehl");
+  assert_eq!(patched.amount, Some(Amount::from(2)));
+
+  let patch = ItemPatchV1 {
+    name: Some("Weizenmehl".to_string()),
+    amount: Some(None),
+    ..Default::default()
+  };
+  let patched = client
+    .patch_item(item_id, patch)
+    .await
+    .expect("patch_item to be successful");
+  assert!(patched.checked);
+  assert_eq!(patched.name, "Weizenmehl");
+  assert_eq!(patched.amount, None);
+  assert_eq!(patched.unit, Some(Unit::KiloGram));
+
+  let patch = ListPatchV1 {
+    name: Some("Patched list".to_string()),
+    ..Default::default()
+  };
+  let patched = client
+    .patch_list(list.id, patch)
+    .await
+    .expect("patch_list to be successful");
+  assert_eq!(patched.name, "Patched list");
+  assert_eq!(patched.items, vec![item_id]);
+
+  // items can not be added or repeated through a patch
+  let patch = ListPatchV1 {
+    items: Some(vec![item_id, item_id]),
+    ..Default::default()
+  };
+  assert!(client.patch_list(list.id, patch).await.is_err());
+
+  let patch = ListPatchV1 {
+    items: Some(vec![]),
+    ..Default::default()
+  };
+  let patched = client
+    .patch_list(list.id, patch)
+    .await
+    .expect("patch_list to be successful");
+  assert!(patched.items.is_empty());
+  let trashed = client
+    .fetch_trashed_items(list.id)
+    .await
+    .expect("fetch_trashed_items to be successful");
+  assert!(trashed.iter().any(|entry| entry.id == item_id));
+}
+
+/**
+Registers a webhook pointing at a local receiver and checks the signed payload sent when adding an item.
+The backend has to be configured with `webhook_allow_private = true`, as webhooks to loopback addresses are rejected otherwise.
+Returns `false` without checking anything if the backend rejects the webhook for that reason.
+*/
+pub async fn webhook_delivery(client: Rc<ApiClient>) -> bool {
+  let receiver = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let address = receiver.local_addr().unwrap();
+
+  let list = new_test_list(&client, "Webhook test").await;
+  let webhook = match client
+    .create_webhook(CreateWebhookV1 {
+      url: format!("http://{address}/hook"),
+      list_id: Some(list.id),
+    })
+    .await
+  {
+    Ok(webhook) => webhook,
+    // private addresses are answered with 400 unless `webhook_allow_private` is set
+    Err(ApiError::Unknown(message)) if message.contains("400") => return false,
+    Err(e) => panic!("create_webhook to be successful: {e}"),
+  };
+
+  let item_id = client
+    .new_item(list.id, test_item("Webhook item"))
+    .await
+    .expect("create_item to be successful");
+
+  let (headers, body) = tokio::time::timeout(Duration::from_secs(10), receive_request(&receiver))
+    .await
+    .expect("webhook to be delivered within 10 seconds");
+
+  let mut mac = Hmac::<Sha256>::new_from_slice(webhook.secret.as_bytes()).unwrap();
+  mac.update(body.as_bytes());
+  let expected: String = mac
+    .finalize()
+    .into_bytes()
+    .iter()
+    .map(|byte| format!("{byte:02x}"))
+    .collect();
+  assert_eq!(
+    headers.get("x-einkaufsliste-signature"),
+    Some(&format!("sha256={expected}")),
+    "signature to match the payload"
+  );
+
+  let payload: WebhookPayload = serde_json::from_str(&body).expect("payload to be valid json");
+  assert_eq!(payload.webhook_id, webhook.hook.id);
+  assert_eq!(payload.list_id, list.id);
+  assert!(matches!(payload.event.kind, ActivityKind::ItemAdded { item_id: id, .. } if id == item_id));
+
+  client
+    .delete_webhook(webhook.hook.id)
+    .await
+    .expect("delete_webhook to be successful");
+
+  true
+}
+
+async fn new_test_list(client: &ApiClient, name: &str) -> List {
+  client
+    .create_list(List {
+      id: 0,
+      name: name.to_string(),
+      shop: None,
+      image_id: None,
+      items: vec![],
+    })
+    .await
+    .expect("create_list to be successful")
+}
+
+/// An unchecked item without amount or article
+fn test_item(name: &str) -> einkaufsliste::model::item::Item {
+  einkaufsliste::model::item::Item {
+    id: 0,
+    name: name.to_string(),
+    checked: false,
+    amount: None,
+    unit: None,
+    article_id: None,
+    alternative_article_ids: None,
+    category_id: None,
+  }
+}
+
+/// Accepts a single HTTP request and answers with 204, returns the lowercased headers and the body
+async fn receive_request(receiver: &TcpListener) -> (HashMap<String, String>, String) {
+  let (mut stream, _) = receiver.accept().await.unwrap();
+
+  let mut buffer = Vec::new();
+  let header_end = loop {
+    let mut chunk = [0u8; 1024];
+    let read = stream.read(&mut chunk).await.unwrap();
+    assert!(read > 0, "connection closed before the headers were complete");
+    buffer.extend_from_slice(&chunk[..read]);
+
+    if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+      break position + 4;
+    }
+  };
+
+  let headers: HashMap<String, String> = String::from_utf8_lossy(&buffer[..header_end])
+    .lines()
+    .skip(1)
+    .filter_map(|line| line.split_once(':'))
+    .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+    .collect();
+
+  let content_length: usize = headers["content-length"].parse().unwrap();
+  while buffer.len() < header_end + content_length {
+    let mut chunk = [0u8; 1024];
+    let read = stream.read(&mut chunk).await.unwrap();
+    assert!(read > 0, "connection closed before the body was complete");
+    buffer.extend_from_slice(&chunk[..read]);
+  }
+
+  stream
+    .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+    .await
+    .unwrap();
+
+  let body = String::from_utf8(buffer[header_end..header_end + content_length].to_vec()).unwrap();
+
+  (headers, body)
 }
 
 pub async fn many_new_items(client: Rc<ApiClient>) {
@@ -119,6 +405,7 @@ pub async fn many_new_items(client: Rc<ApiClient>) {
               unit: None,
               article_id: None,
               alternative_article_ids: None,
+              category_id: None,
             },
           )
           .await
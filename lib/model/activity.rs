@@ -0,0 +1,80 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::item::Item;
+use super::list::List;
+use super::user::User;
+use super::{Identifiable, Role};
+use crate::impl_api_traits;
+
+/// An entry of the append-only activity log of a list
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct ActivityEvent {
+  /// Increases with every event, used as cursor for paging through the log
+  pub id: u64,
+  /// Not set for changes made through a share link
+  pub actor_id: Option<<User as Identifiable>::Id>,
+  /// Name of the actor at the time of the change
+  pub actor_name: Option<String>,
+  /// Unix timestamp in seconds
+  pub timestamp: i64,
+  pub kind: ActivityKind,
+}
+
+impl_api_traits!(ActivityEvent);
+
+/// Item names are recorded along with the ids, as the items may be gone by the time the log is read.
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub enum ActivityKind {
+  ItemAdded {
+    item_id: <Item as Identifiable>::Id,
+    name: String,
+  },
+  ItemEdited {
+    item_id: <Item as Identifiable>::Id,
+    name: String,
+  },
+  ItemChecked {
+    item_id: <Item as Identifiable>::Id,
+    name: String,
+    checked: bool,
+  },
+  ItemDeleted {
+    item_id: <Item as Identifiable>::Id,
+    name: String,
+  },
+  ItemRestored {
+    item_id: <Item as Identifiable>::Id,
+    name: String,
+  },
+  /// Recorded in both lists
+  ItemMoved {
+    item_id: <Item as Identifiable>::Id,
+    name: String,
+    from_list_id: <List as Identifiable>::Id,
+    to_list_id: <List as Identifiable>::Id,
+  },
+  /// The merged items were moved into the trash after adding their amounts to the item
+  ItemsMerged {
+    item_id: <Item as Identifiable>::Id,
+    name: String,
+    merged_item_ids: Vec<<Item as Identifiable>::Id>,
+  },
+  ListRenamed {
+    from: String,
+    to: String,
+  },
+  MemberAdded {
+    user_id: <User as Identifiable>::Id,
+    role: Role,
+  },
+}
+
+/// Query parameters for paging through the activity log, newest events first
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, Clone)]
+pub struct ActivityQuery {
+  /// Only events with a smaller id are returned; pass the id of the last event of the previous page
+  pub before: Option<u64>,
+  pub limit: Option<usize>,
+}
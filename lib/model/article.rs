@@ -1,5 +1,6 @@
 use rkyv::{Archive, Deserialize, Serialize};
 
+use super::category::Category;
 use super::shop::Shop;
 use super::Identifiable;
 use crate::impl_api_traits;
@@ -12,6 +13,8 @@ pub struct Article {
   pub description: Option<String>,
   pub image_id: Option<u32>,
   pub shops: Option<Vec<<Shop as Identifiable>::Id>>,
+  /// Inherited by items of this article that do not have a category of their own
+  pub category_id: Option<<Category as Identifiable>::Id>,
 }
 
 impl Identifiable for Article {
@@ -0,0 +1,44 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::article::Article;
+use super::item::Item;
+use super::list::List;
+use super::requests::{DeleteItem, StoreItemAttached};
+use crate::impl_api_traits;
+
+/// A command of a batch, taking the same parameters as the endpoint it stands for
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub enum BatchCommand {
+  /// `POST /item/attached`, merging duplicates is not supported within batches
+  StoreItem(StoreItemAttached),
+  /// `PUT /item`
+  UpdateItem(Item),
+  /// `DELETE /item`
+  DeleteItem(DeleteItem),
+  /// `PUT /itemList`, except that the items are only taken over if they are a reordering of the current ones.
+  /// Otherwise the current items are kept, including the ones added earlier in the batch.
+  UpdateList(List),
+  /// `POST /article`
+  StoreArticle(Article),
+  /// `PUT /article`
+  UpdateArticle(Article),
+}
+
+/// Commands executed in order and atomically: either all of them are applied or none
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct BatchV1 {
+  pub commands: Vec<BatchCommand>,
+}
+impl_api_traits!(BatchV1);
+
+/// The result of a single command of a batch
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub enum BatchResult {
+  /// The id of the created item or article
+  Created(u64),
+  Done,
+}
+impl_api_traits!(BatchResult);
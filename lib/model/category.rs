@@ -0,0 +1,71 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::item::Item;
+use super::user::User;
+use super::{HasTypeDenominator, Identifiable};
+use crate::impl_api_traits;
+
+/// Ids of the built-in categories start here, far beyond the ids generated by the database
+pub const BUILTIN_CATEGORY_BASE: u64 = 1 << 63;
+
+const BUILTIN_CATEGORIES: [&str; 10] = [
+  "Produce",
+  "Bakery",
+  "Dairy",
+  "Meat & Fish",
+  "Frozen",
+  "Pantry",
+  "Beverages",
+  "Household",
+  "Personal care",
+  "Other",
+];
+
+/// Groups items of a list, either one of the built-in categories or a custom one of a user
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct Category {
+  pub id: <Category as Identifiable>::Id,
+  pub name: String,
+  /// `None` for built-in categories
+  pub owner: Option<<User as Identifiable>::Id>,
+}
+
+impl_api_traits!(Category);
+
+impl Identifiable for Category {
+  type Id = u64;
+}
+
+unsafe impl HasTypeDenominator for Category {
+  const DENOMINATOR: u64 = 6;
+}
+
+impl Category {
+  /// The categories available to every user, in the order they are displayed in
+  pub fn builtin() -> Vec<Category> {
+    BUILTIN_CATEGORIES
+      .iter()
+      .zip(BUILTIN_CATEGORY_BASE..)
+      .map(|(name, id)| Category {
+        id,
+        name: name.to_string(),
+        owner: None,
+      })
+      .collect()
+  }
+
+  pub fn is_builtin(id: <Category as Identifiable>::Id) -> bool {
+    (BUILTIN_CATEGORY_BASE..BUILTIN_CATEGORY_BASE + BUILTIN_CATEGORIES.len() as u64).contains(&id)
+  }
+}
+
+/// Items of a list sharing the same category
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CategoryGroup {
+  /// `None` for items without a category
+  pub category: Option<Category>,
+  /// In the order of the list
+  pub item_ids: Vec<<Item as Identifiable>::Id>,
+}
@@ -0,0 +1,34 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::user::User;
+use super::{HasTypeDenominator, Identifiable, Member, Role};
+use crate::impl_api_traits;
+
+/// A group of users sharing all lists owned by the household. The role of a member applies to every one of these lists.
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct Household {
+  pub id: <Household as Identifiable>::Id,
+  pub name: String,
+  pub members: Vec<Member<User>>,
+}
+
+impl_api_traits!(Household);
+
+impl Identifiable for Household {
+  type Id = u64;
+}
+
+unsafe impl HasTypeDenominator for Household {
+  const DENOMINATOR: u64 = 2;
+}
+
+impl Household {
+  pub fn role_of(&self, user_id: <User as Identifiable>::Id) -> Option<Role> {
+    self
+      .members
+      .iter()
+      .find(|member| member.user_id == user_id)
+      .map(|member| member.role)
+  }
+}
@@ -0,0 +1,35 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::household::Household;
+use super::list::List;
+use super::user::User;
+use super::{Identifiable, Role};
+use crate::impl_api_traits;
+
+/// A single-use invite as stored by the backend, keyed by the SHA-256 digest of its code
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct Invite {
+  pub created_by: <User as Identifiable>::Id,
+  /// Unix timestamp in seconds after which the code is rejected
+  pub expires: i64,
+  /// List the invited user is granted access to on signup
+  pub list_id: Option<<List as Identifiable>::Id>,
+  /// Household the invited user joins on signup
+  pub household_id: Option<<Household as Identifiable>::Id>,
+  /// Role the invited user is granted on the list and in the household
+  pub role: Role,
+}
+
+impl_api_traits!(Invite);
+
+/// Returned once when creating an invite; the code is to be passed along in [`super::requests::RegisterUserV1`] or [`super::requests::RedeemInviteV1`].
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct InviteCode {
+  pub code: String,
+  /// Unix timestamp in seconds
+  pub expires: i64,
+}
+
+impl_api_traits!(InviteCode);
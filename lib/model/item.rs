@@ -1,6 +1,8 @@
 use rkyv::{Archive, Deserialize, Serialize};
 
 use super::article::Article;
+use super::category::Category;
+use super::unit::{format_amount, Amount, Unit};
 use super::Identifiable;
 use crate::impl_api_traits;
 
@@ -10,26 +12,64 @@ pub struct Item {
   pub id: <Item as Identifiable>::Id,
   pub checked: bool,
   pub name: String,
-  pub amount: Option<u64>,
+  pub amount: Option<Amount>,
   pub unit: Option<Unit>,
   pub article_id: Option<<Article as Identifiable>::Id>,
   pub alternative_article_ids: Option<Vec<<Article as Identifiable>::Id>>,
+  /// Overrides the category of the article
+  pub category_id: Option<<Category as Identifiable>::Id>,
 }
 impl_api_traits!(Item);
 
-#[derive(PartialEq, Eq, Archive, Serialize, Deserialize, Clone, Debug, serde::Serialize, serde::Deserialize)]
-#[archive_attr(derive(bytecheck::CheckBytes))]
-pub enum Unit {
-  Gram,
-  KiloGram,
-  Pieces,
-  FreeForm(String),
-}
-
 impl Identifiable for Item {
   type Id = u64;
 }
 
+impl Item {
+  /// The amount along with its unit for display, e.g. `1.5 kg`
+  pub fn display_amount(&self) -> Option<String> {
+    format_amount(self.amount, self.unit.as_ref())
+  }
+
+  /// Whether both items are for the same thing, i.e. they have the same article or, without articles, the same name
+  pub fn is_duplicate_of(&self, other: &Item) -> bool {
+    match (self.article_id, other.article_id) {
+      (Some(a), Some(b)) => a == b,
+      (None, None) => normalize_name(&self.name) == normalize_name(&other.name),
+      _ => false,
+    }
+  }
+
+  /**
+  Adds the amount of the other item to this one, converted into the unit of this item.
+  Items without a unit count pieces. Returns `false` and leaves the item unchanged if the amounts can not be added,
+  e.g. because only one of the items has an amount or the units are incompatible.
+  */
+  pub fn merge(&mut self, other: &Item) -> bool {
+    let unit = self.unit.clone().unwrap_or(Unit::Pieces);
+    let other_unit = other.unit.clone().unwrap_or(Unit::Pieces);
+
+    let amount = match (self.amount, other.amount) {
+      (None, None) if unit == other_unit => None,
+      (Some(amount), Some(other_amount)) => {
+        let Some(sum) = other_unit
+          .convert(other_amount, &unit)
+          .and_then(|other_amount| amount.checked_add(other_amount))
+        else {
+          return false;
+        };
+        Some(sum)
+      }
+      _ => return false,
+    };
+
+    self.amount = amount;
+    self.category_id = self.category_id.or(other.category_id);
+
+    true
+  }
+}
+
 impl PartialEq for Item {
   fn eq(&self, other: &Self) -> bool {
     self.id == other.id &&
@@ -37,6 +77,12 @@ impl PartialEq for Item {
       self.amount == other.amount &&
       self.unit == other.unit &&
       self.article_id == other.article_id &&
-      self.alternative_article_ids == other.alternative_article_ids
+      self.alternative_article_ids == other.alternative_article_ids &&
+      self.category_id == other.category_id
   }
 }
+
+/// Lower case with single spaces, so names only differing in case or spacing are considered the same
+fn normalize_name(name: &str) -> String {
+  name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
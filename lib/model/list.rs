@@ -1,5 +1,6 @@
 use rkyv::{Archive, Deserialize, Serialize};
 
+use super::category::CategoryGroup;
 use super::item::Item;
 use super::shop::Shop;
 use super::{HasTypeDenominator, Identifiable};
@@ -49,6 +50,9 @@ pub struct FlatItemsList {
   pub shop: Option<<Shop as Identifiable>::Id>,
   pub image_id: Option<u64>,
   pub items: Vec<Item>,
+  /// The items grouped by their category, filled in by the backend
+  #[serde(default)]
+  pub groups: Vec<CategoryGroup>,
 }
 
 impl Eq for FlatItemsList {}
@@ -63,6 +67,7 @@ impl FlatItemsList {
       shop: list.shop,
       image_id: list.image_id,
       items: vec,
+      groups: vec![],
     }
   }
 
@@ -2,13 +2,26 @@ use rkyv::de::deserializers::SharedDeserializeMap;
 use rkyv::{Archive, Deserialize, Serialize};
 use zerocopy::AsBytes;
 
+pub mod activity;
 pub mod article;
+pub mod batch;
+pub mod category;
+pub mod household;
+pub mod invite;
 pub mod item;
 pub mod list;
+pub mod patch;
+pub mod quick_add;
 pub mod requests;
 pub mod session;
+pub mod share;
 pub mod shop;
+pub mod token;
+pub mod totp;
+pub mod trash;
+pub mod unit;
 pub mod user;
+pub mod webhook;
 
 /**
 # Safety
@@ -31,5 +44,86 @@ pub trait Identifiable {
 pub struct AccessControlList<Object: Identifiable, User: Identifiable> {
   pub object_id: Object::Id,
   pub owner: User::Id,
-  pub allowed_user_ids: Vec<User::Id>,
+  pub members: Vec<Member<User>>,
+  /// Members of the household get access according to their role in the household
+  pub household: Option<<household::Household as Identifiable>::Id>,
+}
+
+impl<Object: Identifiable, User: Identifiable> AccessControlList<Object, User> {
+  /// The owner always has the [`Role::Owner`], everybody else only the role they were granted as member
+  pub fn role_of(&self, user_id: &User::Id) -> Option<Role> {
+    if self.owner == *user_id {
+      return Some(Role::Owner);
+    }
+
+    self
+      .members
+      .iter()
+      .find(|member| member.user_id == *user_id)
+      .map(|member| member.role)
+  }
+
+  pub fn permits(&self, user_id: &User::Id, permission: Permission) -> bool {
+    self.role_of(user_id).is_some_and(|role| role.permits(permission))
+  }
+
+  /// The previous owner stays on as member with the given role
+  pub fn transfer_ownership(&mut self, new_owner: User::Id, previous_owner_role: Role) {
+    if self.owner == new_owner {
+      return;
+    }
+
+    self.members.retain(|member| member.user_id != new_owner);
+
+    let previous_owner = std::mem::replace(&mut self.owner, new_owner);
+    self.members.push(Member {
+      user_id: previous_owner,
+      role: previous_owner_role,
+    });
+  }
+}
+
+#[derive(Archive, Serialize, Deserialize, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct Member<User: Identifiable> {
+  pub user_id: User::Id,
+  pub role: Role,
+}
+
+/// Roles of the members of a shared object. Every role includes all permissions of the roles before it.
+#[derive(Archive, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+// the order of the variants defines which role includes which
+#[derive(PartialOrd, Ord)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub enum Role {
+  /// May only read
+  Viewer,
+  /// May additionally check and uncheck items, e.g. while shopping
+  Shopper,
+  /// May additionally add, modify and remove items and change the metadata
+  Editor,
+  /// May additionally share, transfer and delete the object
+  Owner,
+}
+
+impl Role {
+  pub fn permits(self, permission: Permission) -> bool {
+    let required = match permission {
+      Permission::Read => Role::Viewer,
+      Permission::Check => Role::Shopper,
+      Permission::Write => Role::Editor,
+      Permission::Manage => Role::Owner,
+    };
+
+    self >= required
+  }
+}
+
+/// Permission required for an operation on a shared object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+  Read,
+  Check,
+  Write,
+  Manage,
 }
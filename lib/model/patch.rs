@@ -0,0 +1,157 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::article::Article;
+use super::category::Category;
+use super::item::Item;
+use super::list::List;
+use super::shop::{Shop, ShopSection};
+use super::unit::{Amount, Unit};
+use super::Identifiable;
+use crate::impl_api_traits;
+
+/*
+Patches only carry the fields that are changed, every other field of the object is kept as it is stored.
+Through serde a missing field leaves the value unchanged, while `null` clears an optional value.
+*/
+
+/// Distinguishes a `null` field, which becomes `Some(None)`, from a missing one, which is `None` through `default`
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+  T: serde::Deserialize<'de>,
+  D: serde::Deserializer<'de>,
+{
+  <Option<T> as serde::Deserialize>::deserialize(deserializer).map(Some)
+}
+
+fn apply<T: Clone>(field: &mut T, value: &Option<T>) {
+  if let Some(value) = value {
+    *field = value.clone();
+  }
+}
+
+#[derive(Debug, Default, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct ItemPatchV1 {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub checked: Option<bool>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub amount: Option<Option<Amount>>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub unit: Option<Option<Unit>>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub article_id: Option<Option<<Article as Identifiable>::Id>>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub alternative_article_ids: Option<Option<Vec<<Article as Identifiable>::Id>>>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub category_id: Option<Option<<Category as Identifiable>::Id>>,
+}
+impl_api_traits!(ItemPatchV1);
+
+impl ItemPatchV1 {
+  /// A patch checking or unchecking the item
+  pub fn checked(checked: bool) -> Self {
+    Self {
+      checked: Some(checked),
+      ..Default::default()
+    }
+  }
+
+  /// Whether the patch changes nothing but `checked`, which is all shoppers are allowed to do
+  pub fn only_checks(&self) -> bool {
+    matches!(
+      self,
+      ItemPatchV1 {
+        name: None,
+        checked: _,
+        amount: None,
+        unit: None,
+        article_id: None,
+        alternative_article_ids: None,
+        category_id: None,
+      }
+    )
+  }
+
+  pub fn apply(&self, item: &mut Item) {
+    apply(&mut item.name, &self.name);
+    apply(&mut item.checked, &self.checked);
+    apply(&mut item.amount, &self.amount);
+    apply(&mut item.unit, &self.unit);
+    apply(&mut item.article_id, &self.article_id);
+    apply(&mut item.alternative_article_ids, &self.alternative_article_ids);
+    apply(&mut item.category_id, &self.category_id);
+  }
+}
+
+/// Changes the metadata of a list; `items` only reorders or removes items, new ones are added through their endpoints
+#[derive(Debug, Default, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct ListPatchV1 {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub shop: Option<Option<<Shop as Identifiable>::Id>>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub image_id: Option<Option<u64>>,
+  /// Has to be a subset of the current items in any order, the items left out are moved into the trash
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub items: Option<Vec<<Item as Identifiable>::Id>>,
+}
+impl_api_traits!(ListPatchV1);
+
+impl ListPatchV1 {
+  pub fn apply(&self, list: &mut List) {
+    apply(&mut list.name, &self.name);
+    apply(&mut list.shop, &self.shop);
+    apply(&mut list.image_id, &self.image_id);
+    apply(&mut list.items, &self.items);
+  }
+}
+
+#[derive(Debug, Default, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct ArticlePatchV1 {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub description: Option<Option<String>>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub image_id: Option<Option<u32>>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub shops: Option<Option<Vec<<Shop as Identifiable>::Id>>>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub category_id: Option<Option<<Category as Identifiable>::Id>>,
+}
+impl_api_traits!(ArticlePatchV1);
+
+impl ArticlePatchV1 {
+  pub fn apply(&self, article: &mut Article) {
+    apply(&mut article.name, &self.name);
+    apply(&mut article.description, &self.description);
+    apply(&mut article.image_id, &self.image_id);
+    apply(&mut article.shops, &self.shops);
+    apply(&mut article.category_id, &self.category_id);
+  }
+}
+
+#[derive(Debug, Default, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct ShopPatchV1 {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+  pub image_id: Option<Option<u32>>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub layout: Option<Vec<ShopSection>>,
+}
+impl_api_traits!(ShopPatchV1);
+
+impl ShopPatchV1 {
+  pub fn apply(&self, shop: &mut Shop) {
+    apply(&mut shop.name, &self.name);
+    apply(&mut shop.image_id, &self.image_id);
+    apply(&mut shop.layout, &self.layout);
+  }
+}
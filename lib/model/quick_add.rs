@@ -0,0 +1,206 @@
+use super::item::Item;
+use super::unit::{Amount, Unit};
+
+/**
+Parses a line like `2kg Mehl`, `3x Joghurt`, `500 g Hack` or `Milch 1,5l` into a new item.
+The amount may come before or after the name, with or without a space before the unit; German and English unit
+names are understood, and decimals may use a point or a comma. Lines without an amount become items with just a name.
+*/
+pub fn parse_item(line: &str) -> Item {
+  let tokens: Vec<&str> = line.split_whitespace().collect();
+
+  let (amount, unit, name) = match parse_leading(&tokens) {
+    Some((amount, unit, rest)) if !rest.is_empty() => (Some(amount), unit, rest),
+    _ => match parse_trailing(&tokens) {
+      Some((amount, unit, rest)) if !rest.is_empty() => (Some(amount), unit, rest),
+      // a line of only an amount is more likely a name like "7up" than an item without a name
+      _ => (None, None, &tokens[..]),
+    },
+  };
+
+  Item {
+    id: 0,
+    checked: false,
+    name: name.join(" "),
+    amount,
+    unit,
+    article_id: None,
+    alternative_article_ids: None,
+    category_id: None,
+  }
+}
+
+/// Parses every non-empty line of the text into an item, see [`parse_item`]
+pub fn parse_items(text: &str) -> Vec<Item> {
+  text
+    .lines()
+    .filter(|line| !line.trim().is_empty())
+    .map(parse_item)
+    .collect()
+}
+
+type Quantity<'a> = (Amount, Option<Unit>, &'a [&'a str]);
+
+/// An amount at the start, e.g. `2kg`, `2 kg`, `3x`, `3 x` or just `3`
+fn parse_leading<'a>(tokens: &'a [&'a str]) -> Option<Quantity<'a>> {
+  let (first, rest) = tokens.split_first()?;
+  let (amount, suffix) = split_amount(first)?;
+
+  if !suffix.is_empty() {
+    return Some((amount, parse_unit(suffix)?, rest));
+  }
+
+  match rest.split_first() {
+    Some((word, name)) => match parse_unit(word) {
+      Some(unit) => Some((amount, unit, name)),
+      None => Some((amount, None, rest)),
+    },
+    None => Some((amount, None, rest)),
+  }
+}
+
+/// An amount at the end, e.g. `1,5l`, `1,5 l` or `x3`
+fn parse_trailing<'a>(tokens: &'a [&'a str]) -> Option<Quantity<'a>> {
+  let (last, rest) = tokens.split_last()?;
+
+  // `x3`, `×3`
+  if let Some(count) = last.strip_prefix(['x', 'X', '×']) {
+    if let Some((amount, "")) = split_amount(count) {
+      return Some((amount, None, rest));
+    }
+  }
+
+  if let Some((amount, suffix)) = split_amount(last) {
+    return match suffix {
+      "" => Some((amount, None, rest)),
+      suffix => Some((amount, parse_unit(suffix)?, rest)),
+    };
+  }
+
+  // `1,5 l`
+  let (number, rest) = rest.split_last()?;
+  match split_amount(number)? {
+    (amount, "") => Some((amount, parse_unit(last)?, rest)),
+    _ => None,
+  }
+}
+
+/// Splits a token like `1,5l` into the amount and the rest
+fn split_amount(token: &str) -> Option<(Amount, &str)> {
+  let end = token
+    .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == ',' || c == '/'))
+    .unwrap_or(token.len());
+  let (number, suffix) = token.split_at(end);
+  // a trailing separator belongs to the sentence rather than the number, e.g. in "2, Äpfel"
+  let number = number.trim_end_matches(['.', ',']);
+  if !number.starts_with(|c: char| c.is_ascii_digit()) {
+    return None;
+  }
+
+  Some((number.parse().ok()?, suffix))
+}
+
+/**
+The unit a word stands for. The outer `None` means the word is not a unit,
+the inner one that it only marks a count, like the `x` in `3x`.
+*/
+fn parse_unit(word: &str) -> Option<Option<Unit>> {
+  let unit = match word.to_lowercase().trim_end_matches('.') {
+    "x" | "×" | "mal" => return Some(None),
+    "mg" | "milligramm" | "milligram" | "milligrams" => Unit::MilliGram,
+    "g" | "gr" | "gramm" | "gram" | "grams" | "gramme" => Unit::Gram,
+    "kg" | "kilo" | "kilos" | "kilogramm" | "kilogram" | "kilograms" => Unit::KiloGram,
+    "ml" | "milliliter" | "millilitre" | "milliliters" | "millilitres" => Unit::MilliLiter,
+    "cl" | "zentiliter" | "centiliter" | "centilitre" => Unit::CentiLiter,
+    "l" | "ltr" | "liter" | "litre" | "liters" | "litres" => Unit::Liter,
+    "stk" | "st" | "stück" | "pc" | "pcs" | "piece" | "pieces" => Unit::Pieces,
+    "dutzend" | "dozen" => Unit::Dozen,
+    // packages are named canonically, so that e.g. `2 Pck.` and `1 Packung` can be merged
+    "packung" | "packungen" | "pck" | "pkg" | "pack" | "packs" | "package" | "packages" | "packet" | "packets" => {
+      Unit::Package("Packung".to_owned())
+    }
+    "flasche" | "flaschen" | "fl" | "bottle" | "bottles" => Unit::Package("Flasche".to_owned()),
+    "dose" | "dosen" | "can" | "cans" => Unit::Package("Dose".to_owned()),
+    "glas" | "gläser" | "jar" | "jars" => Unit::Package("Glas".to_owned()),
+    "becher" | "cup" | "cups" => Unit::Package("Becher".to_owned()),
+    "tüte" | "tüten" | "bag" | "bags" => Unit::Package("Tüte".to_owned()),
+    _ => return None,
+  };
+
+  Some(Some(unit))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parsed(line: &str) -> (Option<Amount>, Option<Unit>, String) {
+    let item = parse_item(line);
+    (item.amount, item.unit, item.name)
+  }
+
+  fn amount(s: &str) -> Option<Amount> {
+    Some(s.parse().unwrap())
+  }
+
+  #[test]
+  fn parses_leading_amounts() {
+    assert_eq!(parsed("2kg Mehl"), (amount("2"), Some(Unit::KiloGram), "Mehl".into()));
+    assert_eq!(parsed("500 g Hack"), (amount("500"), Some(Unit::Gram), "Hack".into()));
+    assert_eq!(parsed("3x Joghurt"), (amount("3"), None, "Joghurt".into()));
+    assert_eq!(parsed("3 x Joghurt"), (amount("3"), None, "Joghurt".into()));
+    assert_eq!(parsed("2 rote Paprika"), (amount("2"), None, "rote Paprika".into()));
+    assert_eq!(parsed("2, Äpfel"), (amount("2"), None, "Äpfel".into()));
+  }
+
+  #[test]
+  fn parses_trailing_amounts() {
+    assert_eq!(parsed("Milch 1,5l"), (amount("1.5"), Some(Unit::Liter), "Milch".into()));
+    assert_eq!(
+      parsed("Milch 1.5 l"),
+      (amount("1.5"), Some(Unit::Liter), "Milch".into())
+    );
+    assert_eq!(parsed("Eier x6"), (amount("6"), None, "Eier".into()));
+    assert_eq!(parsed("Eier 6"), (amount("6"), None, "Eier".into()));
+  }
+
+  #[test]
+  fn names_packages_canonically() {
+    let package = |name: &str| Some(Unit::Package(name.into()));
+
+    assert_eq!(
+      parsed("2 Pck. Butter"),
+      (amount("2"), package("Packung"), "Butter".into())
+    );
+    assert_eq!(
+      parsed("1 Packung Butter"),
+      (amount("1"), package("Packung"), "Butter".into())
+    );
+    assert_eq!(
+      parsed("Cola 2 Flaschen"),
+      (amount("2"), package("Flasche"), "Cola".into())
+    );
+    assert_eq!(
+      parsed("3 cans Tomatoes"),
+      (amount("3"), package("Dose"), "Tomatoes".into())
+    );
+  }
+
+  #[test]
+  fn keeps_names_without_amounts() {
+    assert_eq!(parsed("7up"), (None, None, "7up".into()));
+    assert_eq!(parsed("7up 2"), (amount("2"), None, "7up".into()));
+    assert_eq!(parsed("3"), (None, None, "3".into()));
+    assert_eq!(parsed("Tomaten"), (None, None, "Tomaten".into()));
+    assert_eq!(parsed("2 Liter"), (None, None, "2 Liter".into()));
+  }
+
+  #[test]
+  fn skips_empty_lines() {
+    let names: Vec<String> = parse_items("2kg Mehl\n\n  \nMilch 1,5l\n")
+      .into_iter()
+      .map(|item| item.name)
+      .collect();
+    assert_eq!(names, ["Mehl", "Milch"]);
+  }
+}
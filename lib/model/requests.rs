@@ -2,7 +2,8 @@ use rkyv::{Archive, Deserialize, Serialize};
 
 use super::item::Item;
 use super::list::List;
-use super::Identifiable;
+use super::shop::ShopSection;
+use super::{Identifiable, Role};
 use crate::impl_api_traits;
 
 /// Command-pattern based structs to be used as request parameters
@@ -11,14 +12,30 @@ use crate::impl_api_traits;
 pub struct StoreItemAttached {
   pub item: Item,
   pub list_id: u64,
+  /// Adds the amount to an unchecked duplicate of the item in the list, if there is one, instead of adding a new item
+  #[serde(default)]
+  pub merge: bool,
 }
 impl_api_traits!(StoreItemAttached);
 
+/// Text with one item per line, e.g. `2kg Mehl` or `Milch 1,5l`
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct QuickAddV1 {
+  pub text: String,
+  /// See [`StoreItemAttached::merge`]
+  #[serde(default)]
+  pub merge: bool,
+}
+impl_api_traits!(QuickAddV1);
+
 #[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
 #[archive_attr(derive(bytecheck::CheckBytes))]
 pub struct RegisterUserV1 {
   pub name: String,
   pub password: String,
+  /// Required unless the server allows open registration
+  pub invite_code: Option<String>,
 }
 impl_api_traits!(RegisterUserV1);
 
@@ -30,6 +47,122 @@ pub struct LoginUserV1 {
 }
 impl_api_traits!(LoginUserV1);
 
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CreateApiTokenV1 {
+  pub name: String,
+  /// Unix timestamp in seconds; tokens without expiry stay valid until they are revoked
+  pub expires: Option<i64>,
+  pub read_only: bool,
+}
+impl_api_traits!(CreateApiTokenV1);
+
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CreateInviteV1 {
+  /// Seconds the invite stays valid; defaults to and is capped by the server configuration
+  pub valid_for: Option<u64>,
+  /// Shared list the invited user gets access to
+  pub list_id: Option<u64>,
+  /// Household the invited user joins
+  pub household_id: Option<u64>,
+  /// Ignored unless a list or household is given
+  pub role: Role,
+}
+impl_api_traits!(CreateInviteV1);
+
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CreateShareLinkV1 {
+  /// Unix timestamp in seconds; links without expiry stay valid until they are revoked
+  pub expires: Option<i64>,
+  pub allow_check: bool,
+}
+impl_api_traits!(CreateShareLinkV1);
+
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CreateWebhookV1 {
+  /// Has to be an http or https URL
+  pub url: String,
+  /// Restricts the webhook to a single list instead of all lists of the account
+  pub list_id: Option<<List as Identifiable>::Id>,
+}
+impl_api_traits!(CreateWebhookV1);
+
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct ReorderItemV1 {
+  /// Index within the items of the list, positions past the end move the item to the end
+  pub position: u64,
+}
+impl_api_traits!(ReorderItemV1);
+
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct MoveItemV1 {
+  pub target_list_id: <List as Identifiable>::Id,
+  /// Index within the items of the target list; the item is appended if this is not set
+  pub position: Option<u64>,
+}
+impl_api_traits!(MoveItemV1);
+
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CreateCategoryV1 {
+  pub name: String,
+}
+impl_api_traits!(CreateCategoryV1);
+
+/// Replaces the layout of a shop
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct UpdateShopLayoutV1 {
+  pub layout: Vec<ShopSection>,
+}
+impl_api_traits!(UpdateShopLayoutV1);
+
+/// The previous owner stays on as editor
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct TransferOwnershipV1 {
+  /// Name of the user to become the new owner
+  pub new_owner: String,
+}
+impl_api_traits!(TransferOwnershipV1);
+
+/// Redeems an invite for an existing account
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct RedeemInviteV1 {
+  pub code: String,
+}
+impl_api_traits!(RedeemInviteV1);
+
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CreateHouseholdV1 {
+  pub name: String,
+}
+impl_api_traits!(CreateHouseholdV1);
+
+/// Query parameters the identity provider redirected the user with
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct OidcCallbackV1 {
+  pub code: String,
+  pub state: String,
+}
+impl_api_traits!(OidcCallbackV1);
+
+/// Either a current TOTP code or one of the one-time recovery codes
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct VerifyTotpV1 {
+  pub code: String,
+}
+impl_api_traits!(VerifyTotpV1);
+
 
 #[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
 #[archive_attr(derive(bytecheck::CheckBytes))]
@@ -39,7 +172,11 @@ pub struct DeleteItem {
 }
 impl_api_traits!(DeleteItem);
 
+/// Adds all items to the list at once, their ids are ignored
+#[derive(Debug, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
 pub struct MassStoreItems {
   pub items: Vec<Item>,
   pub list_id: <List as Identifiable>::Id,
-}
\ No newline at end of file
+}
+impl_api_traits!(MassStoreItems);
\ No newline at end of file
@@ -0,0 +1,50 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::list::List;
+use super::{HasTypeDenominator, Identifiable};
+use crate::impl_api_traits;
+
+/// Metadata of a link granting access to a list without an account. Like api tokens, the secret can only be shown once.
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct ShareLink {
+  pub id: <ShareLink as Identifiable>::Id,
+  pub list_id: <List as Identifiable>::Id,
+  /// Unix timestamp in seconds
+  pub created: i64,
+  /// Unix timestamp in seconds after which the link is rejected
+  pub expires: Option<i64>,
+  /// Allows checking and unchecking items in addition to reading the list
+  pub allow_check: bool,
+}
+
+impl_api_traits!(ShareLink);
+
+impl Identifiable for ShareLink {
+  type Id = u64;
+}
+
+/// A [`ShareLink`] as stored by the backend
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct StoredShareLink {
+  pub link: ShareLink,
+  /// SHA-256 digest of the secret part of the token
+  pub secret_hash: Vec<u8>,
+}
+
+impl_api_traits!(StoredShareLink);
+
+unsafe impl HasTypeDenominator for StoredShareLink {
+  const DENOMINATOR: u64 = 3;
+}
+
+/// Returned once when creating a link. The list is available at `/share/<token>`.
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CreatedShareLink {
+  pub link: ShareLink,
+  pub token: String,
+}
+
+impl_api_traits!(CreatedShareLink);
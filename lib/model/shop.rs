@@ -1,5 +1,8 @@
 use rkyv::{Archive, Deserialize, Serialize};
 
+use super::article::Article;
+use super::category::Category;
+use super::list::List;
 use super::Identifiable;
 use crate::impl_api_traits;
 
@@ -9,6 +12,8 @@ pub struct Shop {
   pub id: <Shop as Identifiable>::Id,
   pub name: String,
   pub image_id: Option<u32>,
+  /// The sections of the shop in the order they are walked through
+  pub layout: Vec<ShopSection>,
 }
 
 impl Identifiable for Shop {
@@ -16,3 +21,67 @@ impl Identifiable for Shop {
 }
 
 impl_api_traits!(Shop);
+
+impl Shop {
+  /**
+  The position of the section an item belongs to, if the layout covers it.
+  Sections listing the article take precedence over sections listing its category.
+  */
+  pub fn section_of(
+    &self,
+    article_id: Option<<Article as Identifiable>::Id>,
+    category_id: Option<<Category as Identifiable>::Id>,
+  ) -> Option<usize> {
+    article_id
+      .and_then(|article_id| {
+        self
+          .layout
+          .iter()
+          .position(|section| section.article_ids.contains(&article_id))
+      })
+      .or_else(|| {
+        category_id.and_then(|category_id| {
+          self
+            .layout
+            .iter()
+            .position(|section| section.category_ids.contains(&category_id))
+        })
+      })
+  }
+}
+
+/// An aisle or other section of a shop, holding the listed categories and articles
+#[derive(Debug, Clone, PartialEq, Eq, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes, Debug))]
+pub struct ShopSection {
+  pub name: String,
+  pub category_ids: Vec<<Category as Identifiable>::Id>,
+  pub article_ids: Vec<<Article as Identifiable>::Id>,
+}
+
+/// An item checked off while its list was associated with a shop, from which the walking order is learned
+#[derive(Debug, Clone, Archive, Serialize, Deserialize, serde::Serialize, serde::Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes, Debug))]
+pub struct CheckOff {
+  pub list_id: <List as Identifiable>::Id,
+  pub article_id: <Article as Identifiable>::Id,
+  pub timestamp: i64,
+}
+
+/// How the items of a list are sorted for its shop
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShopOrder {
+  /// By the section of the layout of the shop
+  #[default]
+  Layout,
+  /// By the order in which items were checked off in the shop before
+  Learned,
+}
+
+/// Query parameters for fetching a list sorted for its shop
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SortedListQuery {
+  #[serde(default)]
+  pub order: ShopOrder,
+}
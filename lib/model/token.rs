@@ -0,0 +1,51 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::user::User;
+use super::{HasTypeDenominator, Identifiable};
+use crate::impl_api_traits;
+
+/// Metadata of a personal api token. The secret itself is never stored, so it can only be shown once on creation.
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct ApiToken {
+  pub id: <ApiToken as Identifiable>::Id,
+  pub name: String,
+  /// Unix timestamp in seconds
+  pub created: i64,
+  /// Unix timestamp in seconds after which the token is rejected
+  pub expires: Option<i64>,
+  /// Read-only tokens may only be used for GET requests
+  pub read_only: bool,
+}
+
+impl_api_traits!(ApiToken);
+
+impl Identifiable for ApiToken {
+  type Id = u64;
+}
+
+/// An [`ApiToken`] as stored by the backend
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct StoredApiToken {
+  pub token: ApiToken,
+  pub user_id: <User as Identifiable>::Id,
+  /// SHA-256 digest of the secret part of the token
+  pub secret_hash: Vec<u8>,
+}
+
+impl_api_traits!(StoredApiToken);
+
+unsafe impl HasTypeDenominator for StoredApiToken {
+  const DENOMINATOR: u64 = 1;
+}
+
+/// Returned once when creating a token. `secret` is to be sent as `Authorization: Bearer <secret>`.
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CreatedApiToken {
+  pub token: ApiToken,
+  pub secret: String,
+}
+
+impl_api_traits!(CreatedApiToken);
@@ -0,0 +1,37 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use crate::impl_api_traits;
+
+/// The second factor of a user as stored by the backend. The secret is only accepted for logins once the enrolment has been confirmed.
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct TotpCredentials {
+  pub secret: Vec<u8>,
+  pub confirmed: bool,
+  /// Codes are only valid once, so the last accepted time step is remembered to prevent replaying them
+  pub last_used_step: u64,
+  /// SHA-256 digests of the remaining one-time recovery codes
+  pub recovery_codes: Vec<Vec<u8>>,
+}
+
+impl_api_traits!(TotpCredentials);
+
+/// Returned when starting the enrolment. The uri is meant to be displayed as a QR code for authenticator apps.
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct TotpEnrolment {
+  /// Base32 encoded secret for manual entry
+  pub secret: String,
+  pub otpauth_uri: String,
+}
+
+impl_api_traits!(TotpEnrolment);
+
+/// One-time recovery codes; they are only ever shown once when confirming the enrolment.
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct RecoveryCodes {
+  pub codes: Vec<String>,
+}
+
+impl_api_traits!(RecoveryCodes);
@@ -0,0 +1,42 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::item::Item;
+use super::list::List;
+use super::user::User;
+use super::{HasTypeDenominator, Identifiable};
+use crate::impl_api_traits;
+
+/// A deleted item or list, which can be restored until it is purged
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct TrashEntry {
+  /// Same as the id of the deleted object
+  pub id: <TrashEntry as Identifiable>::Id,
+  /// Unix timestamp in seconds
+  pub deleted_at: i64,
+  pub deleted_by: <User as Identifiable>::Id,
+  pub object: TrashedObject,
+}
+
+impl_api_traits!(TrashEntry);
+
+impl Identifiable for TrashEntry {
+  type Id = u64;
+}
+
+unsafe impl HasTypeDenominator for TrashEntry {
+  const DENOMINATOR: u64 = 4;
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub enum TrashedObject {
+  Item {
+    item: Item,
+    list_id: <List as Identifiable>::Id,
+    /// Index in the items of the list, so restoring puts it back where it was
+    position: u64,
+  },
+  /// The items stay attached to the list while it is in the trash
+  List(List),
+}
@@ -0,0 +1,409 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use rkyv::{Archive, Deserialize, Serialize};
+
+/// Largest number of decimal places an amount is displayed with before falling back to a fraction
+const MAX_DECIMALS: u32 = 6;
+
+/**
+A non-negative rational amount, e.g. 1.5 or 1/3, which is always stored in lowest terms.
+Through serde it is represented as its display string, e.g. `"1.5"` or `"1/3"`; plain numbers are accepted as well.
+*/
+#[derive(Archive, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[archive_attr(derive(Debug))]
+pub struct Amount {
+  numerator: u64,
+  denominator: u64,
+}
+
+impl Amount {
+  pub const ZERO: Amount = Amount {
+    numerator: 0,
+    denominator: 1,
+  };
+
+  /// `None` if the denominator is zero
+  pub fn new(numerator: u64, denominator: u64) -> Option<Self> {
+    if denominator == 0 {
+      return None;
+    }
+
+    let divisor = gcd(numerator, denominator);
+    Some(Self {
+      numerator: numerator / divisor,
+      denominator: denominator / divisor,
+    })
+  }
+
+  pub fn numerator(&self) -> u64 {
+    self.numerator
+  }
+
+  pub fn denominator(&self) -> u64 {
+    self.denominator
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.numerator == 0
+  }
+
+  pub fn as_f64(&self) -> f64 {
+    self.numerator as f64 / self.denominator as f64
+  }
+
+  /// `None` on overflow
+  pub fn checked_add(self, other: Amount) -> Option<Amount> {
+    let divisor = gcd(self.denominator, other.denominator);
+    let denominator = (self.denominator / divisor).checked_mul(other.denominator)?;
+    let numerator = self
+      .numerator
+      .checked_mul(denominator / self.denominator)?
+      .checked_add(other.numerator.checked_mul(denominator / other.denominator)?)?;
+
+    Amount::new(numerator, denominator)
+  }
+
+  /// `None` on overflow
+  pub fn checked_mul(self, other: Amount) -> Option<Amount> {
+    // cancelling crosswise first keeps the intermediate values small
+    let a = gcd(self.numerator, other.denominator);
+    let b = gcd(other.numerator, self.denominator);
+    let numerator = (self.numerator / a).checked_mul(other.numerator / b)?;
+    let denominator = (self.denominator / b).checked_mul(other.denominator / a)?;
+
+    Amount::new(numerator, denominator)
+  }
+
+  /// The number of decimal places needed to display the amount exactly, if it has a finite decimal representation
+  fn decimals(&self) -> Option<u32> {
+    (0..=MAX_DECIMALS).find(|decimals| 10u64.pow(*decimals) % self.denominator == 0)
+  }
+}
+
+impl From<u64> for Amount {
+  fn from(value: u64) -> Self {
+    Self {
+      numerator: value,
+      denominator: 1,
+    }
+  }
+}
+
+impl Display for Amount {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    let whole = self.numerator / self.denominator;
+    let remainder = self.numerator % self.denominator;
+
+    match self.decimals() {
+      Some(0) => write!(f, "{whole}"),
+      Some(decimals) => {
+        let fraction = remainder * (10u64.pow(decimals) / self.denominator);
+        write!(f, "{whole}.{fraction:0width$}", width = decimals as usize)
+      }
+      None => write!(f, "{}/{}", self.numerator, self.denominator),
+    }
+  }
+}
+
+/// Archived amounts are checked to be in lowest terms, as a zero denominator would make [`Display`] and the arithmetic
+/// panic and an amount that is not reduced would not be equal to the same amount in lowest terms
+impl<C: ?Sized> bytecheck::CheckBytes<C> for ArchivedAmount {
+  type Error = InvalidAmountError;
+
+  unsafe fn check_bytes<'a>(value: *const Self, _: &mut C) -> Result<&'a Self, Self::Error> {
+    let amount = &*value;
+    let (numerator, denominator) = (amount.numerator.value(), amount.denominator.value());
+
+    match denominator != 0 && gcd(numerator, denominator) == 1 {
+      true => Ok(amount),
+      false => Err(InvalidAmountError),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidAmountError;
+
+impl Display for InvalidAmountError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.write_str("invalid amount, the denominator must not be zero and the fraction must be in lowest terms")
+  }
+}
+
+impl std::error::Error for InvalidAmountError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseAmountError;
+
+impl Display for ParseAmountError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.write_str("invalid amount, expected a number like 2, 1.5, 0,5 or 1/3")
+  }
+}
+
+impl std::error::Error for ParseAmountError {}
+
+impl FromStr for Amount {
+  type Err = ParseAmountError;
+
+  /// Accepts whole numbers, decimals with a point or comma and fractions
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim();
+
+    if let Some((numerator, denominator)) = s.split_once('/') {
+      let numerator = numerator.trim().parse().map_err(|_| ParseAmountError)?;
+      let denominator = denominator.trim().parse().map_err(|_| ParseAmountError)?;
+      return Amount::new(numerator, denominator).ok_or(ParseAmountError);
+    }
+
+    let (whole, fraction) = s.split_once(['.', ',']).unwrap_or((s, ""));
+    if (whole.is_empty() && fraction.is_empty()) || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+      return Err(ParseAmountError);
+    }
+    let whole: u64 = match whole {
+      "" => 0,
+      whole => whole.parse().map_err(|_| ParseAmountError)?,
+    };
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > MAX_DECIMALS as usize {
+      return Err(ParseAmountError);
+    }
+
+    let denominator = 10u64.pow(fraction.len() as u32);
+    let fraction: u64 = match fraction {
+      "" => 0,
+      fraction => fraction.parse().map_err(|_| ParseAmountError)?,
+    };
+    let numerator = whole
+      .checked_mul(denominator)
+      .and_then(|whole| whole.checked_add(fraction))
+      .ok_or(ParseAmountError)?;
+
+    Amount::new(numerator, denominator).ok_or(ParseAmountError)
+  }
+}
+
+impl serde::Serialize for Amount {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+impl<'de> serde::Deserialize<'de> for Amount {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct AmountVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for AmountVisitor {
+      type Value = Amount;
+
+      fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("a non-negative number or a string like \"1.5\" or \"1/3\"")
+      }
+
+      fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Amount, E> {
+        Ok(Amount::from(value))
+      }
+
+      fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<Amount, E> {
+        // the shortest representation of the float is what the client most likely meant
+        self.visit_str(&value.to_string())
+      }
+
+      fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Amount, E> {
+        value.parse().map_err(E::custom)
+      }
+    }
+
+    deserializer.deserialize_any(AmountVisitor)
+  }
+}
+
+/// What a unit measures; only amounts of the same dimension can be converted into each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dimension {
+  Mass,
+  Volume,
+  Count,
+}
+
+/**
+Unit of the amount of an item.
+New variants are added at the end, as the archived form of stored items depends on the order.
+*/
+#[derive(PartialEq, Eq, Hash, Archive, Serialize, Deserialize, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub enum Unit {
+  Gram,
+  KiloGram,
+  Pieces,
+  FreeForm(String),
+  MilliGram,
+  MilliLiter,
+  CentiLiter,
+  Liter,
+  Dozen,
+  /// Packages, bottles, cans etc. differ in size, so they can not be converted into any other unit
+  Package(String),
+}
+
+impl Unit {
+  /// The dimension of the unit and how many base units (g, ml, pieces) one of it is
+  pub fn base(&self) -> Option<(Dimension, Amount)> {
+    let (dimension, numerator, denominator) = match self {
+      Unit::MilliGram => (Dimension::Mass, 1, 1000),
+      Unit::Gram => (Dimension::Mass, 1, 1),
+      Unit::KiloGram => (Dimension::Mass, 1000, 1),
+      Unit::MilliLiter => (Dimension::Volume, 1, 1),
+      Unit::CentiLiter => (Dimension::Volume, 10, 1),
+      Unit::Liter => (Dimension::Volume, 1000, 1),
+      Unit::Pieces => (Dimension::Count, 1, 1),
+      Unit::Dozen => (Dimension::Count, 12, 1),
+      Unit::FreeForm(_) | Unit::Package(_) => return None,
+    };
+
+    Some((dimension, Amount::new(numerator, denominator)?))
+  }
+
+  pub fn dimension(&self) -> Option<Dimension> {
+    self.base().map(|(dimension, _)| dimension)
+  }
+
+  /// Whether amounts can be converted between both units; every unit is compatible with itself
+  pub fn is_compatible(&self, other: &Unit) -> bool {
+    self == other || matches!((self.dimension(), other.dimension()), (Some(a), Some(b)) if a == b)
+  }
+
+  /// Converts the amount into the other unit, `None` if the units are incompatible or the result overflows
+  pub fn convert(&self, amount: Amount, to: &Unit) -> Option<Amount> {
+    if self == to {
+      return Some(amount);
+    }
+
+    let (from_dimension, from_factor) = self.base()?;
+    let (to_dimension, to_factor) = to.base()?;
+    if from_dimension != to_dimension {
+      return None;
+    }
+
+    let to_factor = Amount::new(to_factor.denominator(), to_factor.numerator())?;
+    amount.checked_mul(from_factor)?.checked_mul(to_factor)
+  }
+
+  /// The abbreviation shown after amounts
+  pub fn symbol(&self) -> &str {
+    match self {
+      Unit::MilliGram => "mg",
+      Unit::Gram => "g",
+      Unit::KiloGram => "kg",
+      Unit::MilliLiter => "ml",
+      Unit::CentiLiter => "cl",
+      Unit::Liter => "l",
+      Unit::Pieces => "pcs",
+      Unit::Dozen => "dozen",
+      Unit::FreeForm(name) | Unit::Package(name) => name,
+    }
+  }
+}
+
+impl Display for Unit {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.write_str(self.symbol())
+  }
+}
+
+/// Formats an amount with its unit for display, e.g. `1.5 kg`, `3` or `pcs`
+pub fn format_amount(amount: Option<Amount>, unit: Option<&Unit>) -> Option<String> {
+  match (amount, unit) {
+    (Some(amount), Some(unit)) => Some(format!("{amount} {unit}")),
+    (Some(amount), None) => Some(amount.to_string()),
+    (None, Some(unit)) => Some(unit.to_string()),
+    (None, None) => None,
+  }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+  while b != 0 {
+    (a, b) = (b, a % b);
+  }
+  a
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn amount(s: &str) -> Amount {
+    s.parse().unwrap()
+  }
+
+  #[test]
+  fn parses_numbers_decimals_and_fractions() {
+    assert_eq!(amount("2"), Amount::from(2));
+    assert_eq!(amount("1.5"), Amount::new(3, 2).unwrap());
+    assert_eq!(amount("0,25"), Amount::new(1, 4).unwrap());
+    assert_eq!(amount(".5"), Amount::new(1, 2).unwrap());
+    assert_eq!(amount("2.50"), Amount::new(5, 2).unwrap());
+    assert_eq!(amount(" 2/6 "), Amount::new(1, 3).unwrap());
+
+    for invalid in ["", ".", "1/0", "-1", "1.5.", "1.2345678", "a", "1e3"] {
+      assert_eq!(invalid.parse::<Amount>(), Err(ParseAmountError), "{invalid:?}");
+    }
+  }
+
+  #[test]
+  fn new_reduces_to_lowest_terms() {
+    assert_eq!(Amount::new(4, 8), Amount::new(1, 2));
+    assert_eq!(Amount::new(0, 5), Some(Amount::ZERO));
+    assert_eq!(Amount::new(1, 0), None);
+  }
+
+  #[test]
+  fn displays_decimals_or_fractions() {
+    assert_eq!(Amount::from(3).to_string(), "3");
+    assert_eq!(amount("1.5").to_string(), "1.5");
+    assert_eq!(amount("0.05").to_string(), "0.05");
+    assert_eq!(amount("1/3").to_string(), "1/3");
+    assert_eq!(amount("7/4").to_string(), "1.75");
+  }
+
+  #[test]
+  fn adds_amounts() {
+    assert_eq!(amount("1/3").checked_add(amount("1/6")), Some(amount("1/2")));
+    assert_eq!(amount("1.5").checked_add(Amount::from(2)), Some(amount("3.5")));
+    assert_eq!(Amount::from(u64::MAX).checked_add(Amount::from(1)), None);
+  }
+
+  #[test]
+  fn converts_within_a_dimension() {
+    assert_eq!(
+      Unit::KiloGram.convert(amount("1.5"), &Unit::Gram),
+      Some(Amount::from(1500))
+    );
+    assert_eq!(
+      Unit::MilliLiter.convert(Amount::from(250), &Unit::Liter),
+      Some(amount("0.25"))
+    );
+    assert_eq!(
+      Unit::Dozen.convert(Amount::from(2), &Unit::Pieces),
+      Some(Amount::from(24))
+    );
+    assert_eq!(Unit::Gram.convert(Amount::from(1), &Unit::Liter), None);
+    assert_eq!(
+      Unit::Package("Packung".into()).convert(Amount::from(1), &Unit::Pieces),
+      None
+    );
+  }
+
+  #[test]
+  fn rejects_archives_not_in_lowest_terms() {
+    for (numerator, denominator, valid) in [(1, 2, true), (0, 1, true), (1, 0, false), (2, 4, false), (0, 3, false)] {
+      let bytes = rkyv::to_bytes::<_, 64>(&Amount { numerator, denominator }).unwrap();
+      assert_eq!(
+        rkyv::check_archived_root::<Amount>(&bytes).is_ok(),
+        valid,
+        "{numerator}/{denominator}"
+      );
+    }
+  }
+}
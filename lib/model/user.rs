@@ -15,6 +15,25 @@ pub struct User {
 
 impl_api_traits!(User);
 
+/// Users with a second factor are only logged in after additionally verifying a code with [`crate::model::requests::VerifyTotpV1`].
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub enum LoginResponseV1 {
+  Authenticated(User),
+  TotpRequired,
+}
+
+impl_api_traits!(LoginResponseV1);
+
+/// The client has to send the user to this url and pass the code and state of the redirect back in [`crate::model::requests::OidcCallbackV1`].
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct OidcAuthorization {
+  pub authorization_url: String,
+}
+
+impl_api_traits!(OidcAuthorization);
+
 #[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
 #[archive_attr(derive(bytecheck::CheckBytes))]
 pub struct UserWithPassword {
@@ -26,9 +45,12 @@ impl_api_traits!(UserWithPassword);
 
 #[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
 #[archive_attr(derive(bytecheck::CheckBytes))]
-pub struct Password {
-  pub hash: Vec<u8>,
-  pub salt: Vec<u8>,
+pub enum Password {
+  /// Hash in the PHC string format, which also contains the algorithm, its parameters and the salt
+  Phc(String),
+  /// Raw Argon2 hash with the default parameters and its salt, as stored by earlier versions.
+  /// It is replaced by a PHC string on the next successful login.
+  Legacy { hash: Vec<u8>, salt: Vec<u8> },
 }
 
 impl Identifiable for User {
@@ -0,0 +1,92 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::activity::ActivityEvent;
+use super::list::List;
+use super::{HasTypeDenominator, Identifiable};
+use crate::impl_api_traits;
+
+/// A URL notified about changes, either of a single list or of all lists the account has access to
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct Webhook {
+  pub id: <Webhook as Identifiable>::Id,
+  pub url: String,
+  /// `None` for webhooks of the whole account
+  pub list_id: Option<<List as Identifiable>::Id>,
+  /// Unix timestamp in seconds
+  pub created: i64,
+}
+
+impl_api_traits!(Webhook);
+
+impl Identifiable for Webhook {
+  type Id = u64;
+}
+
+/// A [`Webhook`] as stored by the backend. Unlike tokens the secret is kept, as every payload is signed with it.
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct StoredWebhook {
+  pub hook: Webhook,
+  pub owner: u64,
+  pub secret: String,
+}
+
+impl_api_traits!(StoredWebhook);
+
+unsafe impl HasTypeDenominator for StoredWebhook {
+  const DENOMINATOR: u64 = 5;
+}
+
+/// Returned once when creating a webhook, the secret cannot be retrieved later on
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CreatedWebhook {
+  pub hook: Webhook,
+  /// Key of the HMAC-SHA256 signature sent along with every payload
+  pub secret: String,
+}
+
+impl_api_traits!(CreatedWebhook);
+
+/// JSON body posted to the webhook URL. Retries of a delivery carry the same `delivery_id`.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct WebhookPayload {
+  pub delivery_id: u64,
+  pub webhook_id: <Webhook as Identifiable>::Id,
+  pub list_id: <List as Identifiable>::Id,
+  pub event: ActivityEvent,
+}
+
+/// A delivery waiting to be attempted by the backend
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct PendingDelivery {
+  pub delivery_id: u64,
+  pub webhook_id: <Webhook as Identifiable>::Id,
+  /// The serialized [`WebhookPayload`], so every attempt sends the exact same body
+  pub payload: String,
+  /// Number of previous attempts
+  pub attempt: u32,
+}
+
+impl_api_traits!(PendingDelivery);
+
+/// Outcome of a single delivery attempt
+#[derive(Archive, Serialize, Deserialize, Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct WebhookDelivery {
+  pub delivery_id: u64,
+  pub webhook_id: <Webhook as Identifiable>::Id,
+  /// Starts at 1
+  pub attempt: u32,
+  /// Unix timestamp in seconds
+  pub timestamp: i64,
+  /// HTTP status of the response, if there was any
+  pub status: Option<u16>,
+  pub error: Option<String>,
+  /// Whether the delivery will be attempted again
+  pub retrying: bool,
+}
+
+impl_api_traits!(WebhookDelivery);